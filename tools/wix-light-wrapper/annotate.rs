@@ -0,0 +1,170 @@
+//! CI annotations for the warnings and errors in WiX output, so they show up on the build
+//! summary instead of only in the log.
+
+/// The workflow-command syntax a CI system understands.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnnotationStyle {
+    /// GitHub Actions: `::warning file=main.wxs,line=41::message`.
+    Github,
+    /// Azure Pipelines: `##vso[task.logissue type=warning;sourcepath=main.wxs;linenumber=41;]message`.
+    Azure,
+}
+
+impl AnnotationStyle {
+    /// `WIX_LIGHT_WRAPPER_ANNOTATIONS` as given (`github` or `azure`), or `None` when it is
+    /// something else.
+    pub fn parse(value: &str) -> Option<AnnotationStyle> {
+        match value.to_ascii_lowercase().as_str() {
+            "github" => Some(AnnotationStyle::Github),
+            "azure" => Some(AnnotationStyle::Azure),
+            _ => None,
+        }
+    }
+}
+
+/// A WiX diagnostic line: `<origin> : <error|warning> <CODE> : <message>`, where the origin is
+/// the tool (`light.exe`) or a source position (`C:\src\main.wxs(41)`).
+struct Diagnostic<'a> {
+    error: bool,
+    file: Option<&'a str>,
+    line: Option<&'a str>,
+    code: &'a str,
+    message: &'a str,
+}
+
+fn parse(line: &str) -> Option<Diagnostic<'_>> {
+    let (origin, rest) = line.trim_end().split_once(" : ")?;
+    let (head, message) = rest.split_once(" : ").unwrap_or((rest, ""));
+    let (severity, code) = head.split_once(' ')?;
+    let error = match severity.to_ascii_lowercase().as_str() {
+        "error" => true,
+        "warning" => false,
+        _ => return None,
+    };
+    if code.is_empty() || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    let origin = origin.trim();
+    let (file, line) = match origin.strip_suffix(')').and_then(|o| o.rsplit_once('(')) {
+        Some((file, line)) if !line.is_empty() && line.bytes().all(|b| b.is_ascii_digit()) => {
+            (Some(file), Some(line))
+        }
+        _ if origin.to_ascii_lowercase().ends_with(".exe") => (None, None),
+        _ => (Some(origin), None),
+    };
+    Some(Diagnostic {
+        error,
+        file,
+        line,
+        code,
+        message,
+    })
+}
+
+/// The annotation for a WiX warning or error line, or `None` for any other line.
+pub fn to_annotation(line: &str, style: AnnotationStyle) -> Option<String> {
+    let d = parse(line)?;
+    let severity = if d.error { "error" } else { "warning" };
+    let message = escape_data(&format!("{}: {}", d.code, d.message.trim()));
+    Some(match style {
+        AnnotationStyle::Github => {
+            let mut properties = Vec::new();
+            if let Some(file) = d.file {
+                properties.push(format!("file={}", escape_github_property(file)));
+            }
+            if let Some(line) = d.line {
+                properties.push(format!("line={line}"));
+            }
+            properties.push(format!("title={}", d.code));
+            format!("::{severity} {}::{message}", properties.join(","))
+        }
+        AnnotationStyle::Azure => {
+            let mut properties = format!("type={severity};");
+            if let Some(file) = d.file {
+                properties.push_str(&format!("sourcepath={};", escape_azure_property(file)));
+            }
+            if let Some(line) = d.line {
+                properties.push_str(&format!("linenumber={line};"));
+            }
+            properties.push_str(&format!("code={};", d.code));
+            format!("##vso[task.logissue {properties}]{message}")
+        }
+    })
+}
+
+/// Message text may not contain raw line breaks, and `%` starts an escape in both syntaxes.
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_github_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+fn escape_azure_property(text: &str) -> String {
+    escape_data(text).replace(';', "%3B").replace(']', "%5D")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE_WARNING: &str =
+        "C:\\src\\main.wxs(41) : warning LGHT1076 : ICE61: This product should remove only older versions of itself.";
+    const TOOL_ERROR: &str =
+        "light.exe : error LGHT0217 : Error executing ICE action 'ICE01'. 100% failed";
+
+    #[test]
+    fn github_annotations() {
+        assert_eq!(
+            to_annotation(SOURCE_WARNING, AnnotationStyle::Github).unwrap(),
+            "::warning file=C%3A\\src\\main.wxs,line=41,title=LGHT1076::LGHT1076: ICE61: This product should remove only older versions of itself."
+        );
+        assert_eq!(
+            to_annotation(TOOL_ERROR, AnnotationStyle::Github).unwrap(),
+            "::error title=LGHT0217::LGHT0217: Error executing ICE action 'ICE01'. 100%25 failed"
+        );
+    }
+
+    #[test]
+    fn azure_annotations() {
+        assert_eq!(
+            to_annotation(SOURCE_WARNING, AnnotationStyle::Azure).unwrap(),
+            "##vso[task.logissue type=warning;sourcepath=C:\\src\\main.wxs;linenumber=41;code=LGHT1076;]LGHT1076: ICE61: This product should remove only older versions of itself."
+        );
+        assert_eq!(
+            to_annotation("main.wixobj : error LGHT0094 : Unresolved reference\r\n", AnnotationStyle::Azure)
+                .unwrap(),
+            "##vso[task.logissue type=error;sourcepath=main.wixobj;code=LGHT0094;]LGHT0094: Unresolved reference"
+        );
+    }
+
+    #[test]
+    fn other_lines_are_not_annotated() {
+        for line in [
+            "Windows Installer XML Toolset Linker version 3.11.2.4516",
+            "",
+            "light.exe : info LGHT0001 : nothing",
+            "note : warning about this : that",
+            "Copyright (c) .NET Foundation and contributors. All rights reserved.",
+        ] {
+            assert_eq!(to_annotation(line, AnnotationStyle::Github), None, "{line}");
+            assert_eq!(to_annotation(line, AnnotationStyle::Azure), None, "{line}");
+        }
+    }
+
+    #[test]
+    fn parses_style_names() {
+        assert_eq!(
+            AnnotationStyle::parse("GitHub"),
+            Some(AnnotationStyle::Github)
+        );
+        assert_eq!(
+            AnnotationStyle::parse("azure"),
+            Some(AnnotationStyle::Azure)
+        );
+        assert_eq!(AnnotationStyle::parse("gitlab"), None);
+    }
+}
@@ -0,0 +1,3653 @@
+use std::cell::Cell;
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::annotate::{self, AnnotationStyle};
+use crate::baseline;
+use crate::cab_cache;
+use crate::capture;
+use crate::cli::{self, WrapperCommand};
+use crate::config::Config;
+use crate::explain;
+use crate::install;
+use crate::interrupt;
+use crate::json::Json;
+use crate::lock;
+use crate::log;
+use crate::longpath;
+use crate::msi;
+use crate::report::InvocationReport;
+use crate::response_file;
+use crate::sha256;
+use crate::sign;
+use crate::toolset::{self, WixVersion};
+use crate::validation;
+use crate::vars::Vars;
+use crate::wait;
+use crate::wrapper::{self, Tool};
+
+/// Prints a wrapper diagnostic prefixed with the tool name, e.g. `light wrapper: ...`, unless
+/// `LIGHT_WRAPPER_QUIET=1` (see `Chatter`).
+macro_rules! diag {
+    ($tool:expr, $($arg:tt)*) => {
+        if CHATTER.get() != Chatter::Quiet {
+            print_diag($tool, format_args!($($arg)*))
+        }
+    };
+}
+
+/// `diag!` for a failure of the wrapper itself, which is printed even when quiet.
+macro_rules! fatal {
+    ($tool:expr, $($arg:tt)*) => {
+        print_diag($tool, format_args!($($arg)*))
+    };
+}
+
+/// How much the wrapper says for itself: `LIGHT_WRAPPER_VERBOSE=1` tags every diagnostic with
+/// `[light-wrapper]`, telling it apart from the real tool's output, and `LIGHT_WRAPPER_QUIET=1`
+/// keeps only the failures. Set at the start of each `run`, for the thread running it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Chatter {
+    Normal,
+    Verbose,
+    Quiet,
+}
+
+thread_local! {
+    static CHATTER: Cell<Chatter> = const { Cell::new(Chatter::Normal) };
+}
+
+fn print_diag(tool: Tool, message: fmt::Arguments) {
+    match CHATTER.get() {
+        Chatter::Verbose => eprintln!("[{0}-wrapper] {0} wrapper: {message}", tool.name()),
+        _ => eprintln!("{} wrapper: {message}", tool.name()),
+    }
+}
+
+/// Entry point shared by the wrapper binaries: runs the wrapped tool for this process and exits
+/// with its code. With `tool` unset it is picked from the executable's name (see `Tool`).
+pub fn main(tool: Option<Tool>) -> ! {
+    let current_exe = match env::current_exe() {
+        Ok(p) => p,
+        Err(e) => {
+            let tool = tool.unwrap_or(Tool::LIGHT);
+            fatal!(tool, "unable to get current exe path: {e}");
+            process::exit(1);
+        }
+    };
+    let tool = tool.unwrap_or_else(|| Tool::from_exe(&current_exe));
+    interrupt::install();
+    let code = run(
+        tool,
+        &Vars::from_process(),
+        &current_exe,
+        env::args_os().skip(1).collect(),
+    );
+    process::exit(code);
+}
+
+/// Runs one wrapped invocation of `tool` and returns the exit code the wrapper should exit with.
+/// The arguments are kept as `OsString`s, so ones that aren't valid Unicode reach the real tool
+/// unchanged.
+///
+/// The invocation goes through stages, each of which may end it early (see `Stop`): the
+/// wrapper's own switches and commands (`Invocation::start`), finding and checking the real
+/// tool (`locate`), building the arguments (`plan`), running it (`launch`), and reporting on
+/// and following up the link (`record`, `finish`).
+pub fn run(tool: Tool, vars: &Vars, current_exe: &Path, cli_args: Vec<OsString>) -> i32 {
+    match invoke(tool, vars, current_exe, cli_args) {
+        Ok(code) | Err(Stop(code)) => code,
+    }
+}
+
+fn invoke(
+    tool: Tool,
+    vars: &Vars,
+    current_exe: &Path,
+    mut cli_args: Vec<OsString>,
+) -> Result<i32, Stop> {
+    let invocation = Invocation::start(tool, vars, current_exe, &mut cli_args)?;
+    let depth = invocation.recursion_depth()?;
+    let lookup = Lookup::from_env(tool, vars);
+    if let Some(disable_var) = invocation.disable_var.as_deref() {
+        if !invocation.doctor {
+            return invocation.passthrough(disable_var, &lookup, depth, &cli_args);
+        }
+    }
+    let config = invocation.load_config()?;
+    let real = invocation.locate(&lookup, &config)?;
+    invocation.check_digest(&config, &real)?;
+    let timeout = invocation.timeout(&config)?;
+    // Held until the invocation is over, whatever became of the real tool.
+    let _lock = invocation.lock()?;
+    invocation.check_startable(&real, &lookup);
+    let plan = invocation.plan(&config, &real, &lookup, depth, cli_args)?;
+    if invocation.doctor {
+        return Ok(invocation.report_plan(&plan));
+    }
+    if invocation.dry_run.active() {
+        return Ok(invocation.print_plan(&real, &plan));
+    }
+    invocation.announce(&real, &plan);
+    let launcher = invocation.launcher(&real, lookup.wine, depth, timeout)?;
+    let linked = invocation.launch(&launcher, &plan);
+    invocation.record(&launcher, &plan, &linked);
+    Ok(invocation.finish(&launcher, &plan, linked))
+}
+
+/// The invocation is over and the wrapper exits with this code, having said why.
+struct Stop(i32);
+
+impl Stop {
+    /// Prints `message` as a failure of the wrapper and stops with exit code 1.
+    fn failed(tool: Tool, message: impl fmt::Display) -> Stop {
+        fatal!(tool, "{message}");
+        Stop(1)
+    }
+}
+
+/// What every stage of one invocation shares: the tool, its settings and how it was asked to
+/// run.
+struct Invocation<'a> {
+    tool: Tool,
+    vars: &'a Vars,
+    /// The wrapper executable, after following a symbolic link to it.
+    current_exe: PathBuf,
+    verbose: bool,
+    quiet: bool,
+    dry_run: DryRun,
+    /// `doctor` goes through the same stages as a link, reporting what it finds on stdout, and
+    /// stops short of starting the real tool for anything but its version.
+    doctor: bool,
+    /// The passthrough switch that is set, if any.
+    disable_var: Option<String>,
+    /// Findings that make `doctor` fail: it reports them and carries on, where a link would
+    /// stop with the same message.
+    problems: Cell<i32>,
+}
+
+/// Where the real tool is looked for and how it is started, as far as the environment alone
+/// says; the config file may still name the real tool.
+struct Lookup {
+    override_exe: Option<PathBuf>,
+    search: wrapper::Search,
+    /// Wine executable the real tool is started through, for cross-builds off Windows.
+    wine: Option<PathBuf>,
+}
+
+impl Lookup {
+    fn from_env(tool: Tool, vars: &Vars) -> Lookup {
+        // `LIGHT_WRAPPER_REAL_EXE` (or the older `LIGHT_REAL_EXE`) names the real tool
+        // verbatim and takes precedence over the config file. Without either,
+        // `WIX_LIGHT_WRAPPER_REAL` is tried if it exists, then the sibling `light-real.exe`,
+        // `PATH`, and the WiX installer's `%WIX%\bin` (see `wrapper::resolve_real_exe`).
+        let override_exe = vars
+            .get(&tool.var("LIGHT_WRAPPER_REAL_EXE"))
+            .or_else(|| vars.get(&tool.var("LIGHT_REAL_EXE")))
+            .map(PathBuf::from);
+        let search = wrapper::Search {
+            preferred: vars
+                .get(&tool.var("WIX_LIGHT_WRAPPER_REAL"))
+                .map(PathBuf::from),
+            wix_bin: vars.get("WIX").map(|wix| Path::new(wix).join("bin")),
+            path: vars
+                .get("PATH")
+                .map(|path| env::split_paths(path).collect())
+                .unwrap_or_default(),
+        };
+        // `LIGHT_WRAPPER_WINE=1` starts the real tool through `wine` (or
+        // `LIGHT_WRAPPER_WINE_EXE`) when cross-building the MSI off Windows. Windows runs the
+        // tool directly regardless.
+        let wine =
+            (cfg!(not(windows)) && vars.enabled(&tool.var("LIGHT_WRAPPER_WINE"))).then(|| {
+                vars.get(&tool.var("LIGHT_WRAPPER_WINE_EXE"))
+                    .unwrap_or("wine")
+                    .into()
+            });
+        Lookup {
+            override_exe,
+            search,
+            wine,
+        }
+    }
+}
+
+/// The real tool an invocation forwards to.
+struct RealTool {
+    exe: PathBuf,
+    /// What the search (or its cache) found; `None` when it failed.
+    resolved: Option<wrapper::Resolved>,
+    /// The cache file and key the search was remembered under, if any.
+    cache: Option<(PathBuf, String)>,
+}
+
+impl RealTool {
+    /// Whether the real tool couldn't be found. A dry run still shows the arguments then,
+    /// under the bare name, and exits with the error code a real run would have.
+    fn unresolved(&self) -> bool {
+        self.resolved.is_none()
+    }
+}
+
+/// The argument lists of one link.
+struct Plan {
+    /// The caller's arguments after stripping, normalizing and deduplication.
+    incoming: Vec<OsString>,
+    /// The caller's arguments forwarded untouched (see `wrapper::split_opaque`).
+    opaque: Vec<OsString>,
+    /// Everything the real tool is given with all the flags injected.
+    args: Vec<OsString>,
+    /// The first link's arguments when they differ from `args`: without the injected `-sval`,
+    /// or in adaptive mode with nothing injected.
+    first_args: Option<Vec<OsString>>,
+    /// What the retry adds, e.g. `-sval` (or `-sval -sacl` in adaptive mode).
+    retry_flags: String,
+    adaptive: bool,
+}
+
+impl Plan {
+    fn first(&self) -> &[OsString] {
+        self.first_args.as_deref().unwrap_or(&self.args)
+    }
+
+    /// The arguments among `args` that the wrapper added.
+    fn injected<'a>(&self, args: &'a [OsString]) -> Vec<&'a OsString> {
+        args.iter()
+            .filter(|a| !self.incoming.contains(a) && !self.opaque.contains(a))
+            .collect()
+    }
+}
+
+/// How a link went.
+struct Linked {
+    /// What became of the last attempt.
+    result: io::Result<ExitStatus>,
+    /// Whether the link was retried with `Plan::args` after failing ICE validation.
+    fallback: bool,
+    /// Everything captured from the real tool. The last attempt's output starts at
+    /// `last_attempt`.
+    output_text: String,
+    last_attempt: usize,
+    started: SystemTime,
+    duration: Duration,
+}
+
+impl Linked {
+    /// The arguments of the last attempt.
+    fn args<'p>(&self, plan: &'p Plan) -> &'p [OsString] {
+        if self.fallback {
+            &plan.args
+        } else {
+            plan.first()
+        }
+    }
+
+    fn last_output(&self) -> &str {
+        &self.output_text[self.last_attempt..]
+    }
+}
+
+impl<'a> Invocation<'a> {
+    /// Reads the wrapper's own switches from `cli_args` and the environment, and runs any
+    /// `--wrapper-` command other than `doctor`, which stops the invocation.
+    fn start(
+        tool: Tool,
+        vars: &'a Vars,
+        current_exe: &Path,
+        cli_args: &mut Vec<OsString>,
+    ) -> Result<Invocation<'a>, Stop> {
+        let verbose_var = tool.var("LIGHT_WRAPPER_VERBOSE");
+        let quiet_var = tool.var("LIGHT_WRAPPER_QUIET");
+        let verbose = vars.enabled(&verbose_var);
+        let quiet = vars.enabled(&quiet_var);
+        CHATTER.set(Chatter::Normal);
+        let chatter = match (verbose, quiet) {
+            (true, true) => {
+                return Err(Stop::failed(
+                    tool,
+                    format!("{verbose_var} and {quiet_var} can't both be set; pick one"),
+                ));
+            }
+            (true, false) => Chatter::Verbose,
+            (false, true) => Chatter::Quiet,
+            (false, false) => Chatter::Normal,
+        };
+        CHATTER.set(chatter);
+
+        // The real tool, config and caches are found beside the wrapper the link points to when
+        // it was started through a symbolic link (see `wrapper::followed_exe`);
+        // `LIGHT_WRAPPER_NO_FOLLOW_SYMLINKS=1` keeps the link's own directory instead.
+        let current_exe = if vars.enabled(&tool.var("LIGHT_WRAPPER_NO_FOLLOW_SYMLINKS")) {
+            current_exe.to_path_buf()
+        } else {
+            wrapper::followed_exe(current_exe)
+        };
+
+        // Dry run: show exactly what would be executed, without running it.
+        // `--wrapper-dry-run` or `LIGHT_WRAPPER_DRY_RUN=1` prints a pasteable command line to
+        // stdout; `WIX_LIGHT_WRAPPER_DRY_RUN=1` prints one quoted token per line to stderr, so
+        // CI logs show the exact `Command::args` vector.
+        let mut dry_run = DryRun {
+            line: cli::take_dry_run(cli_args) || vars.enabled(&tool.var("LIGHT_WRAPPER_DRY_RUN")),
+            tokens: vars.enabled(&tool.var("WIX_LIGHT_WRAPPER_DRY_RUN")),
+        };
+        let mut doctor = false;
+        match cli::take_wrapper_command(cli_args) {
+            Ok(None) => {}
+            Ok(Some(WrapperCommand::Doctor)) => {
+                cli_args.clear();
+                dry_run = DryRun {
+                    line: false,
+                    tokens: false,
+                };
+                doctor = true;
+            }
+            Ok(Some(WrapperCommand::Version)) => {
+                println!("{}", wrapper::version_line(tool));
+                return Err(Stop(0));
+            }
+            Ok(Some(WrapperCommand::Install { dir, force })) => {
+                let result = install::install(tool, &current_exe, &dir, force);
+                return Err(Stop(install_outcome(tool, result, 0)));
+            }
+            Ok(Some(WrapperCommand::Uninstall { dir })) => {
+                let result = install::uninstall(tool, &current_exe, &dir);
+                return Err(Stop(install_outcome(
+                    tool,
+                    result,
+                    install::NOTHING_TO_UNINSTALL,
+                )));
+            }
+            Ok(Some(WrapperCommand::Verify { msi })) => {
+                return Err(match msi::verify(&msi) {
+                    Ok(info) => {
+                        println!(
+                            "{}: MSI database with {} tables",
+                            msi.display(),
+                            info.tables.len()
+                        );
+                        println!("ProductCode: {}", info.product_code);
+                        println!("ProductVersion: {}", info.product_version);
+                        Stop(0)
+                    }
+                    Err(e) => Stop::failed(tool, e),
+                });
+            }
+            Err(e) => return Err(Stop::failed(tool, e)),
+        }
+        if doctor {
+            report("wrapper", current_exe.display());
+            report("version", wrapper::version_line(tool));
+        }
+
+        // Passthrough mode: forward the caller's arguments untouched, e.g. to reproduce the
+        // original ICE failures without uninstalling the wrapper. `WIX_LIGHT_WRAPPER_DISABLE=1`
+        // is the same switch under the `WIX_` prefix the other settings use.
+        let disable_var = [
+            tool.var("WIX_LIGHT_WRAPPER_DISABLE"),
+            tool.var("LIGHT_WRAPPER_DISABLE"),
+        ]
+        .into_iter()
+        .find(|name| vars.enabled(name));
+
+        Ok(Invocation {
+            tool,
+            vars,
+            current_exe,
+            verbose,
+            quiet,
+            dry_run,
+            doctor,
+            disable_var,
+            problems: Cell::new(0),
+        })
+    }
+
+    /// Reports `message` as a `doctor` finding.
+    fn problem(&self, message: impl fmt::Display) {
+        report("problem", message);
+        self.problems.set(self.problems.get() + 1);
+    }
+
+    /// Stops with `code` over `message`, or in `doctor` reports it and carries on.
+    fn refuse(&self, message: String, code: i32) -> Result<(), Stop> {
+        if !self.doctor {
+            fatal!(self.tool, "{message}");
+            return Err(Stop(code));
+        }
+        self.problem(message);
+        Ok(())
+    }
+
+    fn exe_dir(&self) -> &Path {
+        self.current_exe.parent().unwrap_or_else(|| Path::new("."))
+    }
+
+    /// The wrapper's nesting depth, after refusing to recurse into itself.
+    fn recursion_depth(&self) -> Result<u32, Stop> {
+        let (tool, vars) = (self.tool, self.vars);
+        // Set on every child we spawn: seeing it on entry means the "real" tool we were started
+        // as is another wrapper copy (e.g. reached through PATH), which would recurse forever.
+        let active_var = tool.var("WIX_LIGHT_WRAPPER_ACTIVE");
+        let active = vars.get(&active_var).is_some();
+        if active {
+            self.refuse(
+                format!(
+                    "refusing to invoke itself: {active_var} is already set, so {} is another copy of the wrapper",
+                    tool.real_exe_name()
+                ),
+                1,
+            )?;
+        }
+        // The same guard for chains that lose the marker variable on the way (a build script
+        // starting us with a scrubbed environment, say): each wrapper passes its depth on, plus
+        // one.
+        let depth_var = tool.var("LIGHT_WRAPPER_DEPTH");
+        let depth: u32 = vars
+            .get(&depth_var)
+            .and_then(|depth| depth.parse().ok())
+            .unwrap_or(0);
+        if depth >= 2 {
+            self.refuse(
+                format!(
+                    "refusing to run nested {depth} deep ({depth_var}={depth}): the real tool keeps starting the wrapper again"
+                ),
+                1,
+            )?;
+        } else if self.doctor && !active {
+            report(
+                "recursion",
+                format!("none seen ({active_var} unset, nesting depth {depth})"),
+            );
+        }
+        Ok(depth)
+    }
+
+    /// Forwards `cli_args` untouched. Only the recursion guards come first: the real tool is
+    /// found from the environment alone, with no config file, cache, digest check, timeout or
+    /// lock, so none of those being broken can stand in the way of light's own behavior.
+    fn passthrough(
+        &self,
+        disable_var: &str,
+        lookup: &Lookup,
+        depth: u32,
+        cli_args: &[OsString],
+    ) -> Result<i32, Stop> {
+        let tool = self.tool;
+        diag!(
+            tool,
+            "passthrough mode active ({disable_var}=1), no flags injected"
+        );
+        let resolved = wrapper::resolve_real_exe(
+            tool,
+            &self.current_exe,
+            lookup.override_exe.as_deref(),
+            &lookup.search,
+        );
+        let (real_exe, code) = match resolved {
+            Ok(real_exe) => (real_exe, 0),
+            Err(e) if self.dry_run.active() => {
+                fatal!(tool, "{e}");
+                (PathBuf::from(tool.real_exe_name()), 1)
+            }
+            Err(e) => return Err(Stop::failed(tool, e)),
+        };
+        if self.dry_run.active() {
+            self.dry_run.print(tool, &real_exe, cli_args);
+            return Ok(code);
+        }
+        let launcher = Launcher {
+            tool,
+            real_exe: &real_exe,
+            output_log: open_output_log(tool, self.vars),
+            annotations: None,
+            wine: lookup.wine.clone(),
+            depth,
+            timeout: None,
+            response_files: false,
+            retries: 0,
+            retry_delay: Duration::ZERO,
+        };
+        let result = launcher.run(cli_args, false).map(|(status, _)| status);
+        Ok(launcher.exit_code(cli_args, result))
+    }
+
+    /// Settings checked into the repo: `WIX_LIGHT_WRAPPER_CONFIG` names the file, otherwise
+    /// `light-wrapper.toml` or `wix-light-wrapper.toml` beside the wrapper is used if present.
+    /// Environment variables override whatever the file sets.
+    fn load_config(&self) -> Result<Config, Stop> {
+        let (tool, vars) = (self.tool, self.vars);
+        let config_var = tool.var("WIX_LIGHT_WRAPPER_CONFIG");
+        let config_path = match vars.get(&config_var) {
+            Some(path) if !Path::new(path).is_file() => {
+                self.refuse(
+                    format!("{config_var} names '{path}', which is not a file"),
+                    1,
+                )?;
+                PathBuf::from(path)
+            }
+            Some(path) => PathBuf::from(path),
+            None => {
+                let exe_dir = self.exe_dir();
+                let beside = exe_dir.join(tool.wrapper_file_name("toml"));
+                let prefixed = exe_dir.join(format!("wix-{}", tool.wrapper_file_name("toml")));
+                if !beside.exists() && prefixed.exists() {
+                    prefixed
+                } else {
+                    beside
+                }
+            }
+        };
+        if self.doctor {
+            if config_path.is_file() {
+                report("config", config_path.display());
+            } else {
+                report("config", format!("none ({} absent)", config_path.display()));
+            }
+        }
+        match Config::load(&config_path) {
+            Ok(config) => Ok(config),
+            Err(e) => {
+                self.refuse(e.to_string(), 1)?;
+                Ok(Config::default())
+            }
+        }
+    }
+
+    /// Finds the real tool. The config file's `real_exe` applies when neither variable sets
+    /// it. Tauri links several times per bundle, so the search's result and the real tool's
+    /// WiX version are remembered in the temp directory (`TEMP`, `TMP` or `TMPDIR`) for the
+    /// next invocation of the same wrapper with the same settings; see
+    /// `wrapper::resolve_cached`.
+    fn locate(&self, lookup: &Lookup, config: &Config) -> Result<RealTool, Stop> {
+        let (tool, vars, current_exe) = (self.tool, self.vars, self.current_exe.as_path());
+        let override_exe = lookup
+            .override_exe
+            .as_deref()
+            .or(config.real_exe.as_deref());
+        let resolve = || wrapper::resolve_real_exe(tool, current_exe, override_exe, &lookup.search);
+        let cache = ["TEMP", "TMP", "TMPDIR"]
+            .iter()
+            .find_map(|name| vars.get(name))
+            .filter(|_| !self.doctor)
+            .zip(wrapper::resolution_key(
+                tool,
+                current_exe,
+                override_exe,
+                &lookup.search,
+            ))
+            .map(|(temp, key)| {
+                let name = sha256::hex(current_exe.as_os_str().as_encoded_bytes());
+                let path = Path::new(temp).join(format!(
+                    "{}-wrapper-{}.resolved",
+                    tool.name(),
+                    &name[..16]
+                ));
+                (path, key)
+            });
+        let resolved = match &cache {
+            Some((path, key)) => wrapper::resolve_cached(path, key, current_exe, resolve),
+            None => resolve().map(|real_exe| wrapper::Resolved {
+                real_exe,
+                version: None,
+            }),
+        };
+        let real = match resolved {
+            Ok(resolved) => RealTool {
+                exe: longpath::real_exe(&resolved.real_exe),
+                resolved: Some(resolved),
+                cache,
+            },
+            Err(e) => {
+                if self.doctor {
+                    self.problem(e);
+                } else if self.dry_run.active() {
+                    fatal!(tool, "{e}");
+                } else {
+                    return Err(Stop::failed(tool, e));
+                }
+                RealTool {
+                    exe: PathBuf::from(tool.real_exe_name()),
+                    resolved: None,
+                    cache,
+                }
+            }
+        };
+        if self.doctor && !real.unresolved() {
+            // Resolution already refuses wrapper copies, but the report says what it saw
+            // itself.
+            let real_exe = &real.exe;
+            match wrapper::is_wrapper_copy(real_exe, current_exe) {
+                Ok(false) => report(
+                    "real tool",
+                    format!("{} (no wrapper marker)", real_exe.display()),
+                ),
+                Ok(true) => {
+                    report("real tool", real_exe.display());
+                    self.problem(wrapper::WrapperError::RealExeIsWrapper(
+                        tool,
+                        real_exe.clone(),
+                    ));
+                }
+                Err(e) => {
+                    report("real tool", real_exe.display());
+                    self.problem(format!("unable to read '{}': {e}", real_exe.display()));
+                }
+            }
+        }
+        Ok(real)
+    }
+
+    /// `LIGHT_WRAPPER_EXPECTED_SHA256=<hex>[,<hex>...]` (or `real_exe_sha256` in the config)
+    /// refuses to start a real tool whose digest is none of those, e.g. one swapped on a shared
+    /// build box; several cover different WiX patch levels. Unset, nothing is hashed.
+    fn check_digest(&self, config: &Config, real: &RealTool) -> Result<(), Stop> {
+        let tool = self.tool;
+        let expected_var = tool.var("LIGHT_WRAPPER_EXPECTED_SHA256");
+        let expected: Vec<String> = match self.vars.get(&expected_var) {
+            Some(list) => list
+                .split(',')
+                .map(|digest| digest.trim().to_ascii_lowercase())
+                .filter(|digest| !digest.is_empty())
+                .collect(),
+            None => config.real_exe_sha256.clone(),
+        };
+        if let Some(bad) = expected.iter().find(|d| !sha256::is_hex_digest(d)) {
+            return Err(Stop::failed(
+                tool,
+                format!("{expected_var}: '{bad}' is not a SHA-256 digest (64 hex digits)"),
+            ));
+        }
+        if expected.is_empty() || real.unresolved() {
+            return Ok(());
+        }
+        let message = match sha256::file_hex(&real.exe) {
+            Ok(actual) if expected.contains(&actual) => return Ok(()),
+            Ok(actual) => format!(
+                "refusing to run '{}': its SHA-256 is {actual}, expected {}",
+                real.exe.display(),
+                expected.join(" or ")
+            ),
+            Err(e) => format!("unable to hash '{}': {e}", real.exe.display()),
+        };
+        self.refuse(message, wrapper::INTEGRITY_MISMATCH)
+    }
+
+    /// `LIGHT_WRAPPER_TIMEOUT_SECS=600` kills a real tool that hangs (e.g. on a network share)
+    /// instead of stalling the bundle step; unset, the wrapper waits as long as it takes.
+    /// `WIX_LIGHT_WRAPPER_TIMEOUT_SECS` is the same setting under the `WIX_` prefix.
+    fn timeout(&self, config: &Config) -> Result<Option<Duration>, Stop> {
+        let tool = self.tool;
+        let timeout_setting = [
+            tool.var("WIX_LIGHT_WRAPPER_TIMEOUT_SECS"),
+            tool.var("LIGHT_WRAPPER_TIMEOUT_SECS"),
+        ]
+        .into_iter()
+        .find_map(|name| self.vars.get(&name).map(|value| (name, value)));
+        match timeout_setting {
+            None => Ok(config.timeout_secs.map(Duration::from_secs)),
+            Some((name, value)) => match value.parse::<u64>() {
+                Ok(secs) if secs > 0 => Ok(Some(Duration::from_secs(secs))),
+                _ => Err(Stop::failed(
+                    tool,
+                    format!("{name} must be a positive whole number of seconds"),
+                )),
+            },
+        }
+    }
+
+    /// `LIGHT_WRAPPER_LOCK=<path>` runs one invocation at a time per lock file (parallel builds
+    /// sharing a cabinet cache), waiting up to `LIGHT_WRAPPER_LOCK_TIMEOUT_SECS` (default 600)
+    /// for the current holder. Unset, or in a dry run, nothing is locked and no file is created.
+    fn lock(&self) -> Result<Option<lock::Lock>, Stop> {
+        let (tool, vars) = (self.tool, self.vars);
+        let lock_var = tool.var("LIGHT_WRAPPER_LOCK");
+        let Some(path) = vars
+            .get(&lock_var)
+            .filter(|_| !self.dry_run.active() && !self.doctor)
+        else {
+            return Ok(None);
+        };
+        let lock_timeout = whole_number(vars, &tool.var("LIGHT_WRAPPER_LOCK_TIMEOUT_SECS"))
+            .map_err(|e| Stop::failed(tool, e))?;
+        let lock_timeout = Duration::from_secs(lock_timeout.unwrap_or(600).into());
+        let waiting = || diag!(tool, "waiting for another invocation to release '{path}'");
+        match lock::acquire(Path::new(path), lock_timeout, waiting) {
+            Ok(lock) => Ok(Some(lock)),
+            Err(e) => Err(Stop::failed(
+                tool,
+                format!("unable to lock '{path}' ({lock_var}): {e}"),
+            )),
+        }
+    }
+
+    /// A real tool that exists but doesn't look startable is worth a word before the spawn
+    /// fails with a bare permission error. Through wine, the mode bits don't matter.
+    fn check_startable(&self, real: &RealTool, lookup: &Lookup) {
+        if let Some(message) = lookup
+            .wine
+            .is_none()
+            .then(|| wrapper::executable_problem(&real.exe))
+            .flatten()
+            .filter(|_| !real.unresolved())
+        {
+            if self.doctor {
+                self.problem(message);
+            } else {
+                diag!(self.tool, "{message}; trying anyway");
+            }
+        }
+    }
+
+    /// Builds the arguments of the link from the caller's `cli_args` and the flags to inject.
+    fn plan(
+        &self,
+        config: &Config,
+        real: &RealTool,
+        lookup: &Lookup,
+        depth: u32,
+        cli_args: Vec<OsString>,
+    ) -> Result<Plan, Stop> {
+        let (tool, vars) = (self.tool, self.vars);
+        // Arbitrary passthrough flags (e.g. `-cultures:en-US -spdb`), forwarded as given right
+        // after the injected suppressions and ahead of the caller's arguments. Stripping,
+        // normalizing and deduplication only ever see the caller's arguments. See
+        // `wrapper::split_args` for quoting.
+        let extra_flags_var = tool.var("WIX_LIGHT_WRAPPER_EXTRA_FLAGS");
+        let extra_flags: Vec<OsString> = match vars.get(&extra_flags_var) {
+            Some(_) => vars.args(&extra_flags_var),
+            None => config.extra_flags.clone(),
+        }
+        .into_iter()
+        .map(OsString::from)
+        .collect();
+        let (incoming, opaque) = self.caller_args(config, cli_args)?;
+        let version = self.wix_version(real, lookup, depth);
+        let (prepend, append) = self.injected_flags(config, version);
+        let (prepend, append) = (as_strs(&prepend), as_strs(&append));
+        let args = wrapper::build_args(&incoming, &opaque, &prepend, &extra_flags, &append);
+
+        // Light only skips ICE validation once it has actually failed: the first link leaves
+        // out the injected `-sval` and a failed validation is retried once with the full
+        // argument list. `WIX_LIGHT_WRAPPER_ADAPTIVE=1` goes further and runs the first link
+        // with nothing injected at all; `LIGHT_WRAPPER_ALWAYS_SVAL=1` injects everything up
+        // front and links only once.
+        let adaptive_var = tool.var("WIX_LIGHT_WRAPPER_ADAPTIVE");
+        let adaptive = tool.profile().validation_retry
+            && match vars.get(&adaptive_var) {
+                Some(_) => vars.enabled(&adaptive_var),
+                None => config.adaptive.unwrap_or(false),
+            };
+        let first_args = (tool.profile().validation_retry
+            && !vars.enabled(&tool.var("LIGHT_WRAPPER_ALWAYS_SVAL")))
+        .then(|| {
+            if adaptive {
+                wrapper::build_args(&incoming, &opaque, &[], &extra_flags, &[])
+            } else {
+                wrapper::build_args(
+                    &incoming,
+                    &opaque,
+                    &without_flag(&prepend, "-sval"),
+                    &extra_flags,
+                    &without_flag(&append, "-sval"),
+                )
+            }
+        })
+        .filter(|first| *first != args);
+        let retry_flags = first_args
+            .as_ref()
+            .map(|first| {
+                let added: Vec<OsString> = args
+                    .iter()
+                    .filter(|a| !first.contains(a))
+                    .cloned()
+                    .collect();
+                display_args(&added)
+            })
+            .unwrap_or_default();
+        Ok(Plan {
+            incoming,
+            opaque,
+            args,
+            first_args,
+            retry_flags,
+            adaptive,
+        })
+    }
+
+    /// Splits the caller's arguments into the ones the wrapper may rewrite and the opaque
+    /// rest, and rewrites the former as configured.
+    fn caller_args(
+        &self,
+        config: &Config,
+        mut cli_args: Vec<OsString>,
+    ) -> Result<(Vec<OsString>, Vec<OsString>), Stop> {
+        let (tool, vars) = (self.tool, self.vars);
+        // Everything after a `--` among the caller's arguments, or from position
+        // `WIX_LIGHT_WRAPPER_OPAQUE_FROM` (0-based) on, is forwarded untouched and never taken
+        // for a flag; see `wrapper::split_opaque` for how that relates to light's own parsing.
+        let opaque_from = whole_number(vars, &tool.var("WIX_LIGHT_WRAPPER_OPAQUE_FROM"))
+            .map_err(|e| Stop::failed(tool, e))?
+            .map(|from| from as usize);
+        let opaque = wrapper::split_opaque(&mut cli_args, opaque_from);
+        let mut incoming = cli_args;
+
+        // Remove flags the caller (usually Tauri) passes that this environment doesn't want,
+        // such as `LIGHT_WRAPPER_STRIP="-cultures:* -loc"` (or `strip` in the config) for an
+        // installer in one language. `LIGHT_WRAPPER_STRIP_ARGS` is the older name of the
+        // setting.
+        let strip_var = [
+            tool.var("LIGHT_WRAPPER_STRIP"),
+            tool.var("LIGHT_WRAPPER_STRIP_ARGS"),
+        ]
+        .into_iter()
+        .find(|name| vars.get(name).is_some());
+        let strip = match strip_var {
+            Some(name) => vars.args(&name),
+            None => config.strip.clone(),
+        };
+        let stripped = wrapper::strip_args(&mut incoming, &strip);
+        if !stripped.is_empty() {
+            diag!(tool, "removed {}", display_args(&stripped));
+        }
+
+        // A `-sval` or `-sacl` passed twice (say by Tauri and an extra-flags setting) is
+        // forwarded once; `-sice:` flags next to a `-sval` do nothing, which is worth saying.
+        let suppressions = wrapper::normalize_suppressions(&mut incoming);
+        if !suppressions.dropped.is_empty() {
+            diag!(
+                tool,
+                "dropped repeated {}",
+                display_args(&suppressions.dropped)
+            );
+        }
+        if !suppressions.redundant.is_empty() {
+            diag!(
+                tool,
+                "redundant next to -sval, which skips all ICE validation: {}",
+                display_args(&suppressions.redundant)
+            );
+        }
+
+        // Repeated `-ext`/`-loc`/`-b` pairs and `.wixobj` inputs make light fail (e.g.
+        // LGHT0144); they are reported, and with `LIGHT_WRAPPER_DEDUPE=1` the later copies are
+        // dropped.
+        let duplicates = wrapper::duplicate_args(&incoming);
+        if !duplicates.is_empty() {
+            let listed: Vec<String> = duplicates
+                .iter()
+                .map(|range| display_args(&incoming[range.clone()]))
+                .collect();
+            let dedupe_var = tool.var("LIGHT_WRAPPER_DEDUPE");
+            if vars.enabled(&dedupe_var) {
+                for range in duplicates.into_iter().rev() {
+                    incoming.drain(range);
+                }
+                diag!(tool, "dropped duplicate arguments: {}", listed.join(", "));
+            } else {
+                diag!(
+                    tool,
+                    "duplicate arguments (set {dedupe_var}=1 to drop them): {}",
+                    listed.join(", ")
+                );
+            }
+        }
+
+        // `LIGHT_WRAPPER_LONGPATH=1` gives absolute output, cache, bind and input paths the
+        // `\\?\` prefix, for workspaces nested past `MAX_PATH` (see `longpath::rewrite_args`).
+        if vars.enabled(&tool.var("LIGHT_WRAPPER_LONGPATH")) {
+            longpath::rewrite_args(&mut incoming);
+        }
+        Ok((incoming, opaque))
+    }
+
+    /// The WiX version of the real tool, which picks the flags to inject (see
+    /// `injected_flags`). A tool whose version can't be told is taken to be v3.
+    fn wix_version(&self, real: &RealTool, lookup: &Lookup, depth: u32) -> WixVersion {
+        let tool = self.tool;
+        if !tool.is_wix() {
+            return WixVersion::V3;
+        }
+        let real_exe = &real.exe;
+        let recorded = real.resolved.as_ref().and_then(|r| r.version.as_deref());
+        let toolset = if self.dry_run.active() || real.unresolved() {
+            // A dry run doesn't start the real tool, not even to ask for its version.
+            toolset::read_version_resource(real_exe)
+        } else if let Some(recorded) = recorded {
+            toolset::parse_version(recorded)
+        } else {
+            let probe = wrapper::build_command(
+                tool,
+                real_exe,
+                &[OsString::from("-?")],
+                lookup.wine.as_deref(),
+                depth,
+            );
+            let version = toolset::detect_wix_version(real_exe, probe);
+            if let (Some((path, key)), Some(resolved)) = (&real.cache, &real.resolved) {
+                let recorded = version.map_or_else(|| "unknown".to_string(), |v| v.to_string());
+                wrapper::record_version(path, key, &resolved.real_exe, &recorded);
+            }
+            version
+        };
+        if self.verbose {
+            match toolset {
+                Some(version) => diag!(tool, "{} is WiX {version}", real_exe.display()),
+                None => diag!(
+                    tool,
+                    "could not tell the WiX version of {}, assuming v3",
+                    real_exe.display()
+                ),
+            }
+        }
+        if self.doctor {
+            match toolset {
+                Some(version) => report("WiX version", version),
+                None => report("WiX version", "unknown, taken to be v3"),
+            }
+        }
+        toolset.unwrap_or(WixVersion::V3)
+    }
+
+    /// The flags injected ahead of the caller's inputs and after all the caller's arguments.
+    ///
+    /// The v3-style switches make WiX v4 and later fail with a usage error, so of the
+    /// wrapper's own defaults only those the real tool's `version` accepts (see
+    /// `toolset::supported_flags`) are injected. Flags asked for in the environment or the
+    /// config are injected as given. `WIX_LIGHT_WRAPPER_SUPPRESS_V4` (for the detected major
+    /// version) lists flags to inject instead, for toolsets that spell their switches
+    /// differently.
+    fn injected_flags(&self, config: &Config, version: WixVersion) -> (Vec<String>, Vec<String>) {
+        let (tool, vars) = (self.tool, self.vars);
+        let major_suppress =
+            vars.get(&tool.var(&format!("WIX_LIGHT_WRAPPER_SUPPRESS_V{}", version.major)));
+
+        // Suppression flags injected by default, e.g. `WIX_LIGHT_WRAPPER_SUPPRESS=sval` on
+        // agents where `-sacl` trips a different WiX policy.
+        let (requested, explicit) = match (
+            vars.get(&tool.var("WIX_LIGHT_WRAPPER_SUPPRESS")),
+            &config.suppress,
+        ) {
+            (Some(list), _) => (wrapper::suppress_flags(list), true),
+            (None, Some(list)) => (wrapper::suppress_flags(&list.join(",")), true),
+            (None, None) => (wrapper::suppress_flags(tool.default_suppress()), false),
+        };
+        let mut suppress = match major_suppress {
+            Some(list) => wrapper::suppress_flags(list),
+            None if explicit => requested,
+            None => supported_only(tool, version, requested),
+        };
+
+        // `LIGHT_WRAPPER_SUPPRESS_ICES=ICE61,ICE69` (`WIX_LIGHT_WRAPPER_SICE` is the same
+        // setting, or `suppress_ices` in the config) skips just those checks; `-sval` is then
+        // never added, not even as a fallback, and one that was asked for is warned about.
+        let ices: Vec<String> = match vars
+            .get(&tool.var("LIGHT_WRAPPER_SUPPRESS_ICES"))
+            .or_else(|| vars.get(&tool.var("WIX_LIGHT_WRAPPER_SICE")))
+        {
+            Some(list) => list.split(',').map(str::to_string).collect(),
+            None => config.suppress_ices.clone(),
+        };
+        let sice = wrapper::sice_flags(&ices);
+        let mut sval_requested = explicit && suppress.iter().any(|f| wrapper::flag_eq(f, "-sval"));
+        if !sice.is_empty() {
+            suppress.retain(|f| !wrapper::flag_eq(f, "-sval"));
+            suppress.splice(0..0, sice.iter().cloned());
+        }
+
+        // `LIGHT_WRAPPER_SUPPRESS_PDB=1` adds `-spdb`, so no `.wixpdb` is written next to the
+        // MSI.
+        if vars.enabled(&tool.var("LIGHT_WRAPPER_SUPPRESS_PDB")) {
+            suppress.push("-spdb".into());
+        }
+
+        // `LIGHT_WRAPPER_QUIET=1` drops the real tool's banner as well; every WiX tool takes
+        // `-nologo`.
+        if self.quiet && tool.is_wix() {
+            suppress.push("-nologo".into());
+        }
+
+        // Extra environment-specific flags (e.g. `-sw1076`), whitespace-separated.
+        let extra = vars
+            .get(&tool.var("LIGHT_WRAPPER_EXTRA_FLAGS"))
+            .unwrap_or_default();
+        let mut prepend = suppress;
+        prepend.extend(config.prepend_args.iter().cloned());
+        prepend.extend(extra.split_whitespace().map(String::from));
+        prepend.extend(vars.args(&tool.var("LIGHT_WRAPPER_PREPEND_ARGS")));
+        let mut append = config.append_args.clone();
+        append.extend(vars.args(&tool.var("LIGHT_WRAPPER_APPEND_ARGS")));
+        if !ices.is_empty() {
+            let is_sval = |f: &String| wrapper::flag_eq(f, "-sval");
+            sval_requested |= vars.enabled(&tool.var("LIGHT_WRAPPER_ALWAYS_SVAL"))
+                || prepend.iter().any(is_sval)
+                || append.iter().any(is_sval);
+            prepend.retain(|f| !is_sval(f));
+            append.retain(|f| !is_sval(f));
+            if sval_requested && !sice.is_empty() {
+                diag!(
+                    tool,
+                    "-sval is configured along with individual ICE suppressions; injecting only {}",
+                    sice.join(" ")
+                );
+            }
+        }
+        (prepend, append)
+    }
+
+    /// The `doctor` report's last lines, and its exit code.
+    fn report_plan(&self, plan: &Plan) -> i32 {
+        if let Some(disable_var) = &self.disable_var {
+            report("injected", format!("nothing ({disable_var}=1)"));
+        } else if plan.args.is_empty() {
+            report("injected", "nothing");
+        } else {
+            report("injected", display_args(&plan.args));
+        }
+        if plan.first_args.is_some() && self.disable_var.is_none() {
+            report(
+                "first link",
+                format!(
+                    "without {}, added when ICE validation fails",
+                    plan.retry_flags
+                ),
+            );
+        }
+        self.problems.get().min(1)
+    }
+
+    /// Prints the dry run's command line and returns its exit code.
+    fn print_plan(&self, real: &RealTool, plan: &Plan) -> i32 {
+        self.dry_run.print(self.tool, &real.exe, plan.first());
+        if plan.first_args.is_some() {
+            diag!(
+                self.tool,
+                "dry run: a failed ICE validation would be retried with {}",
+                plan.retry_flags
+            );
+        }
+        if real.unresolved() {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// What `LIGHT_WRAPPER_VERBOSE=1` says before the first link.
+    fn announce(&self, real: &RealTool, plan: &Plan) {
+        if !self.verbose {
+            return;
+        }
+        let (tool, first) = (self.tool, plan.first());
+        let injected: Vec<OsString> = plan.injected(first).into_iter().cloned().collect();
+        diag!(tool, "real tool: {}", real.exe.display());
+        diag!(
+            tool,
+            "injected: {}",
+            if injected.is_empty() {
+                "nothing".to_string()
+            } else {
+                display_args(&injected)
+            }
+        );
+        diag!(tool, "{} arguments", first.len());
+        diag!(
+            tool,
+            "running {}",
+            wrapper::display_command(&real.exe, first)
+        );
+    }
+
+    /// How the real tool is started for this link.
+    fn launcher<'r>(
+        &self,
+        real: &'r RealTool,
+        wine: Option<PathBuf>,
+        depth: u32,
+        timeout: Option<Duration>,
+    ) -> Result<Launcher<'r>, Stop> {
+        let (tool, vars) = (self.tool, self.vars);
+        // `LIGHT_WRAPPER_RETRIES=3` re-runs light when the output is locked by another process
+        // (antivirus, indexing), waiting `LIGHT_WRAPPER_RETRY_DELAY_MS` (default 2000) before
+        // the first retry and twice as long before each further one.
+        let (retries, retry_delay_ms) = match (
+            whole_number(vars, &tool.var("LIGHT_WRAPPER_RETRIES")),
+            whole_number(vars, &tool.var("LIGHT_WRAPPER_RETRY_DELAY_MS")),
+        ) {
+            (Ok(retries), Ok(delay)) => (retries.unwrap_or(0), delay.unwrap_or(2000)),
+            (Err(e), _) | (_, Err(e)) => return Err(Stop::failed(tool, e)),
+        };
+
+        // Under CI, warnings and errors are repeated as annotations: `GITHUB_ACTIONS=true`
+        // picks GitHub's syntax, `WIX_LIGHT_WRAPPER_ANNOTATIONS=github|azure` chooses
+        // explicitly.
+        let annotations_var = tool.var("WIX_LIGHT_WRAPPER_ANNOTATIONS");
+        let annotations = match vars.get(&annotations_var) {
+            Some(value) => match AnnotationStyle::parse(value) {
+                Some(style) => Some(style),
+                None => {
+                    return Err(Stop::failed(
+                        tool,
+                        format!("{annotations_var} must be 'github' or 'azure', not '{value}'"),
+                    ));
+                }
+            },
+            None => (vars.get("GITHUB_ACTIONS") == Some("true")).then_some(AnnotationStyle::Github),
+        };
+
+        Ok(Launcher {
+            tool,
+            real_exe: &real.exe,
+            output_log: open_output_log(tool, vars),
+            annotations,
+            wine,
+            depth,
+            timeout,
+            response_files: true,
+            retries,
+            retry_delay: Duration::from_millis(retry_delay_ms.into()),
+        })
+    }
+
+    /// `LIGHT_WRAPPER_WARNING_BASELINE=<path>`; see `check_warnings`.
+    fn warning_baseline(&self) -> Option<&str> {
+        self.vars
+            .get(&self.tool.var("LIGHT_WRAPPER_WARNING_BASELINE"))
+    }
+
+    /// `LIGHT_WRAPPER_EXPLAIN=1` adds a hint for well-known failures; the code stays light's.
+    fn explain(&self) -> bool {
+        self.vars.enabled(&self.tool.var("LIGHT_WRAPPER_EXPLAIN"))
+    }
+
+    /// Runs the link, and once more with `Plan::args` when the first attempt failed ICE
+    /// validation.
+    fn launch(&self, launcher: &Launcher, plan: &Plan) -> Linked {
+        let tool = self.tool;
+        // `WIX_LIGHT_WRAPPER_TIMING=1` says how long the real tool took, each attempt
+        // separately.
+        let timing = self.vars.enabled(&tool.var("WIX_LIGHT_WRAPPER_TIMING"));
+        let started = SystemTime::now();
+        let clock = Instant::now();
+        let mut fallback = false;
+        let mut output_text = String::new();
+        let mut last_attempt = 0;
+        let capture = self.warning_baseline().is_some() || self.explain();
+        let retrying = plan.first_args.is_some();
+        let first_result = launcher.run_retrying(plan.first(), retrying || capture);
+        let first_elapsed = clock.elapsed();
+        if let Ok((_, Some(output))) = &first_result {
+            output_text.push_str(&output.text());
+        }
+        let result = match first_result {
+            Ok((status, Some(_)))
+                if retrying
+                    && !status.success()
+                    && wrapper::is_validation_failure(&output_text) =>
+            {
+                diag!(
+                    tool,
+                    "ICE validation failed; retrying with {}",
+                    plan.retry_flags
+                );
+                if self.verbose {
+                    diag!(
+                        tool,
+                        "running {}",
+                        wrapper::display_command(launcher.real_exe, &plan.args)
+                    );
+                }
+                fallback = true;
+                if timing {
+                    diag!(
+                        tool,
+                        "first attempt failed in {} ({})",
+                        seconds(first_elapsed),
+                        outcome(&Ok(status))
+                    );
+                }
+                last_attempt = output_text.len();
+                launcher
+                    .run_retrying(&plan.args, capture)
+                    .map(|(status, output)| {
+                        if let Some(output) = output {
+                            output_text.push_str(&output.text());
+                        }
+                        status
+                    })
+            }
+            other => other.map(|(status, _)| status),
+        };
+        let duration = clock.elapsed();
+        if timing {
+            let (what, elapsed) = if fallback {
+                ("retry", duration - first_elapsed)
+            } else {
+                ("link", duration)
+            };
+            diag!(
+                tool,
+                "{what} completed in {} ({})",
+                seconds(elapsed),
+                outcome(&result)
+            );
+        }
+        if plan.adaptive && retrying {
+            if fallback {
+                diag!(
+                    tool,
+                    "adaptive mode: ICE validation failed, re-ran with {}",
+                    plan.retry_flags
+                );
+            } else {
+                diag!(
+                    tool,
+                    "adaptive mode: ran with the original arguments, nothing injected"
+                );
+            }
+        }
+        Linked {
+            result,
+            fallback,
+            output_text,
+            last_attempt,
+            started,
+            duration,
+        }
+    }
+
+    /// Says what became of ICE validation, and writes the JSON log and the report.
+    fn record(&self, launcher: &Launcher, plan: &Plan, linked: &Linked) {
+        let (tool, vars) = (self.tool, self.vars);
+        let (args, result) = (linked.args(plan), &linked.result);
+        let finished = linked.started + linked.duration;
+
+        // When validation was bypassed (or the output is being logged), say what it reported,
+        // so each build records which checks it went without.
+        let validation = validation::summarize_output(&linked.output_text);
+        let logged = launcher.output_log.is_some();
+        if (linked.fallback || plan.adaptive || logged) && !validation.is_empty() {
+            diag!(
+                tool,
+                "suppressed validation: {validation}{}",
+                if logged { ", see log" } else { "" }
+            );
+        }
+
+        // `LIGHT_WRAPPER_LOG=1` logs next to the wrapper; `LIGHT_WRAPPER_LOG_PATH` picks another
+        // file. `WIX_LIGHT_WRAPPER_LOG_JSON=1` writes the record to stderr when neither is set.
+        let log_path = vars
+            .get(&tool.var("LIGHT_WRAPPER_LOG_PATH"))
+            .map(PathBuf::from)
+            .or_else(|| {
+                vars.enabled(&tool.var("LIGHT_WRAPPER_LOG"))
+                    .then(|| self.exe_dir().join(tool.wrapper_file_name("log")))
+            });
+        let injected = plan.injected(args);
+        if log_path.is_some() || vars.enabled(&tool.var("WIX_LIGHT_WRAPPER_LOG_JSON")) {
+            let record = Json::object(vec![
+                (
+                    "timestamp",
+                    Json::string(log::utc_timestamp(linked.started)),
+                ),
+                (
+                    "real_exe",
+                    Json::string(launcher.real_exe.display().to_string()),
+                ),
+                ("args", Json::strings(&lossy_strings(args))),
+                ("sval_fallback", linked.fallback.into()),
+                (
+                    "validation_codes",
+                    Json::strings(&validation.codes.iter().collect::<Vec<_>>()),
+                ),
+                (
+                    "duration_ms",
+                    Json::Number(linked.duration.as_millis() as i64),
+                ),
+                (
+                    "exit_code",
+                    result.as_ref().ok().and_then(|s| s.code()).into(),
+                ),
+                ("error", result.as_ref().err().map(|e| e.to_string()).into()),
+                ("finished", Json::string(log::utc_timestamp(finished))),
+                ("arg_count", Json::Number(args.len() as i64)),
+                (
+                    "injected",
+                    Json::strings(&lossy_strings(injected.iter().copied())),
+                ),
+                ("adaptive", plan.adaptive.into()),
+            ]);
+            match log_path {
+                Some(log_path) => {
+                    if let Err(e) = log::append_record(&log_path, &record, log::MAX_LOG_BYTES) {
+                        diag!(tool, "unable to write log '{}': {e}", log_path.display());
+                    }
+                }
+                None => eprintln!("{record}"),
+            }
+        }
+
+        // `LIGHT_WRAPPER_REPORT=<path>` writes a provenance document for the link, failed or
+        // not. A report that can't be written is only mentioned; it never changes the exit
+        // status.
+        if let Some(report_path) = vars.get(&tool.var("LIGHT_WRAPPER_REPORT")) {
+            let report = InvocationReport {
+                real_exe: launcher.real_exe,
+                args,
+                injected,
+                started: linked.started,
+                finished,
+                exit_code: result.as_ref().ok().and_then(|s| s.code()),
+            };
+            if let Err(e) = report.write(Path::new(report_path)) {
+                diag!(tool, "unable to write report '{report_path}': {e}");
+            }
+        }
+    }
+
+    /// The wrapper's exit code for the link, after the follow-ups of a successful one:
+    /// cleaning the cabinet cache, checking the warnings and signing the MSI.
+    fn finish(&self, launcher: &Launcher, plan: &Plan, linked: Linked) -> i32 {
+        let (tool, vars) = (self.tool, self.vars);
+        let args = linked.args(plan);
+        if let (true, Ok(status)) = (self.explain(), &linked.result) {
+            if !status.success() {
+                for hint in explain::explain(wrapper::exit_code(*status), linked.last_output()) {
+                    diag!(tool, "{hint}");
+                }
+            }
+        }
+        let last_output = linked.last_output().to_string();
+        let code = launcher.exit_code(args, linked.result);
+        if code == 0 && vars.enabled(&tool.var("LIGHT_WRAPPER_CLEAN_CABCACHE")) {
+            clean_cab_cache(tool, args);
+        }
+        if let Some(path) = self.warning_baseline().filter(|_| code == 0) {
+            let code = check_warnings(tool, vars, Path::new(path), &last_output);
+            if code != 0 {
+                return code;
+            }
+        }
+        match vars.get(&tool.var("LIGHT_WRAPPER_SIGNTOOL_ARGS")) {
+            Some(_) if code == 0 => sign_output(tool, vars, self.verbose, args),
+            _ => code,
+        }
+    }
+}
+
+/// Removes the cabinet cache the successful link in `args` used (`-cc`, passed or injected).
+/// Only warns when that fails: the MSI itself is fine.
+fn clean_cab_cache(tool: Tool, args: &[OsString]) {
+    let Some(cache) = wrapper::cab_cache_path(args) else {
+        diag!(
+            tool,
+            "no -cc cabinet cache among the arguments; nothing to clean"
+        );
+        return;
+    };
+    match cab_cache::remove(&cache) {
+        Ok(bytes) => diag!(
+            tool,
+            "removed cabinet cache '{}', reclaiming {bytes} bytes",
+            cache.display()
+        ),
+        Err(e) => diag!(
+            tool,
+            "unable to remove cabinet cache '{}': {e}",
+            cache.display()
+        ),
+    }
+}
+
+/// Compares the warnings of a successful link, `output`, with the baseline at `path` and fails
+/// the link when there are new codes or more of one. `LIGHT_WRAPPER_UPDATE_BASELINE=1` writes
+/// the baseline from `output` instead.
+fn check_warnings(tool: Tool, vars: &Vars, path: &Path, output: &str) -> i32 {
+    let current = baseline::warning_counts(output);
+    let update_var = tool.var("LIGHT_WRAPPER_UPDATE_BASELINE");
+    if vars.enabled(&update_var) {
+        return match baseline::write(path, &current) {
+            Ok(()) => {
+                diag!(tool, "wrote warning baseline '{}'", path.display());
+                0
+            }
+            Err(e) => {
+                diag!(
+                    tool,
+                    "unable to write warning baseline '{}': {e}",
+                    path.display()
+                );
+                1
+            }
+        };
+    }
+    let allowed = match baseline::read(path) {
+        Ok(allowed) => allowed,
+        Err(e) => {
+            fatal!(
+                tool,
+                "unable to read warning baseline '{}' ({update_var}=1 creates it): {e}",
+                path.display()
+            );
+            return 1;
+        }
+    };
+    let regressions = baseline::regressions(&allowed, &current);
+    if !regressions.is_empty() {
+        fatal!(
+            tool,
+            "more warnings than the baseline '{}' allows:",
+            path.display()
+        );
+        for regression in regressions {
+            eprintln!("  {regression}");
+        }
+        return 1;
+    }
+    if baseline::improved(&allowed, &current) {
+        diag!(
+            tool,
+            "fewer warnings than the baseline '{}'; {update_var}=1 tightens it",
+            path.display()
+        );
+    }
+    0
+}
+
+/// `LIGHT_WRAPPER_SIGNTOOL_ARGS="/fd sha256 /f cert.pfx /p ..."` signs the MSI a successful link
+/// produced with `signtool sign <those> <msi>`, found through `LIGHT_WRAPPER_SIGNTOOL_EXE` or
+/// the `PATH`. The wrapper fails when signing does, so an unsigned installer is never passed on.
+fn sign_output(tool: Tool, vars: &Vars, verbose: bool, args: &[OsString]) -> i32 {
+    let Some(msi) = wrapper::output_path(args) else {
+        fatal!(tool, "nothing to sign: no -out among the arguments");
+        return 1;
+    };
+    let signtool = PathBuf::from(
+        vars.get(&tool.var("LIGHT_WRAPPER_SIGNTOOL_EXE"))
+            .unwrap_or("signtool.exe"),
+    );
+    let mut command = sign::command(
+        &signtool,
+        &vars.args(&tool.var("LIGHT_WRAPPER_SIGNTOOL_ARGS")),
+        &msi,
+    );
+    if verbose {
+        let sign_args: Vec<OsString> = command.get_args().map(OsString::from).collect();
+        diag!(
+            tool,
+            "signing: {}",
+            wrapper::display_command(&signtool, &sign::redacted(&sign_args))
+        );
+    }
+    match command
+        .spawn()
+        .and_then(|mut child| wait::wait(&mut child, None))
+    {
+        Ok(status) if status.success() => 0,
+        Ok(status) => {
+            fatal!(tool, "signing '{}' failed with {status}", msi.display());
+            wrapper::exit_code(status)
+        }
+        Err(e) => {
+            fatal!(tool, "failed to start '{}': {e}", signtool.display());
+            1
+        }
+    }
+}
+
+/// `args` as text, lossily where they aren't valid Unicode.
+fn lossy_strings<'a>(args: impl IntoIterator<Item = &'a OsString>) -> Vec<String> {
+    args.into_iter()
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// `args` joined with spaces for a diagnostic.
+fn display_args(args: &[OsString]) -> String {
+    lossy_strings(args).join(" ")
+}
+
+/// The default `flags` the real tool's `version` accepts. Tools other than WiX keep all.
+fn supported_only(tool: Tool, version: WixVersion, flags: Vec<String>) -> Vec<String> {
+    flags
+        .into_iter()
+        .filter(|flag| !tool.is_wix() || toolset::supports(tool, version, flag))
+        .collect()
+}
+
+fn as_strs(flags: &[String]) -> Vec<&str> {
+    flags.iter().map(String::as_str).collect()
+}
+
+fn without_flag<'a>(flags: &[&'a str], flag: &str) -> Vec<&'a str> {
+    flags
+        .iter()
+        .copied()
+        .filter(|f| !wrapper::flag_eq(f, flag))
+        .collect()
+}
+
+/// `12.34s`, for the timing lines.
+fn seconds(elapsed: Duration) -> String {
+    format!("{:.2}s", elapsed.as_secs_f64())
+}
+
+/// How an attempt ended, for the timing lines: `exit 0`, `timed out`.
+fn outcome(result: &io::Result<ExitStatus>) -> String {
+    match result {
+        Ok(status) => match status.code() {
+            Some(code) => format!("exit {code}"),
+            None => status.to_string(),
+        },
+        Err(e) if e.kind() == io::ErrorKind::TimedOut => "timed out".to_string(),
+        Err(_) => "failed to start".to_string(),
+    }
+}
+
+/// The variable parsed as a whole number, `None` when unset.
+fn whole_number(vars: &Vars, name: &str) -> Result<Option<u32>, String> {
+    vars.get(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| format!("{name} must be a whole number, not '{value}'"))
+        })
+        .transpose()
+}
+
+/// Opens the `WIX_LIGHT_WRAPPER_LOG` (or `LIGHT_WRAPPER_OUTPUT_FILE`) file the real tool's
+/// output is appended to, or written afresh with `LIGHT_WRAPPER_OUTPUT_TRUNCATE=1`. A log that
+/// can't be opened only warns, so the build carries on with inherited stdio.
+fn open_output_log(tool: Tool, vars: &Vars) -> Option<File> {
+    let path = vars
+        .get(&tool.var("WIX_LIGHT_WRAPPER_LOG"))
+        .or_else(|| vars.get(&tool.var("LIGHT_WRAPPER_OUTPUT_FILE")))?;
+    let mut options = OpenOptions::new();
+    if vars.enabled(&tool.var("LIGHT_WRAPPER_OUTPUT_TRUNCATE")) {
+        options.write(true).truncate(true);
+    } else {
+        options.append(true);
+    }
+    match options.create(true).open(path) {
+        Ok(file) => Some(file),
+        Err(e) => {
+            diag!(
+                tool,
+                "unable to open output log '{path}': {e}; output is not logged"
+            );
+            None
+        }
+    }
+}
+
+/// Prints what `--wrapper-install` or `--wrapper-uninstall` did and returns the exit code,
+/// `unchanged_code` when there was nothing to do.
+fn install_outcome(
+    tool: Tool,
+    result: Result<install::Outcome, String>,
+    unchanged_code: i32,
+) -> i32 {
+    match result {
+        Ok(install::Outcome::Changed(steps)) => {
+            for step in steps {
+                println!("{step}");
+            }
+            0
+        }
+        Ok(install::Outcome::Unchanged(reason)) => {
+            println!("{reason}");
+            unchanged_code
+        }
+        Err(e) => {
+            fatal!(tool, "{e}");
+            1
+        }
+    }
+}
+
+/// One line of the `doctor` report, e.g. `real tool: C:\WiX\bin\light-real.exe`.
+fn report(label: &str, value: impl fmt::Display) {
+    println!("{label}: {value}");
+}
+
+/// Which dry-run outputs were asked for; see `Invocation::start`.
+struct DryRun {
+    line: bool,
+    tokens: bool,
+}
+
+impl DryRun {
+    fn active(&self) -> bool {
+        self.line || self.tokens
+    }
+
+    fn print(&self, tool: Tool, real_exe: &Path, args: &[OsString]) {
+        if self.line {
+            println!("{}", wrapper::display_command(real_exe, args));
+        }
+        if self.tokens {
+            diag!(tool, "dry run, would execute:");
+            eprint!("{}", wrapper::display_tokens(real_exe, args));
+        }
+    }
+}
+
+/// How the real tool is started, shared by every attempt of one wrapped invocation.
+struct Launcher<'a> {
+    tool: Tool,
+    real_exe: &'a Path,
+    /// Open `WIX_LIGHT_WRAPPER_LOG` file, if any.
+    output_log: Option<File>,
+    /// CI annotations to print for the warnings and errors in the output.
+    annotations: Option<AnnotationStyle>,
+    /// Wine executable the real tool is started through, for cross-builds off Windows.
+    wine: Option<PathBuf>,
+    /// This wrapper's `LIGHT_WRAPPER_DEPTH`, passed on to the child plus one.
+    depth: u32,
+    timeout: Option<Duration>,
+    /// Whether over-long command lines may be moved into a temporary response file.
+    response_files: bool,
+    /// Extra attempts allowed after a sharing violation, and the delay before the first one.
+    retries: u32,
+    retry_delay: Duration,
+}
+
+impl Launcher<'_> {
+    /// `run`, repeated while the real tool fails because a file it writes is locked by another
+    /// process (see `wrapper::is_sharing_violation`). Other failures are returned at once; the
+    /// result is always that of the last attempt.
+    fn run_retrying(
+        &self,
+        args: &[OsString],
+        capture: bool,
+    ) -> io::Result<(ExitStatus, Option<capture::Captured>)> {
+        let mut delay = self.retry_delay;
+        for attempt in 1..=self.retries {
+            let result = self.run(args, true)?;
+            let locked = matches!(&result, (status, Some(output))
+                if !status.success() && wrapper::is_sharing_violation(&output.text()));
+            if !locked {
+                return Ok(result);
+            }
+            diag!(
+                self.tool,
+                "attempt {attempt}/{} failed: output file is in use by another process; retrying in {} ms",
+                self.retries + 1,
+                delay.as_millis()
+            );
+            thread::sleep(delay);
+            delay = delay.saturating_mul(2);
+        }
+        self.run(args, capture)
+    }
+
+    /// Runs one invocation of the real tool. Its output is captured (and still echoed) when
+    /// the caller needs to inspect it, an output log is open or annotations are wanted;
+    /// otherwise stdio is inherited.
+    fn run(
+        &self,
+        args: &[OsString],
+        capture: bool,
+    ) -> io::Result<(ExitStatus, Option<capture::Captured>)> {
+        let (tool, real_exe) = (self.tool, self.real_exe);
+        // Too long for a Windows command line: hand light a response file instead. It is
+        // removed when this function returns, after the child has exited.
+        let response_file = (self.response_files
+            && wrapper::display_command(real_exe, args).len() > response_file::MAX_COMMAND_LINE)
+            .then(|| response_file::TempResponseFile::write(args))
+            .and_then(|written| match written {
+                Ok(file) => Some(file),
+                Err(e) => {
+                    diag!(
+                        tool,
+                        "unable to write a response file, passing the long command line as-is: {e}"
+                    );
+                    None
+                }
+            });
+        let response_args = response_file
+            .as_ref()
+            .map(|file| vec![OsString::from(file.argument())]);
+        let args = response_args.as_deref().unwrap_or(args);
+
+        if let Some(mut log) = self.output_log.as_ref() {
+            let _ = writeln!(
+                log,
+                "==== {} {}",
+                log::utc_timestamp(SystemTime::now()),
+                wrapper::display_command(real_exe, args)
+            );
+        } else if !capture && self.annotations.is_none() {
+            return self.spawn_and_wait(args).map(|status| (status, None));
+        }
+        let (status, output) = capture::run_teed(
+            &mut wrapper::build_command(tool, real_exe, args, self.wine.as_deref(), self.depth),
+            self.output_log.as_ref(),
+            self.timeout,
+        )?;
+        if let Some(style) = self.annotations {
+            for line in output.text().lines() {
+                if let Some(annotation) = annotate::to_annotation(line, style) {
+                    println!("{annotation}");
+                }
+            }
+        }
+        Ok((status, Some(output)))
+    }
+
+    /// Runs the real linker with the wrapper's own stdio handles so its ICE/LGHT diagnostics
+    /// reach whatever is reading the wrapper's output.
+    fn spawn_and_wait(&self, args: &[OsString]) -> io::Result<ExitStatus> {
+        let mut child = wrapper::build_command(
+            self.tool,
+            self.real_exe,
+            args,
+            self.wine.as_deref(),
+            self.depth,
+        )
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+        wait::wait(&mut child, self.timeout)
+    }
+
+    /// The wrapper's exit code for the last attempt, which ran with `args`.
+    fn exit_code(&self, args: &[OsString], result: io::Result<ExitStatus>) -> i32 {
+        let tool = self.tool;
+        match result {
+            Ok(status) => wrapper::exit_code(status),
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                fatal!(
+                    tool,
+                    "timed out: {e}: {}",
+                    wrapper::display_command(self.real_exe, args)
+                );
+                wait::TIMED_OUT
+            }
+            Err(e) => {
+                fatal!(tool, "failed to start '{}': {e}", self.real_exe.display());
+                1
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::testutil::{Recorder, TempDir};
+
+    fn strings(items: &[&str]) -> Vec<OsString> {
+        items.iter().map(OsString::from).collect()
+    }
+
+    /// Fails ICE validation unless `-sval` is passed, like light on a locked-down agent.
+    const ICE_FAILING_LINKER: &str = "case \" $* \" in *' -sval '*) exit 0;; esac\n\
+        echo 'light.exe : error LGHT0217 : Error executing ICE action' >&2\n\
+        exit 217";
+
+    #[test]
+    fn first_link_keeps_validation_on() {
+        let dir = TempDir::new("app-default");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        let code = run(
+            Tool::LIGHT,
+            &Vars::default(),
+            &dir.join("light.exe"),
+            strings(&["-out", "app.msi", "main.wixobj"]),
+        );
+        assert_eq!(code, 0);
+        assert_eq!(
+            recorder.runs(),
+            vec![strings(&["-out", "app.msi", "-sacl", "main.wixobj"])]
+        );
+    }
+
+    #[test]
+    fn retries_with_sval_when_validation_fails() {
+        let dir = TempDir::new("app-fallback");
+        let recorder = Recorder::with_script(&dir, "light-real.exe", ICE_FAILING_LINKER);
+        let code = run(
+            Tool::LIGHT,
+            &Vars::default(),
+            &dir.join("light.exe"),
+            strings(&["-out", "app.msi", "main.wixobj"]),
+        );
+        assert_eq!(code, 0);
+        assert_eq!(
+            recorder.runs(),
+            vec![
+                strings(&["-out", "app.msi", "-sacl", "main.wixobj"]),
+                strings(&["-out", "app.msi", "-sval", "-sacl", "main.wixobj"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn timing_lines_give_seconds_and_how_the_attempt_ended() {
+        use std::os::unix::process::ExitStatusExt;
+        assert_eq!(seconds(Duration::from_millis(12_345)), "12.35s");
+        assert_eq!(seconds(Duration::ZERO), "0.00s");
+        assert_eq!(outcome(&Ok(ExitStatus::from_raw(0))), "exit 0");
+        assert_eq!(outcome(&Ok(ExitStatus::from_raw(217 << 8))), "exit 217");
+        let timed_out = io::Error::new(io::ErrorKind::TimedOut, "after 1s");
+        assert_eq!(outcome(&Err(timed_out)), "timed out");
+        let missing = io::Error::from(io::ErrorKind::NotFound);
+        assert_eq!(outcome(&Err(missing)), "failed to start");
+
+        // Timing only reports; the links and their results are unchanged.
+        let dir = TempDir::new("app-timing");
+        let recorder = Recorder::with_script(&dir, "light-real.exe", ICE_FAILING_LINKER);
+        let vars = Vars::from_pairs(&[
+            ("WIX_LIGHT_WRAPPER_ADAPTIVE", "1"),
+            ("WIX_LIGHT_WRAPPER_TIMING", "1"),
+        ]);
+        let code = run(
+            Tool::LIGHT,
+            &vars,
+            &dir.join("light.exe"),
+            strings(&["-out", "app.msi", "main.wixobj"]),
+        );
+        assert_eq!(code, 0);
+        assert_eq!(recorder.runs().len(), 2);
+    }
+
+    #[test]
+    fn adaptive_mode_first_links_without_any_injected_flags() {
+        let dir = TempDir::new("app-adaptive");
+        let recorder = Recorder::with_script(&dir, "light-real.exe", ICE_FAILING_LINKER);
+        let vars = Vars::from_pairs(&[("WIX_LIGHT_WRAPPER_ADAPTIVE", "1")]);
+        let code = run(
+            Tool::LIGHT,
+            &vars,
+            &dir.join("light.exe"),
+            strings(&["-out", "app.msi", "main.wixobj"]),
+        );
+        assert_eq!(code, 0);
+        assert_eq!(
+            recorder.runs(),
+            vec![
+                strings(&["-out", "app.msi", "main.wixobj"]),
+                strings(&["-out", "app.msi", "-sval", "-sacl", "main.wixobj"]),
+            ]
+        );
+
+        let dir = TempDir::new("app-adaptive-ok");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        assert_eq!(
+            run(
+                Tool::LIGHT,
+                &vars,
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"])
+            ),
+            0
+        );
+        assert_eq!(recorder.runs(), vec![strings(&["a.wixobj"])]);
+    }
+
+    #[test]
+    fn other_failures_are_not_retried() {
+        let dir = TempDir::new("app-no-fallback");
+        let recorder = Recorder::with_script(
+            &dir,
+            "light-real.exe",
+            "echo 'error LGHT0103 : cannot find file' >&2; exit 103",
+        );
+        let code = run(
+            Tool::LIGHT,
+            &Vars::default(),
+            &dir.join("light.exe"),
+            strings(&["a.wixobj"]),
+        );
+        assert_eq!(code, 103);
+        assert_eq!(recorder.runs().len(), 1);
+    }
+
+    #[test]
+    fn suppressed_ices_replace_sval_entirely() {
+        let dir = TempDir::new("app-sice");
+        let recorder = Recorder::with_script(&dir, "light-real.exe", ICE_FAILING_LINKER);
+        let vars = Vars::from_pairs(&[
+            ("LIGHT_WRAPPER_SUPPRESS_ICES", "ICE61,ice69"),
+            ("LIGHT_WRAPPER_PREPEND_ARGS", "-sval"),
+        ]);
+        assert_eq!(
+            run(
+                Tool::LIGHT,
+                &vars,
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"])
+            ),
+            217
+        );
+        assert_eq!(
+            recorder.runs(),
+            vec![strings(&[
+                "-sice:ICE61",
+                "-sice:ICE69",
+                "-sacl",
+                "a.wixobj"
+            ])]
+        );
+    }
+
+    #[test]
+    fn sice_setting_wins_over_configured_sval() {
+        let dir = TempDir::new("app-sice-var");
+        let recorder = Recorder::with_script(&dir, "light-real.exe", ICE_FAILING_LINKER);
+        let sice = ("WIX_LIGHT_WRAPPER_SICE", "ICE03,ICE57");
+        for sval in [
+            ("WIX_LIGHT_WRAPPER_SUPPRESS", "sval,sacl"),
+            ("LIGHT_WRAPPER_ALWAYS_SVAL", "1"),
+            ("LIGHT_WRAPPER_APPEND_ARGS", "-sval"),
+        ] {
+            let code = run(
+                Tool::LIGHT,
+                &Vars::from_pairs(&[sice, sval]),
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"]),
+            );
+            assert_eq!(code, 217, "{sval:?}");
+            // Linked once, with only the targeted suppressions: no `-sval`, not even on retry.
+            assert_eq!(
+                recorder.runs(),
+                vec![strings(&[
+                    "-sice:ICE03",
+                    "-sice:ICE57",
+                    "-sacl",
+                    "a.wixobj"
+                ])],
+                "{sval:?}"
+            );
+            std::fs::remove_file(dir.join("light-real.exe.args")).unwrap();
+        }
+
+        // The older name takes precedence when both are set.
+        let vars = Vars::from_pairs(&[sice, ("LIGHT_WRAPPER_SUPPRESS_ICES", "ICE61")]);
+        run(
+            Tool::LIGHT,
+            &vars,
+            &dir.join("light.exe"),
+            strings(&["a.wixobj"]),
+        );
+        assert_eq!(
+            recorder.args(),
+            strings(&["-sice:ICE61", "-sacl", "a.wixobj"])
+        );
+    }
+
+    #[test]
+    fn always_sval_links_once_with_sval() {
+        let dir = TempDir::new("app-always-sval");
+        let recorder = Recorder::with_script(&dir, "light-real.exe", ICE_FAILING_LINKER);
+        let vars = Vars::from_pairs(&[("LIGHT_WRAPPER_ALWAYS_SVAL", "1")]);
+        assert_eq!(
+            run(
+                Tool::LIGHT,
+                &vars,
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"])
+            ),
+            0
+        );
+        assert_eq!(
+            recorder.runs(),
+            vec![strings(&["-sval", "-sacl", "a.wixobj"])]
+        );
+    }
+
+    #[test]
+    fn environment_beats_config_file_beats_defaults() {
+        let dir = TempDir::new("app-config-precedence");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        let link = |pairs: &[(&str, &str)]| {
+            let vars = Vars::from_pairs(pairs);
+            let code = run(
+                Tool::LIGHT,
+                &vars,
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"]),
+            );
+            assert_eq!(code, 0);
+            recorder.args()
+        };
+
+        let always = ("LIGHT_WRAPPER_ALWAYS_SVAL", "1");
+        assert_eq!(link(&[always]), strings(&["-sval", "-sacl", "a.wixobj"]));
+
+        std::fs::write(
+            dir.join("wix-light-wrapper.toml"),
+            "suppress = [\"sacl\"]\nextra_flags = [\"-spdb\"]\nadaptive = true\n",
+        )
+        .unwrap();
+        assert_eq!(link(&[always]), strings(&["-sacl", "-spdb", "a.wixobj"]));
+        assert_eq!(link(&[]), strings(&["-spdb", "a.wixobj"]));
+        assert_eq!(
+            link(&[
+                always,
+                ("WIX_LIGHT_WRAPPER_SUPPRESS", "sval"),
+                ("WIX_LIGHT_WRAPPER_EXTRA_FLAGS", "-nologo"),
+            ]),
+            strings(&["-sval", "-nologo", "a.wixobj"])
+        );
+        assert_eq!(
+            link(&[("WIX_LIGHT_WRAPPER_ADAPTIVE", "0")]),
+            strings(&["-sacl", "-spdb", "a.wixobj"])
+        );
+        assert_eq!(recorder.runs().len(), 5);
+    }
+
+    #[test]
+    fn config_path_can_be_given_explicitly() {
+        let dir = TempDir::new("app-config-path");
+        let recorder = Recorder::install(&dir, "linker", 0);
+        let config = dir.join("build.toml");
+        std::fs::write(&config, "real_exe = \"linker\"\nsuppress = []\n").unwrap();
+        let vars = Vars::from_pairs(&[("WIX_LIGHT_WRAPPER_CONFIG", config.to_str().unwrap())]);
+        let wrapper_exe = dir.join("elsewhere").join("light.exe");
+        assert_eq!(
+            run(Tool::LIGHT, &vars, &wrapper_exe, strings(&["a.wixobj"])),
+            0
+        );
+        assert_eq!(recorder.args(), strings(&["a.wixobj"]));
+
+        let missing = dir.join("missing.toml");
+        let vars = Vars::from_pairs(&[("WIX_LIGHT_WRAPPER_CONFIG", missing.to_str().unwrap())]);
+        assert_eq!(
+            run(Tool::LIGHT, &vars, &wrapper_exe, strings(&["a.wixobj"])),
+            1
+        );
+        assert_eq!(recorder.runs().len(), 1);
+    }
+
+    #[test]
+    fn wix_v4_and_later_get_no_v3_flags() {
+        let dir = TempDir::new("app-toolset");
+        let link = |version: (u16, u16), pairs: &[(&str, &str)]| {
+            let recorder = Recorder::install(&dir, "light-real.exe", 0);
+            let mut stub = OpenOptions::new()
+                .append(true)
+                .open(dir.join("light-real.exe"))
+                .unwrap();
+            stub.write_all(&toolset::version_resource(version.0, version.1, 0, 0))
+                .unwrap();
+            drop(stub);
+            let vars = Vars::from_pairs(pairs);
+            let code = run(
+                Tool::LIGHT,
+                &vars,
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"]),
+            );
+            assert_eq!(code, 0);
+            let runs = recorder.runs();
+            std::fs::remove_file(dir.join("light-real.exe.args")).unwrap();
+            runs
+        };
+
+        let always = ("LIGHT_WRAPPER_ALWAYS_SVAL", "1");
+        assert_eq!(
+            link((3, 11), &[always]),
+            [strings(&["-sval", "-sacl", "a.wixobj"])]
+        );
+        assert_eq!(link((4, 0), &[]), [strings(&["a.wixobj"])]);
+        assert_eq!(
+            link(
+                (5, 0),
+                &[
+                    always,
+                    ("WIX_LIGHT_WRAPPER_SUPPRESS", "sval"),
+                    ("LIGHT_WRAPPER_SUPPRESS_ICES", "ICE61"),
+                ]
+            ),
+            [strings(&["-sice:ICE61", "a.wixobj"])]
+        );
+        // Asked-for flags are forwarded whatever the version; `-ice:` is a v3 switch as well.
+        let ice = ("WIX_LIGHT_WRAPPER_SUPPRESS", "sval,sacl,ice:ICE03");
+        for version in [(3, 11), (5, 0)] {
+            assert_eq!(
+                link(version, &[always, ice]),
+                [strings(&["-sval", "-sacl", "-ice:ICE03", "a.wixobj"])]
+            );
+        }
+        assert_eq!(
+            link(
+                (5, 0),
+                &[
+                    ("WIX_LIGHT_WRAPPER_SUPPRESS_V5", "sw1076"),
+                    ("WIX_LIGHT_WRAPPER_SUPPRESS_V3", "sacl"),
+                ]
+            ),
+            [strings(&["-sw1076", "a.wixobj"])]
+        );
+        assert_eq!(
+            link(
+                (3, 11),
+                &[always, ("WIX_LIGHT_WRAPPER_SUPPRESS_V3", "sacl")]
+            ),
+            [strings(&["-sacl", "a.wixobj"])]
+        );
+    }
+
+    #[test]
+    fn version_probe_picks_the_flags_and_is_cached() {
+        let dir = TempDir::new("app-toolset-probe");
+        let temp = TempDir::new("app-toolset-probe-temp");
+        let temp_dir = temp.join("");
+        let wrapper_exe = dir.join("light.exe");
+        std::fs::write(&wrapper_exe, "wrapper").unwrap();
+        let link = |banner: &str, pairs: &[(&str, &str)]| {
+            let recorder = Recorder::with_banner(&dir, "light-real.exe", banner, "exit 0");
+            let mut pairs = pairs.to_vec();
+            pairs.push(("TMPDIR", temp_dir.to_str().unwrap()));
+            let code = run(
+                Tool::LIGHT,
+                &Vars::from_pairs(&pairs),
+                &wrapper_exe,
+                strings(&["a.wixobj"]),
+            );
+            assert_eq!(code, 0);
+            recorder.args()
+        };
+        let record = || {
+            let entry = std::fs::read_dir(&temp_dir).unwrap().next().unwrap();
+            std::fs::read_to_string(entry.unwrap().path()).unwrap()
+        };
+        let suppress = ("WIX_LIGHT_WRAPPER_SUPPRESS_V3", "sacl,sw1076");
+        assert_eq!(
+            link("WiX Toolset version 5.0.2+aa65968", &[suppress]),
+            strings(&["a.wixobj"])
+        );
+        assert!(record().contains("\t5.0.2.0\t"), "{}", record());
+        assert!(!dir.join("light-wrapper.version").exists());
+
+        // Replacing the stub changes its size, so it is probed afresh.
+        assert_eq!(
+            link(
+                "Windows Installer XML Toolset Linker version 3.11.2.4516",
+                &[suppress]
+            ),
+            strings(&["-sacl", "-sw1076", "a.wixobj"])
+        );
+        assert!(record().contains("\t3.11.2.4516\t"), "{}", record());
+    }
+
+    #[test]
+    fn copy_under_another_name_wraps_that_tool() {
+        let dir = TempDir::new("app-other-tool");
+        let recorder = Recorder::install(&dir, "bar-real.exe", 0);
+        let light = Recorder::install(&dir, "light-real.exe", 0);
+        let wrapper_exe = dir.join("bar.exe");
+        let tool = Tool::from_exe(&wrapper_exe);
+        let vars = Vars::from_pairs(&[
+            ("WIX_LIGHT_WRAPPER_SUPPRESS_BAR", "quiet,nologo"),
+            ("WIX_LIGHT_WRAPPER_SUPPRESS", "sval"),
+        ]);
+        assert_eq!(
+            run(tool, &vars, &wrapper_exe, strings(&["in.txt", "-quiet"])),
+            0
+        );
+        assert_eq!(recorder.runs(), [strings(&["-nologo", "in.txt", "-quiet"])]);
+        assert!(!light.was_run());
+
+        // Nothing is injected by default, and a failing run is not retried.
+        let recorder = Recorder::install(&dir, "bar-real.exe", 3);
+        assert_eq!(
+            run(tool, &Vars::default(), &wrapper_exe, strings(&["in.txt"])),
+            3
+        );
+        assert_eq!(recorder.runs().last().unwrap(), &strings(&["in.txt"]));
+    }
+
+    #[test]
+    fn candle_copy_forwards_to_candle_real_without_light_flags() {
+        let dir = TempDir::new("app-candle");
+        let recorder = Recorder::install(&dir, "candle-real.exe", 0);
+        let vars = Vars::from_pairs(&[
+            ("WIX_LIGHT_WRAPPER_SUPPRESS", "sval"),
+            ("WIX_CANDLE_WRAPPER_SUPPRESS", "sw1077"),
+        ]);
+        let code = run(
+            Tool::named("candle"),
+            &vars,
+            &dir.join("candle.exe"),
+            strings(&["main.wxs"]),
+        );
+        assert_eq!(code, 0);
+        assert_eq!(recorder.args(), strings(&["-sw1077", "main.wxs"]));
+    }
+
+    #[test]
+    fn heat_copy_injects_only_what_is_asked_for() {
+        let dir = TempDir::new("app-heat");
+        let recorder = Recorder::install(&dir, "heat-real.exe", 0);
+        let wrapper_exe = dir.join("heat.exe");
+        let tool = Tool::from_exe(&wrapper_exe);
+        let harvest = strings(&["dir", "assets", "-out", "assets.wxs"]);
+        assert_eq!(
+            run(tool, &Vars::default(), &wrapper_exe, harvest.clone()),
+            0
+        );
+        assert_eq!(recorder.args(), harvest);
+
+        // What is asked for is forwarded as given, even light's `-sval`.
+        let vars = Vars::from_pairs(&[("WIX_HEAT_WRAPPER_SUPPRESS", "sreg,sval,scom")]);
+        assert_eq!(run(tool, &vars, &wrapper_exe, harvest.clone()), 0);
+        let mut expected = strings(&["-sreg", "-sval", "-scom"]);
+        expected.extend(harvest);
+        assert_eq!(recorder.args(), expected);
+    }
+
+    #[test]
+    fn log_path_records_each_invocation() {
+        let dir = TempDir::new("app-log");
+        Recorder::install(&dir, "light-real.exe", 2);
+        let log_path = dir.join("build.log");
+        let vars = Vars::from_pairs(&[("LIGHT_WRAPPER_LOG_PATH", log_path.to_str().unwrap())]);
+        assert_eq!(
+            run(
+                Tool::LIGHT,
+                &vars,
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"])
+            ),
+            2
+        );
+        assert_eq!(
+            run(
+                Tool::LIGHT,
+                &vars,
+                &dir.join("light.exe"),
+                strings(&["b.wixobj"])
+            ),
+            2
+        );
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(
+            lines[1].contains(r#""args":["-sacl","b.wixobj"],"sval_fallback":false"#),
+            "{}",
+            lines[1]
+        );
+        assert!(lines[1].contains(r#""duration_ms":"#), "{}", lines[1]);
+        assert!(
+            lines[1].contains(r#""exit_code":2,"error":null"#),
+            "{}",
+            lines[1]
+        );
+        assert!(
+            lines[1].contains(r#""arg_count":2,"injected":["-sacl"],"adaptive":false"#),
+            "{}",
+            lines[1]
+        );
+        assert!(lines[1].contains(r#""finished":"20"#), "{}", lines[1]);
+    }
+
+    #[test]
+    fn log_records_the_bypassed_validation() {
+        let dir = TempDir::new("app-log-validation");
+        Recorder::with_script(&dir, "light-real.exe", ICE_FAILING_LINKER);
+        let log_path = dir.join("build.log");
+        let vars = Vars::from_pairs(&[("LIGHT_WRAPPER_LOG_PATH", log_path.to_str().unwrap())]);
+        let code = run(
+            Tool::LIGHT,
+            &vars,
+            &dir.join("light.exe"),
+            strings(&["a.wixobj"]),
+        );
+        assert_eq!(code, 0);
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        assert!(
+            log.contains(r#""sval_fallback":true,"validation_codes":["LGHT0217"]"#),
+            "{log}"
+        );
+    }
+
+    #[test]
+    fn report_is_written_for_failed_links_without_changing_the_exit_code() {
+        let dir = TempDir::new("app-report");
+        Recorder::with_script(
+            &dir,
+            "light-real.exe",
+            "for out; do :; done\necho partial > \"$out\"\nexit 5",
+        );
+        let report_path = dir.join("report.json");
+        let msi = dir.join("app.msi");
+        let vars = Vars::from_pairs(&[
+            ("LIGHT_WRAPPER_REPORT", report_path.to_str().unwrap()),
+            ("LIGHT_WRAPPER_ALWAYS_SVAL", "1"),
+        ]);
+        let args = strings(&["-out", msi.to_str().unwrap()]);
+        assert_eq!(
+            run(Tool::LIGHT, &vars, &dir.join("light.exe"), args.clone()),
+            5
+        );
+        let report = std::fs::read_to_string(&report_path).unwrap();
+        assert!(report.contains(r#""exit_code":5"#), "{report}");
+        assert!(
+            report.ends_with(",\"output_size\":null,\"output_sha256\":null}\n"),
+            "{report}"
+        );
+
+        // An unwritable report is mentioned, not fatal.
+        let vars = Vars::from_pairs(&[(
+            "LIGHT_WRAPPER_REPORT",
+            dir.join("missing/report.json").to_str().unwrap(),
+        )]);
+        assert_eq!(run(Tool::LIGHT, &vars, &dir.join("light.exe"), args), 5);
+    }
+
+    #[test]
+    fn output_log_tees_every_attempt_under_a_header() {
+        let dir = TempDir::new("app-output-log");
+        let recorder = Recorder::with_script(&dir, "light-real.exe", ICE_FAILING_LINKER);
+        let log_path = dir.join("wix-output.log");
+        let vars = Vars::from_pairs(&[("WIX_LIGHT_WRAPPER_LOG", log_path.to_str().unwrap())]);
+        assert_eq!(
+            run(
+                Tool::LIGHT,
+                &vars,
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"])
+            ),
+            0
+        );
+        assert_eq!(recorder.runs().len(), 2);
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        let headers: Vec<&str> = log.lines().filter(|l| l.starts_with("==== ")).collect();
+        assert_eq!(headers.len(), 2, "{log}");
+        assert!(
+            headers[0].ends_with("light-real.exe -sacl a.wixobj"),
+            "{log}"
+        );
+        assert!(
+            headers[1].ends_with("light-real.exe -sval -sacl a.wixobj"),
+            "{log}"
+        );
+        assert!(log.contains("error LGHT0217"), "{log}");
+    }
+
+    #[test]
+    fn output_file_can_be_truncated_first() {
+        let dir = TempDir::new("app-output-file");
+        Recorder::with_script(
+            &dir,
+            "light-real.exe",
+            "echo linked; echo warning LGHT1076 >&2",
+        );
+        let log_path = dir.join("light-output.txt");
+        std::fs::write(&log_path, "previous build\n").unwrap();
+        let file = log_path.to_str().unwrap();
+        let run_with = |vars: &[(&str, &str)]| {
+            let vars = Vars::from_pairs(vars);
+            run(
+                Tool::LIGHT,
+                &vars,
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"]),
+            )
+        };
+
+        assert_eq!(run_with(&[("LIGHT_WRAPPER_OUTPUT_FILE", file)]), 0);
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        assert!(log.starts_with("previous build\n"), "{log}");
+
+        let vars = [
+            ("LIGHT_WRAPPER_OUTPUT_FILE", file),
+            ("LIGHT_WRAPPER_OUTPUT_TRUNCATE", "1"),
+        ];
+        assert_eq!(run_with(&vars), 0);
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        assert!(log.starts_with("==== "), "{log}");
+        assert_eq!(log.matches("warning LGHT1076").count(), 1, "{log}");
+        assert!(log.contains("linked\n"), "{log}");
+    }
+
+    #[test]
+    fn annotation_style_must_be_known() {
+        let dir = TempDir::new("app-annotations");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        let vars = Vars::from_pairs(&[("WIX_LIGHT_WRAPPER_ANNOTATIONS", "gitlab")]);
+        let code = run(
+            Tool::LIGHT,
+            &vars,
+            &dir.join("light.exe"),
+            strings(&["a.wixobj"]),
+        );
+        assert_eq!(code, 1);
+        assert!(!recorder.was_run());
+
+        // With annotations on, the output is captured but the exit code is unchanged.
+        let vars = Vars::from_pairs(&[
+            ("GITHUB_ACTIONS", "true"),
+            ("LIGHT_WRAPPER_ALWAYS_SVAL", "1"),
+        ]);
+        Recorder::install(&dir, "light-real.exe", 5);
+        let code = run(
+            Tool::LIGHT,
+            &vars,
+            &dir.join("light.exe"),
+            strings(&["a.wixobj"]),
+        );
+        assert_eq!(code, 5);
+    }
+
+    #[test]
+    fn unopenable_output_log_still_links() {
+        let dir = TempDir::new("app-output-log-missing");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        let log_path = dir.join("no-such-dir").join("wix-output.log");
+        let vars = Vars::from_pairs(&[("WIX_LIGHT_WRAPPER_LOG", log_path.to_str().unwrap())]);
+        assert_eq!(
+            run(
+                Tool::LIGHT,
+                &vars,
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"])
+            ),
+            0
+        );
+        assert!(recorder.was_run());
+        assert!(!log_path.exists());
+    }
+
+    #[test]
+    fn recorder_sees_the_forwarded_link() {
+        for code in [0, 1, 217] {
+            let dir = TempDir::new("app-contract");
+            let recorder = Recorder::install(&dir, "light-real.exe", code);
+            let vars = Vars::from_pairs(&[
+                ("LIGHT_WRAPPER_ALWAYS_SVAL", "1"),
+                ("LIGHT_WRAPPER_DEDUPE", "1"),
+            ]);
+            let args = [
+                "-out", "app.msi", "-sacl", "a.wixobj", "b.wixobj", "a.wixobj",
+            ];
+            assert_eq!(
+                run(Tool::LIGHT, &vars, &dir.join("light.exe"), strings(&args)),
+                code
+            );
+            assert_eq!(
+                recorder.runs(),
+                vec![strings(&[
+                    "-out", "app.msi", "-sacl", "-sval", "a.wixobj", "b.wixobj"
+                ])]
+            );
+            assert_eq!(
+                recorder.cwd(),
+                env::current_dir().unwrap().canonicalize().unwrap()
+            );
+        }
+
+        // Nothing to forward to: the wrapper fails on its own.
+        let dir = TempDir::new("app-contract-missing");
+        let elsewhere = Recorder::install(&dir, "other-real.exe", 0);
+        let vars = Vars::from_pairs(&[("PATH", dir.join("empty").to_str().unwrap())]);
+        assert_eq!(
+            run(
+                Tool::LIGHT,
+                &vars,
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"])
+            ),
+            1
+        );
+        assert!(!elsewhere.was_run());
+    }
+
+    #[test]
+    fn later_invocations_reuse_the_resolved_real_tool() {
+        let dir = TempDir::new("app-resolve-cache");
+        let bin = TempDir::new("app-resolve-cache-bin");
+        let temp = TempDir::new("app-resolve-cache-temp");
+        let on_path = Recorder::install(&bin, "light-real.exe", 0);
+        let bin_dir = bin.join("");
+        let vars = Vars::from_pairs(&[
+            ("PATH", bin_dir.to_str().unwrap()),
+            ("TMPDIR", temp.join("").to_str().unwrap()),
+        ]);
+        let wrapper_exe = dir.join("light.exe");
+        std::fs::write(&wrapper_exe, "wrapper").unwrap();
+        let link = |vars: &Vars| run(Tool::LIGHT, vars, &wrapper_exe, strings(&["a.wixobj"]));
+
+        assert_eq!(link(&vars), 0);
+        assert_eq!(on_path.runs().len(), 1);
+
+        assert_eq!(link(&vars), 0);
+        assert_eq!(on_path.runs().len(), 2);
+
+        // A sibling light-real.exe that appears later wins over the remembered one.
+        let sibling = Recorder::install(&dir, "light-real.exe", 0);
+        assert_eq!(link(&vars), 0);
+        assert_eq!(on_path.runs().len(), 2);
+        assert_eq!(sibling.runs().len(), 1);
+        assert_eq!(link(&vars), 0);
+        assert_eq!(sibling.runs().len(), 2);
+    }
+
+    #[test]
+    fn real_tool_without_execute_permission_is_still_tried() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = TempDir::new("app-not-executable");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        let real = dir.join("light-real.exe");
+        std::fs::set_permissions(&real, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(
+            run(
+                Tool::LIGHT,
+                &Vars::default(),
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"])
+            ),
+            1
+        );
+        assert!(!recorder.was_run());
+    }
+
+    #[test]
+    fn propagates_unusual_exit_codes_and_signals() {
+        let dir = TempDir::new("app-exit-code");
+        Recorder::install(&dir, "light-real.exe", 203);
+        let vars = Vars::from_pairs(&[("LIGHT_WRAPPER_ALWAYS_SVAL", "1")]);
+        assert_eq!(
+            run(
+                Tool::LIGHT,
+                &vars,
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"])
+            ),
+            203
+        );
+
+        let dir = TempDir::new("app-signal");
+        Recorder::with_script(&dir, "light-real.exe", "kill -TERM $$");
+        assert_eq!(
+            run(
+                Tool::LIGHT,
+                &vars,
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"])
+            ),
+            128 + 15
+        );
+    }
+
+    #[test]
+    fn long_command_lines_go_through_a_response_file() {
+        let dir = TempDir::new("app-long");
+        let copy = dir.join("copy.rsp");
+        let recorder = Recorder::with_script(
+            &dir,
+            "light-real.exe",
+            &format!("cp \"${{1#@}}\" '{}'", copy.display()),
+        );
+        let binder = format!("-b {}", "x".repeat(response_file::MAX_COMMAND_LINE));
+        let vars = Vars::from_pairs(&[("LIGHT_WRAPPER_ALWAYS_SVAL", "1")]);
+        let code = run(
+            Tool::LIGHT,
+            &vars,
+            &dir.join("light.exe"),
+            strings(&[&binder, "main.wixobj"]),
+        );
+        assert_eq!(code, 0);
+        let args = recorder.args();
+        assert_eq!(args.len(), 1);
+        let argument = args[0].to_str().unwrap();
+        assert!(
+            argument.starts_with('@') && argument.ends_with(".rsp"),
+            "{args:?}"
+        );
+        assert!(!Path::new(&argument[1..]).exists());
+        assert_eq!(
+            response_file::expand(&strings(&[&format!("@{}", copy.display())])),
+            strings(&[&binder, "-sval", "-sacl", "main.wixobj"])
+        );
+    }
+
+    #[test]
+    fn uninstall_exit_codes_follow_the_directory_state() {
+        let uninstall = |dir: &TempDir, wrapper: &Path| {
+            run(
+                Tool::LIGHT,
+                &Vars::default(),
+                wrapper,
+                strings(&["--wrapper-uninstall", dir.join("").to_str().unwrap()]),
+            )
+        };
+        let setup = |label: &str, light: Option<&[u8]>, real: Option<&[u8]>| {
+            let dir = TempDir::new(label);
+            let wrapper = dir.join("wrapper.exe");
+            std::fs::write(&wrapper, b"wrapper build").unwrap();
+            if let Some(bytes) = light {
+                std::fs::write(dir.join("light.exe"), bytes).unwrap();
+            }
+            if let Some(bytes) = real {
+                std::fs::write(dir.join("light-real.exe"), bytes).unwrap();
+            }
+            (dir, wrapper)
+        };
+        let light = |dir: &TempDir| std::fs::read(dir.join("light.exe")).ok();
+
+        // Wrapped: the copy is deleted and the real linker renamed back.
+        let (dir, wrapper) = setup("uninstall-wrapped", Some(b"wrapper build"), Some(b"real"));
+        assert_eq!(uninstall(&dir, &wrapper), 0);
+        assert_eq!(light(&dir).as_deref(), Some(&b"real"[..]));
+        assert!(!dir.join("light-real.exe").exists());
+
+        // Half restored: only the rename is left to do.
+        let (dir, wrapper) = setup("uninstall-partial", None, Some(b"real"));
+        assert_eq!(uninstall(&dir, &wrapper), 0);
+        assert_eq!(light(&dir).as_deref(), Some(&b"real"[..]));
+
+        // Never wrapped: nothing to do.
+        let (dir, wrapper) = setup("uninstall-clean", Some(b"real"), None);
+        assert_eq!(uninstall(&dir, &wrapper), install::NOTHING_TO_UNINSTALL);
+        assert_eq!(light(&dir).as_deref(), Some(&b"real"[..]));
+
+        // light.exe is some other linker: it is not deleted.
+        let (dir, wrapper) = setup("uninstall-foreign", Some(b"other"), Some(b"real"));
+        assert_eq!(uninstall(&dir, &wrapper), 1);
+        assert_eq!(light(&dir).as_deref(), Some(&b"other"[..]));
+        assert!(dir.join("light-real.exe").exists());
+
+        // The wrapper with nothing to restore, or an empty directory, is an error.
+        let (dir, wrapper) = setup("uninstall-orphan", Some(b"wrapper build"), None);
+        assert_eq!(uninstall(&dir, &wrapper), 1);
+        assert!(dir.join("light.exe").exists());
+        let (dir, wrapper) = setup("uninstall-empty", None, None);
+        assert_eq!(uninstall(&dir, &wrapper), 1);
+    }
+
+    #[test]
+    fn hung_linker_is_killed_after_the_timeout() {
+        let dir = TempDir::new("app-timeout");
+        let recorder = Recorder::with_script(&dir, "light-real.exe", "exec sleep 30");
+        let vars = Vars::from_pairs(&[("LIGHT_WRAPPER_TIMEOUT_SECS", "1")]);
+        let started = Instant::now();
+        let code = run(
+            Tool::LIGHT,
+            &vars,
+            &dir.join("light.exe"),
+            strings(&["a.wixobj"]),
+        );
+        assert_eq!(code, wait::TIMED_OUT);
+        assert!(started.elapsed() < Duration::from_secs(20));
+        // A timeout is not an ICE failure, so there is no -sval retry.
+        assert_eq!(recorder.runs().len(), 1);
+
+        let vars = Vars::from_pairs(&[("WIX_LIGHT_WRAPPER_TIMEOUT_SECS", "1")]);
+        let code = run(
+            Tool::LIGHT,
+            &vars,
+            &dir.join("light.exe"),
+            strings(&["a.wixobj"]),
+        );
+        assert_eq!(code, wait::TIMED_OUT);
+
+        for var in [
+            "LIGHT_WRAPPER_TIMEOUT_SECS",
+            "WIX_LIGHT_WRAPPER_TIMEOUT_SECS",
+        ] {
+            let vars = Vars::from_pairs(&[(var, "soon")]);
+            assert_eq!(
+                run(
+                    Tool::LIGHT,
+                    &vars,
+                    &dir.join("light.exe"),
+                    strings(&["a.wixobj"])
+                ),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn lock_serializes_invocations_and_is_always_released() {
+        let dir = TempDir::new("app-lock");
+        let recorder = Recorder::with_script(
+            &dir,
+            "light-real.exe",
+            "for last; do :; done; [ \"$last\" = hang.wixobj ] && exec sleep 30; exit 3",
+        );
+        let lock_path = dir.join("light.lock");
+        let lock_str = lock_path.to_str().unwrap();
+        let link = |pairs: &[(&str, &str)], input: &str| {
+            run(
+                Tool::LIGHT,
+                &Vars::from_pairs(pairs),
+                &dir.join("light.exe"),
+                strings(&[input]),
+            )
+        };
+
+        // Unset, or in a dry run, nothing is locked or created.
+        assert_eq!(link(&[], "a.wixobj"), 3);
+        let dry_run = [
+            ("LIGHT_WRAPPER_LOCK", lock_str),
+            ("LIGHT_WRAPPER_DRY_RUN", "1"),
+        ];
+        assert_eq!(link(&dry_run, "a.wixobj"), 0);
+        assert!(!lock_path.exists());
+
+        // Released after a failed link and after the timeout kill.
+        let locked = ("LIGHT_WRAPPER_LOCK", lock_str);
+        assert_eq!(link(&[locked], "a.wixobj"), 3);
+        drop(lock::acquire(&lock_path, Duration::ZERO, || {}).unwrap());
+        let killed = [locked, ("LIGHT_WRAPPER_TIMEOUT_SECS", "1")];
+        assert_eq!(link(&killed, "hang.wixobj"), wait::TIMED_OUT);
+        let held = lock::acquire(&lock_path, Duration::ZERO, || {}).unwrap();
+
+        // Held elsewhere: gives up once the timeout passes, without starting the real tool.
+        let runs = recorder.runs().len();
+        let impatient = [locked, ("LIGHT_WRAPPER_LOCK_TIMEOUT_SECS", "0")];
+        assert_eq!(link(&impatient, "a.wixobj"), 1);
+        assert_eq!(recorder.runs().len(), runs);
+        drop(held);
+        assert_eq!(link(&impatient, "a.wixobj"), 3);
+
+        let invalid = [locked, ("LIGHT_WRAPPER_LOCK_TIMEOUT_SECS", "soon")];
+        assert_eq!(link(&invalid, "a.wixobj"), 1);
+    }
+
+    #[test]
+    fn longpath_prefixes_absolute_paths_only_when_enabled() {
+        let dir = TempDir::new("app-longpath");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        let deep = format!(r"C:\ci\{}target", "nested\\".repeat(30));
+        let msi = format!(r"{deep}\app.msi");
+        let link = |pairs: &[(&str, &str)]| {
+            run(
+                Tool::LIGHT,
+                &Vars::from_pairs(pairs),
+                &dir.join("light.exe"),
+                strings(&[&format!("-out:{msi}"), "main.wixobj"]),
+            )
+        };
+        assert_eq!(link(&[]), 0);
+        assert_eq!(
+            recorder.args(),
+            strings(&[&format!("-out:{msi}"), "-sacl", "main.wixobj"])
+        );
+        assert_eq!(link(&[("LIGHT_WRAPPER_LONGPATH", "1")]), 0);
+        assert_eq!(
+            recorder.args(),
+            strings(&[&format!(r"-out:\\?\{msi}"), "-sacl", "main.wixobj"])
+        );
+    }
+
+    #[test]
+    fn flag_lookalikes_after_the_separator_are_opaque() {
+        let dir = TempDir::new("app-opaque");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        let link = |pairs: &[(&str, &str)], args: &[&str]| {
+            run(
+                Tool::LIGHT,
+                &Vars::from_pairs(pairs),
+                &dir.join("light.exe"),
+                strings(args),
+            )
+        };
+        let always = ("LIGHT_WRAPPER_ALWAYS_SVAL", "1");
+        let strip = ("LIGHT_WRAPPER_STRIP_ARGS", "-sval");
+
+        // Without the separator the generated name is taken for a -sval and stripped.
+        assert_eq!(link(&[always, strip], &["-out", "app.msi", "-sval"]), 0);
+        assert_eq!(
+            recorder.args(),
+            strings(&["-sval", "-sacl", "-out", "app.msi"])
+        );
+
+        assert_eq!(
+            link(
+                &[always, strip],
+                &["-out", "app.msi", "--", "-sval", "main.wixobj"]
+            ),
+            0
+        );
+        assert_eq!(
+            recorder.args(),
+            strings(&["-sval", "-sacl", "-out", "app.msi", "-sval", "main.wixobj"])
+        );
+
+        let from = ("WIX_LIGHT_WRAPPER_OPAQUE_FROM", "1");
+        assert_eq!(link(&[always, from], &["main.wixobj", "-SACL"]), 0);
+        assert_eq!(
+            recorder.args(),
+            strings(&["-sval", "-sacl", "main.wixobj", "-SACL"])
+        );
+        let invalid = ("WIX_LIGHT_WRAPPER_OPAQUE_FROM", "last");
+        assert_eq!(link(&[invalid], &["main.wixobj"]), 1);
+    }
+
+    #[test]
+    fn explaining_failures_keeps_the_exit_code() {
+        let dir = TempDir::new("app-explain");
+        let recorder = Recorder::with_script(&dir, "light-real.exe", ICE_FAILING_LINKER);
+        let vars = Vars::from_pairs(&[
+            ("LIGHT_WRAPPER_EXPLAIN", "1"),
+            ("WIX_LIGHT_WRAPPER_SUPPRESS", "sacl"),
+        ]);
+        assert_eq!(
+            run(
+                Tool::LIGHT,
+                &vars,
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"])
+            ),
+            217
+        );
+        assert_eq!(recorder.runs(), vec![strings(&["-sacl", "a.wixobj"])]);
+    }
+
+    #[test]
+    fn doctor_reports_without_linking_and_fails_on_problems() {
+        let dir = TempDir::new("app-doctor");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        let doctor = |pairs: &[(&str, &str)]| {
+            run(
+                Tool::LIGHT,
+                &Vars::from_pairs(pairs),
+                &dir.join("light.exe"),
+                strings(&["doctor"]),
+            )
+        };
+        assert_eq!(doctor(&[]), 0);
+        assert!(!recorder.was_run());
+
+        let missing = dir.join("missing.exe");
+        let missing = missing.to_str().unwrap();
+        assert_eq!(doctor(&[("LIGHT_WRAPPER_REAL_EXE", missing)]), 1);
+        assert_eq!(doctor(&[("WIX_LIGHT_WRAPPER_ACTIVE", "1")]), 1);
+
+        let not_executable = dir.join("light-copy.exe");
+        std::fs::write(&not_executable, "MZ").unwrap();
+        let not_executable = not_executable.to_str().unwrap();
+        assert_eq!(doctor(&[("LIGHT_WRAPPER_REAL_EXE", not_executable)]), 1);
+        assert_eq!(doctor(&[("LIGHT_WRAPPER_DISABLE", "1")]), 0);
+        assert_eq!(
+            doctor(&[
+                ("LIGHT_WRAPPER_DISABLE", "1"),
+                ("LIGHT_WRAPPER_REAL_EXE", missing)
+            ]),
+            1
+        );
+        assert!(!recorder.was_run());
+    }
+
+    #[test]
+    fn a_symlinked_wrapper_finds_the_real_tool_beside_its_target() {
+        let dir = TempDir::new("app-symlink");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        std::fs::write(dir.join("light.exe"), "wrapper").unwrap();
+        let farm = dir.join("farm");
+        std::fs::create_dir_all(&farm).unwrap();
+        std::os::unix::fs::symlink(dir.join("light.exe"), farm.join("light.exe")).unwrap();
+
+        let link = |pairs: &[(&str, &str)]| {
+            run(
+                Tool::LIGHT,
+                &Vars::from_pairs(pairs),
+                &farm.join("light.exe"),
+                strings(&["main.wixobj"]),
+            )
+        };
+        assert_eq!(link(&[]), 0);
+        assert_eq!(recorder.runs(), [strings(&["-sacl", "main.wixobj"])]);
+
+        assert_eq!(link(&[("LIGHT_WRAPPER_NO_FOLLOW_SYMLINKS", "1")]), 1);
+        assert_eq!(recorder.runs().len(), 1);
+    }
+
+    #[test]
+    fn quiet_mode_injects_nologo_once_and_excludes_verbose() {
+        let dir = TempDir::new("app-quiet");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        let link = |pairs: &[(&str, &str)], args: &[&str]| {
+            run(
+                Tool::LIGHT,
+                &Vars::from_pairs(pairs),
+                &dir.join("light.exe"),
+                strings(args),
+            )
+        };
+
+        assert_eq!(link(&[("LIGHT_WRAPPER_QUIET", "1")], &["a.wixobj"]), 0);
+        assert_eq!(recorder.args(), strings(&["-sacl", "-nologo", "a.wixobj"]));
+        assert_eq!(
+            link(&[("LIGHT_WRAPPER_QUIET", "1")], &["/NOLOGO", "a.wixobj"]),
+            0
+        );
+        assert_eq!(recorder.args(), strings(&["/NOLOGO", "-sacl", "a.wixobj"]));
+        assert_eq!(link(&[("LIGHT_WRAPPER_VERBOSE", "1")], &["a.wixobj"]), 0);
+        assert_eq!(recorder.args(), strings(&["-sacl", "a.wixobj"]));
+
+        let both = [("LIGHT_WRAPPER_VERBOSE", "1"), ("LIGHT_WRAPPER_QUIET", "1")];
+        assert_eq!(link(&both, &["a.wixobj"]), 1);
+        assert_eq!(recorder.runs().len(), 3);
+    }
+
+    #[test]
+    fn pdb_suppression_and_cab_cache_cleanup_are_opt_in() {
+        let dir = TempDir::new("app-cabcache");
+        let recorder = Recorder::with_script(
+            &dir,
+            "light-real.exe",
+            "for arg; do case $arg in broken.wixobj) exit 9;; esac; done; exit 0",
+        );
+        let cache = dir.join("cabs");
+        let cache_str = cache.to_str().unwrap();
+        let fill_cache = || {
+            std::fs::create_dir_all(&cache).unwrap();
+            std::fs::write(cache.join("cab1.cab"), [0u8; 64]).unwrap();
+        };
+        let link = |pairs: &[(&str, &str)], args: &[&str]| {
+            run(
+                Tool::LIGHT,
+                &Vars::from_pairs(pairs),
+                &dir.join("light.exe"),
+                strings(args),
+            )
+        };
+
+        fill_cache();
+        assert_eq!(link(&[], &["-cc", cache_str, "a.wixobj"]), 0);
+        assert_eq!(
+            recorder.args(),
+            strings(&["-cc", cache_str, "-sacl", "a.wixobj"])
+        );
+        assert!(cache.exists());
+
+        let opted_in = [
+            ("LIGHT_WRAPPER_SUPPRESS_PDB", "1"),
+            ("LIGHT_WRAPPER_CLEAN_CABCACHE", "1"),
+        ];
+        assert_eq!(
+            link(&opted_in, &["-cc", cache_str, "-spdb", "broken.wixobj"]),
+            9
+        );
+        assert_eq!(
+            recorder.args(),
+            strings(&["-cc", cache_str, "-spdb", "-sacl", "broken.wixobj"])
+        );
+        assert!(cache.exists(), "kept after a failed link");
+        assert_eq!(link(&opted_in, &["-cc", cache_str, "a.wixobj"]), 0);
+        assert_eq!(
+            recorder.args(),
+            strings(&["-cc", cache_str, "-sacl", "-spdb", "a.wixobj"])
+        );
+        assert!(!cache.exists());
+
+        // A cache injected through the wrapper's own flags is found too; cleanup that can't
+        // happen (no cache given, or it isn't a directory) leaves the exit code alone.
+        fill_cache();
+        let injected = [
+            opted_in[1],
+            ("LIGHT_WRAPPER_PREPEND_ARGS", &format!("-cc:{cache_str}")),
+        ];
+        assert_eq!(link(&injected, &["a.wixobj"]), 0);
+        assert!(!cache.exists());
+        assert_eq!(link(&opted_in, &["a.wixobj"]), 0);
+        std::fs::write(&cache, "not a directory").unwrap();
+        assert_eq!(link(&opted_in, &["-cc", cache_str, "a.wixobj"]), 0);
+    }
+
+    #[test]
+    fn warning_baseline_fails_links_with_more_warnings() {
+        let dir = TempDir::new("app-baseline");
+        Recorder::with_script(
+            &dir,
+            "light-real.exe",
+            "echo 'main.wxs(41) : warning LGHT1076 : ICE61: remove older versions' >&2\n\
+             for arg; do case $arg in\n\
+             more.wixobj) echo 'light.exe : Warnung LGHT1105 : Validierung übersprungen' >&2;;\n\
+             broken.wixobj) exit 4;;\n\
+             esac; done\n\
+             exit 0",
+        );
+        let baseline = dir.join("warnings.txt");
+        let baseline_str = baseline.to_str().unwrap();
+        let link = |pairs: &[(&str, &str)], input: &str| {
+            run(
+                Tool::LIGHT,
+                &Vars::from_pairs(pairs),
+                &dir.join("light.exe"),
+                strings(&["-out", "app.msi", input]),
+            )
+        };
+        let checked = ("LIGHT_WRAPPER_WARNING_BASELINE", baseline_str);
+        let updating = [checked, ("LIGHT_WRAPPER_UPDATE_BASELINE", "1")];
+
+        // No baseline yet: the link fails until one is written.
+        assert_eq!(link(&[checked], "a.wixobj"), 1);
+        assert_eq!(link(&updating, "a.wixobj"), 0);
+        assert_eq!(std::fs::read_to_string(&baseline).unwrap(), "LGHT1076 1\n");
+        assert_eq!(link(&[checked], "a.wixobj"), 0);
+
+        // A new code fails the link; a failed link keeps its own exit code.
+        assert_eq!(link(&[checked], "more.wixobj"), 1);
+        assert_eq!(link(&[checked], "broken.wixobj"), 4);
+        assert_eq!(link(&updating, "more.wixobj"), 0);
+        assert_eq!(
+            std::fs::read_to_string(&baseline).unwrap(),
+            "LGHT1076 1\nLGHT1105 1\n"
+        );
+        assert_eq!(link(&[checked], "a.wixobj"), 0);
+        assert_eq!(link(&[], "more.wixobj"), 0);
+    }
+
+    #[test]
+    fn signs_the_linked_msi_and_fails_when_signing_does() {
+        let dir = TempDir::new("app-sign");
+        Recorder::with_script(
+            &dir,
+            "light-real.exe",
+            "case \" $* \" in *' broken.wixobj '*) exit 2;; esac; exit 0",
+        );
+        let signtool = Recorder::install(&dir, "signtool.exe", 0);
+        Recorder::install(&dir, "bad-signtool.exe", 5);
+        let signtool_exe = dir.join("signtool.exe");
+        let bad_signtool_exe = dir.join("bad-signtool.exe");
+        let link = |pairs: &[(&str, &str)], args: &[&str]| {
+            run(
+                Tool::LIGHT,
+                &Vars::from_pairs(pairs),
+                &dir.join("light.exe"),
+                strings(args),
+            )
+        };
+        let sign_args = ("LIGHT_WRAPPER_SIGNTOOL_ARGS", "/fd sha256 /p hunter2");
+
+        // Unset, nothing is signed.
+        assert_eq!(link(&[], &["-out", "My App.msi", "a.wixobj"]), 0);
+        assert!(!signtool.was_run());
+
+        let signing = [
+            sign_args,
+            ("LIGHT_WRAPPER_SIGNTOOL_EXE", signtool_exe.to_str().unwrap()),
+            ("LIGHT_WRAPPER_VERBOSE", "1"),
+        ];
+        assert_eq!(link(&signing, &["-out", "My App.msi", "a.wixobj"]), 0);
+        assert_eq!(
+            signtool.args(),
+            strings(&["sign", "/fd", "sha256", "/p", "hunter2", "My App.msi"])
+        );
+        assert_eq!(link(&signing, &["-out:\"out dir/app.msi\"", "a.wixobj"]), 0);
+        assert_eq!(signtool.args().last().unwrap(), "out dir/app.msi");
+
+        // A failed link is not signed, and there is nothing to sign without -out.
+        let runs = signtool.runs().len();
+        assert_eq!(link(&signing, &["-out", "app.msi", "broken.wixobj"]), 2);
+        assert_eq!(link(&signing, &["a.wixobj"]), 1);
+        assert_eq!(signtool.runs().len(), runs);
+
+        let failing = [
+            sign_args,
+            (
+                "LIGHT_WRAPPER_SIGNTOOL_EXE",
+                bad_signtool_exe.to_str().unwrap(),
+            ),
+        ];
+        assert_eq!(link(&failing, &["-out", "app.msi", "a.wixobj"]), 5);
+        let missing = [
+            sign_args,
+            ("LIGHT_WRAPPER_SIGNTOOL_EXE", "/nonexistent/signtool"),
+        ];
+        assert_eq!(link(&missing, &["-out", "app.msi", "a.wixobj"]), 1);
+    }
+
+    /// Fails with a sharing violation on the first `n` runs, then succeeds.
+    fn locked_linker(dir: &TempDir, n: usize) -> Recorder {
+        let record = dir.join("light-real.exe.args");
+        Recorder::with_script(
+            dir,
+            "light-real.exe",
+            &format!(
+                "[ \"$(grep -c -- --end-of-run-- '{}')\" -gt {n} ] && exit 0\n\
+                 echo \"light.exe : error LGHT0001 : The process cannot access the file 'app.msi' because it is being used by another process.\" >&2\n\
+                 exit 1",
+                record.display()
+            ),
+        )
+    }
+
+    #[test]
+    fn retries_while_the_output_is_locked() {
+        let dir = TempDir::new("app-retry");
+        let recorder = locked_linker(&dir, 2);
+        let vars = Vars::from_pairs(&[
+            ("LIGHT_WRAPPER_RETRIES", "3"),
+            ("LIGHT_WRAPPER_RETRY_DELAY_MS", "1"),
+            ("LIGHT_WRAPPER_ALWAYS_SVAL", "1"),
+        ]);
+        let code = run(
+            Tool::LIGHT,
+            &vars,
+            &dir.join("light.exe"),
+            strings(&["a.wixobj"]),
+        );
+        assert_eq!(code, 0);
+        assert_eq!(recorder.runs().len(), 3);
+
+        // Out of retries: the last attempt's exit code is propagated.
+        let dir = TempDir::new("app-retry-exhausted");
+        let recorder = locked_linker(&dir, 10);
+        let code = run(
+            Tool::LIGHT,
+            &vars,
+            &dir.join("light.exe"),
+            strings(&["a.wixobj"]),
+        );
+        assert_eq!(code, 1);
+        assert_eq!(recorder.runs().len(), 4);
+    }
+
+    #[test]
+    fn other_failures_are_not_retried_for_locks() {
+        let dir = TempDir::new("app-retry-other");
+        let recorder = Recorder::with_script(
+            &dir,
+            "light-real.exe",
+            "echo 'error LGHT0103 : The system cannot find the file' >&2; exit 103",
+        );
+        let vars = Vars::from_pairs(&[
+            ("LIGHT_WRAPPER_RETRIES", "3"),
+            ("LIGHT_WRAPPER_RETRY_DELAY_MS", "1"),
+        ]);
+        let code = run(
+            Tool::LIGHT,
+            &vars,
+            &dir.join("light.exe"),
+            strings(&["a.wixobj"]),
+        );
+        assert_eq!(code, 103);
+        assert_eq!(recorder.runs().len(), 1);
+    }
+
+    #[test]
+    fn refuses_to_run_nested_inside_another_wrapper() {
+        let dir = TempDir::new("app-nested");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        let vars = Vars::from_pairs(&[("WIX_LIGHT_WRAPPER_ACTIVE", "1")]);
+        assert_eq!(
+            run(
+                Tool::LIGHT,
+                &vars,
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"])
+            ),
+            1
+        );
+        assert!(!recorder.was_run());
+    }
+
+    #[test]
+    fn nesting_depth_is_passed_on_and_capped() {
+        let dir = TempDir::new("app-depth");
+        let recorder = Recorder::with_script(
+            &dir,
+            "light-real.exe",
+            "printf '%s\\n' \"$LIGHT_WRAPPER_DEPTH\" >> \"$0.depth\"",
+        );
+        let link = |depth: Option<&str>| {
+            let vars = Vars::from_pairs(
+                &depth
+                    .map(|d| ("LIGHT_WRAPPER_DEPTH", d))
+                    .into_iter()
+                    .collect::<Vec<_>>(),
+            );
+            run(
+                Tool::LIGHT,
+                &vars,
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"]),
+            )
+        };
+        assert_eq!(link(None), 0);
+        assert_eq!(link(Some("1")), 0);
+        assert_eq!(link(Some("2")), 1);
+        assert_eq!(link(Some("7")), 1);
+        assert_eq!(recorder.runs().len(), 2);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("light-real.exe.depth")).unwrap(),
+            "1\n2\n"
+        );
+    }
+
+    #[test]
+    fn refuses_a_real_tool_that_is_another_wrapper_copy() {
+        let dir = TempDir::new("app-real-is-wrapper");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        let mut stub = OpenOptions::new()
+            .append(true)
+            .open(dir.join("light-real.exe"))
+            .unwrap();
+        writeln!(stub, "# {}", wrapper::version_line(Tool::LIGHT)).unwrap();
+        drop(stub);
+        assert_eq!(
+            run(
+                Tool::LIGHT,
+                &Vars::default(),
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"])
+            ),
+            1
+        );
+        assert!(!recorder.was_run());
+    }
+
+    #[test]
+    fn real_tool_must_match_an_expected_digest() {
+        let dir = TempDir::new("app-integrity");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        let actual = sha256::file_hex(&dir.join("light-real.exe")).unwrap();
+        let other = "0".repeat(64);
+        let link = |expected: &str| {
+            let vars = Vars::from_pairs(&[("LIGHT_WRAPPER_EXPECTED_SHA256", expected)]);
+            run(
+                Tool::LIGHT,
+                &vars,
+                &dir.join("light.exe"),
+                strings(&["a.wixobj"]),
+            )
+        };
+        assert_eq!(link(&other), wrapper::INTEGRITY_MISMATCH);
+        assert!(!recorder.was_run());
+        assert_eq!(link("not-a-digest"), 1);
+        assert_eq!(link(&format!("{other}, {}", actual.to_uppercase())), 0);
+        assert_eq!(recorder.runs().len(), 1);
+
+        std::fs::write(
+            dir.join("light-wrapper.toml"),
+            format!("real_exe_sha256 = \"{other}\"\n"),
+        )
+        .unwrap();
+        let code = run(
+            Tool::LIGHT,
+            &Vars::default(),
+            &dir.join("light.exe"),
+            strings(&["a.wixobj"]),
+        );
+        assert_eq!(code, wrapper::INTEGRITY_MISMATCH);
+        assert_eq!(link(&actual), 0);
+        assert_eq!(recorder.runs().len(), 2);
+    }
+
+    #[test]
+    fn marks_the_child_as_running_under_the_wrapper() {
+        let dir = TempDir::new("app-marker");
+        let record = dir.join("marker");
+        let stub = dir.join("light-real.exe");
+        std::fs::write(
+            &stub,
+            format!(
+                "#!/bin/sh\nprintf '%s' \"$WIX_LIGHT_WRAPPER_ACTIVE\" > '{}'\n",
+                record.display()
+            ),
+        )
+        .unwrap();
+        crate::testutil::make_executable(&stub);
+        assert_eq!(
+            run(
+                Tool::LIGHT,
+                &Vars::default(),
+                &dir.join("light.exe"),
+                Vec::new()
+            ),
+            0
+        );
+        assert_eq!(std::fs::read_to_string(&record).unwrap(), "1");
+    }
+
+    #[test]
+    fn verify_checks_the_msi_without_running_the_real_tool() {
+        let dir = TempDir::new("app-verify");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        let msi = dir.join("app.msi");
+        let verify = |contents: &[u8]| {
+            std::fs::write(&msi, contents).unwrap();
+            run(
+                Tool::LIGHT,
+                &Vars::default(),
+                &dir.join("light.exe"),
+                strings(&["verify", "--msi", msi.to_str().unwrap()]),
+            )
+        };
+        let database =
+            crate::msi::database(&[("ProductCode", "{0}"), ("ProductVersion", "1.0")], false);
+        assert_eq!(verify(&database), 0);
+        assert_eq!(verify(&database[..1024]), 1);
+        assert!(!recorder.was_run());
+    }
+
+    #[test]
+    fn dry_run_does_not_spawn() {
+        let dir = TempDir::new("app-dry-run");
+        let recorder = Recorder::install(&dir, "light-real.exe", 7);
+        for var in ["LIGHT_WRAPPER_DRY_RUN", "WIX_LIGHT_WRAPPER_DRY_RUN"] {
+            let vars = Vars::from_pairs(&[(var, "1")]);
+            assert_eq!(
+                run(
+                    Tool::LIGHT,
+                    &vars,
+                    &dir.join("light.exe"),
+                    strings(&["a.wixobj"])
+                ),
+                0
+            );
+            assert!(!recorder.was_run(), "{var}");
+        }
+    }
+
+    #[test]
+    fn dry_run_switch_is_not_forwarded() {
+        let dir = TempDir::new("app-dry-run-switch");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        let code = run(
+            Tool::LIGHT,
+            &Vars::default(),
+            &dir.join("light.exe"),
+            strings(&["--wrapper-dry-run", "a.wixobj"]),
+        );
+        assert_eq!(code, 0);
+        assert!(!recorder.was_run());
+
+        // Without a real tool to find, the dry run still completes but fails like a real run.
+        let empty = TempDir::new("app-dry-run-unresolved");
+        let code = run(
+            Tool::LIGHT,
+            &Vars::default(),
+            &empty.join("light.exe"),
+            strings(&["--wrapper-dry-run", "a.wixobj"]),
+        );
+        assert_eq!(code, 1);
+    }
+
+    #[test]
+    fn repeated_suppressions_are_forwarded_once() {
+        let dir = TempDir::new("app-normalize");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        let code = run(
+            Tool::LIGHT,
+            &Vars::default(),
+            &dir.join("light.exe"),
+            strings(&[
+                "-SVAL",
+                "-out",
+                "app.msi",
+                "-sacl",
+                "main.wixobj",
+                "-sacl",
+                "-Sval",
+            ]),
+        );
+        assert_eq!(code, 0);
+        assert_eq!(
+            recorder.args(),
+            strings(&["-SVAL", "-out", "app.msi", "-sacl", "main.wixobj"])
+        );
+    }
+
+    #[test]
+    fn extra_flags_are_injected_as_given() {
+        let dir = TempDir::new("app-extra-injected");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        let log_path = dir.join("light.jsonl");
+        let vars = Vars::from_pairs(&[
+            (
+                "WIX_LIGHT_WRAPPER_EXTRA_FLAGS",
+                "-SPDB -spdb -sacl -cultures:en-US",
+            ),
+            ("LIGHT_WRAPPER_STRIP", "-spdb -cultures:*"),
+            ("LIGHT_WRAPPER_DEDUPE", "1"),
+            ("LIGHT_WRAPPER_ALWAYS_SVAL", "1"),
+            ("LIGHT_WRAPPER_LOG_PATH", log_path.to_str().unwrap()),
+        ]);
+        let code = run(
+            Tool::LIGHT,
+            &vars,
+            &dir.join("light.exe"),
+            strings(&["-out", "app.msi", "-spdb", "main.wixobj"]),
+        );
+        assert_eq!(code, 0);
+        // Only the caller's `-spdb` is stripped; the extra flags keep their spelling and
+        // repeats, after the default `-sval` and in place of the default `-sacl`.
+        let injected = ["-sval", "-SPDB", "-spdb", "-sacl", "-cultures:en-US"];
+        let mut expected = strings(&["-out", "app.msi"]);
+        expected.extend(strings(&injected));
+        expected.extend(strings(&["main.wixobj"]));
+        assert_eq!(recorder.args(), expected);
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        assert!(
+            log.contains(r#""injected":["-sval","-SPDB","-spdb","-sacl","-cultures:en-US"]"#),
+            "{log}"
+        );
+    }
+
+    #[test]
+    fn dedupe_drops_later_duplicates_only_when_asked() {
+        let dir = TempDir::new("app-dedupe");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        let incoming = strings(&[
+            "-ext",
+            "WixUIExtension",
+            "a.wixobj",
+            "-ext",
+            "WixUtilExtension",
+            "-ext",
+            "WixUIExtension",
+            "a.wixobj",
+        ]);
+        let vars = Vars::from_pairs(&[("LIGHT_WRAPPER_ALWAYS_SVAL", "1")]);
+        run(Tool::LIGHT, &vars, &dir.join("light.exe"), incoming.clone());
+        let mut expected = incoming.clone();
+        expected.splice(2..2, strings(&["-sval", "-sacl"]));
+        assert_eq!(recorder.args(), expected);
+
+        let vars = Vars::from_pairs(&[
+            ("LIGHT_WRAPPER_ALWAYS_SVAL", "1"),
+            ("LIGHT_WRAPPER_DEDUPE", "1"),
+        ]);
+        run(Tool::LIGHT, &vars, &dir.join("light.exe"), incoming);
+        assert_eq!(
+            recorder.args(),
+            strings(&[
+                "-ext",
+                "WixUIExtension",
+                "-sval",
+                "-sacl",
+                "a.wixobj",
+                "-ext",
+                "WixUtilExtension"
+            ])
+        );
+    }
+
+    #[test]
+    fn non_unicode_arguments_are_forwarded_byte_for_byte() {
+        use std::os::unix::ffi::OsStringExt;
+        let dir = TempDir::new("app-non-unicode");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        let path = OsString::from_vec(b"C:\\Temp\\Relat\xf3rio\xff.wixobj".to_vec());
+        let culture = OsString::from_vec(b"-cultures:pt-\xc3\x28".to_vec());
+        let mut incoming = strings(&["-SVAL"]);
+        incoming.extend([culture.clone(), path.clone(), path.clone()]);
+        let vars = Vars::from_pairs(&[("LIGHT_WRAPPER_ALWAYS_SVAL", "1")]);
+        let code = run(Tool::LIGHT, &vars, &dir.join("light.exe"), incoming.clone());
+        assert_eq!(code, 0);
+        let mut expected = incoming;
+        expected.insert(2, OsString::from("-sacl"));
+        assert_eq!(recorder.args(), expected);
+    }
+
+    #[test]
+    fn wine_runs_an_extensionless_real_linker() {
+        let dir = TempDir::new("app-wine");
+        std::fs::write(dir.join("light-real"), b"").unwrap();
+        let wine = Recorder::install(&dir, "fake-wine", 3);
+        let wine_exe = dir.join("fake-wine");
+        let vars = Vars::from_pairs(&[
+            ("LIGHT_WRAPPER_WINE", "1"),
+            ("LIGHT_WRAPPER_WINE_EXE", wine_exe.to_str().unwrap()),
+            ("LIGHT_WRAPPER_ALWAYS_SVAL", "1"),
+        ]);
+        let code = run(
+            Tool::LIGHT,
+            &vars,
+            &dir.join("light"),
+            strings(&["a.wixobj"]),
+        );
+        assert_eq!(code, 3);
+        let real = dir.join("light-real");
+        assert_eq!(
+            wine.args(),
+            strings(&[real.to_str().unwrap(), "-sval", "-sacl", "a.wixobj"])
+        );
+    }
+
+    #[test]
+    fn disable_forwards_arguments_unmodified() {
+        let dir = TempDir::new("app-disable");
+        let recorder = Recorder::install(&dir, "light-real.exe", 3);
+        let config = dir.join("broken.toml");
+        std::fs::write(&config, "extra_flags = -spdb").unwrap();
+        let incoming = strings(&["-SVAL", "  spaced  arg ", "main.wixobj", ""]);
+        // None of the settings that would stop or change a normal run apply.
+        let vars = Vars::from_pairs(&[
+            ("LIGHT_WRAPPER_DISABLE", "1"),
+            ("WIX_LIGHT_WRAPPER_EXTRA_FLAGS", "-spdb"),
+            ("LIGHT_WRAPPER_STRIP_ARGS", "-SVAL"),
+            ("WIX_LIGHT_WRAPPER_CONFIG", config.to_str().unwrap()),
+            ("LIGHT_WRAPPER_EXPECTED_SHA256", &"0".repeat(64)),
+            ("LIGHT_WRAPPER_TIMEOUT_SECS", "soon"),
+        ]);
+        let code = run(Tool::LIGHT, &vars, &dir.join("light.exe"), incoming.clone());
+        assert_eq!(code, 3);
+        assert_eq!(recorder.args(), incoming);
+    }
+
+    #[test]
+    fn disable_skips_a_broken_config_file() {
+        let dir = TempDir::new("app-disable-config");
+        let recorder = Recorder::install(&dir, "light-real.exe", 0);
+        std::fs::write(
+            dir.join("light-wrapper.toml"),
+            "timeout_secs = \"soon\"\n[[",
+        )
+        .unwrap();
+        let incoming = strings(&["-sval", "main.wixobj"]);
+        let vars = Vars::from_pairs(&[("LIGHT_WRAPPER_DISABLE", "1")]);
+        let code = run(Tool::LIGHT, &vars, &dir.join("light.exe"), incoming.clone());
+        assert_eq!(code, 0);
+        assert_eq!(recorder.args(), incoming);
+
+        // The same file stops a normal run.
+        let code = run(
+            Tool::LIGHT,
+            &Vars::default(),
+            &dir.join("light.exe"),
+            incoming,
+        );
+        assert_eq!(code, 1);
+    }
+
+    #[test]
+    fn wix_disable_forwards_argv_exactly() {
+        let dir = TempDir::new("app-wix-disable");
+        let recorder = Recorder::with_script(&dir, "light-real.exe", ICE_FAILING_LINKER);
+        let rsp = dir.join("light.rsp");
+        std::fs::write(&rsp, "-sval").unwrap();
+        let long = "x".repeat(response_file::MAX_COMMAND_LINE);
+        let incoming = strings(&[
+            "-sacl",
+            &format!("@{}", rsp.display()),
+            &long,
+            "main.wixobj",
+        ]);
+        let vars = Vars::from_pairs(&[
+            ("WIX_LIGHT_WRAPPER_DISABLE", "1"),
+            ("LIGHT_WRAPPER_PREPEND_ARGS", "-nologo"),
+            ("LIGHT_WRAPPER_STRIP_ARGS", "-sacl"),
+        ]);
+        let code = run(Tool::LIGHT, &vars, &dir.join("light.exe"), incoming.clone());
+        // No response-file spill, no stripping or injection, and no -sval retry.
+        assert_eq!(code, 217);
+        assert_eq!(recorder.runs(), vec![incoming]);
+    }
+
+    /// The wrapper as a binary of its own, built from `light_wrapper.rs` beside this file
+    /// (relative to where the tests were built, as `file!()` is) with `$RUSTC` or `rustc`.
+    #[cfg(unix)]
+    fn built_wrapper(dir: &TempDir) -> PathBuf {
+        let source = Path::new(file!()).with_file_name("light_wrapper.rs");
+        assert!(
+            source.is_file(),
+            "{} not found; run the tests from the directory they were built in",
+            source.display()
+        );
+        let exe = dir.join("light.exe");
+        let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+        let output = process::Command::new(rustc)
+            .args(["--edition", "2021", "-o"])
+            .arg(&exe)
+            .arg(&source)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        exe
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn installed_binary_forwards_to_its_sibling() {
+        let dir = TempDir::new("app-binary");
+        let wrapper_exe = built_wrapper(&dir);
+        let linker =
+            "[ -n \"$STUB_EXIT\" ] && exit \"$STUB_EXIT\"\n".to_string() + ICE_FAILING_LINKER;
+        let recorder = Recorder::with_script(&dir, "light-real.exe", &linker);
+        let link = |stub_exit: Option<&str>| {
+            let mut command = process::Command::new(&wrapper_exe);
+            command
+                .args(["-out", "app.msi", "main.wixobj"])
+                .env_clear()
+                .env("PATH", env::var_os("PATH").unwrap_or_default())
+                .env("TMPDIR", dir.join(""))
+                .stderr(process::Stdio::null());
+            if let Some(code) = stub_exit {
+                command.env("STUB_EXIT", code);
+            }
+            command.status().unwrap().code()
+        };
+
+        // Validation fails, so the link is retried with `-sval`.
+        assert_eq!(link(None), Some(0));
+        assert_eq!(
+            recorder.runs(),
+            [
+                strings(&["-out", "app.msi", "-sacl", "main.wixobj"]),
+                strings(&["-out", "app.msi", "-sval", "-sacl", "main.wixobj"]),
+            ]
+        );
+
+        // Any other failure is the wrapper's exit code, without a retry.
+        assert_eq!(link(Some("5")), Some(5));
+        assert_eq!(recorder.runs().len(), 3);
+        assert_eq!(
+            recorder.args(),
+            strings(&["-out", "app.msi", "-sacl", "main.wixobj"])
+        );
+    }
+}
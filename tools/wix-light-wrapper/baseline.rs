@@ -0,0 +1,181 @@
+//! `LIGHT_WRAPPER_WARNING_BASELINE`: the `LGHT` warnings a link is allowed, code by code, so
+//! that with validation skipped their number can at least not creep up unnoticed.
+//!
+//! The baseline is plain text, one `<code> <count>` line per code, sorted, so it diffs well:
+//!
+//! ```text
+//! LGHT1076 2
+//! LGHT1105 1
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Warning counts keyed by code, e.g. `LGHT1076` → 2.
+pub type WarningCounts = BTreeMap<String, usize>;
+
+/// Counts the warning lines of `text` by the first `LGHTnnnn` code on each. A line is a
+/// warning when it says so in English or, for localized WiX, when its code is a `LGHT1nnn`
+/// one, the range WiX numbers its warnings in. Lines that say `error` never count.
+pub fn warning_counts(text: &str) -> WarningCounts {
+    let mut counts = WarningCounts::new();
+    for line in text.lines() {
+        let upper = line.to_ascii_uppercase();
+        let words: Vec<&str> = upper.split(|c: char| !c.is_ascii_alphanumeric()).collect();
+        let Some(code) = words.iter().copied().find(|word| is_light_code(word)) else {
+            continue;
+        };
+        if words.contains(&"ERROR") {
+            continue;
+        }
+        if words.contains(&"WARNING") || code.as_bytes()[4] == b'1' {
+            *counts.entry(code.to_string()).or_default() += 1;
+        }
+    }
+    counts
+}
+
+fn is_light_code(word: &str) -> bool {
+    word.strip_prefix("LGHT")
+        .is_some_and(|rest| rest.len() == 4 && rest.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Reads a baseline file. Blank lines and `#` comments are skipped.
+pub fn read(path: &Path) -> io::Result<WarningCounts> {
+    parse(&fs::read_to_string(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn parse(text: &str) -> Result<WarningCounts, String> {
+    let mut counts = WarningCounts::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let entry = line
+            .split_once(char::is_whitespace)
+            .and_then(|(code, count)| Some((code, count.trim().parse().ok()?)))
+            .filter(|(code, _)| is_light_code(code));
+        match entry {
+            Some((code, count)) => {
+                counts.insert(code.to_string(), count);
+            }
+            None => {
+                return Err(format!(
+                    "line {}: expected '<LGHT code> <count>', found '{line}'",
+                    number + 1
+                ))
+            }
+        }
+    }
+    Ok(counts)
+}
+
+/// Writes `counts` as a baseline file, replacing any earlier one.
+pub fn write(path: &Path, counts: &WarningCounts) -> io::Result<()> {
+    fs::write(path, render(counts))
+}
+
+fn render(counts: &WarningCounts) -> String {
+    counts
+        .iter()
+        .map(|(code, count)| format!("{code} {count}\n"))
+        .collect()
+}
+
+/// The codes that went past `baseline`: `LGHT1076: 2 -> 3`, or `LGHT1105: new (1)` for a
+/// code the baseline doesn't list.
+pub fn regressions(baseline: &WarningCounts, current: &WarningCounts) -> Vec<String> {
+    current
+        .iter()
+        .filter_map(|(code, &count)| match baseline.get(code) {
+            None => Some(format!("{code}: new ({count})")),
+            Some(&allowed) if count > allowed => Some(format!("{code}: {allowed} -> {count}")),
+            Some(_) => None,
+        })
+        .collect()
+}
+
+/// Whether `current` is under `baseline` anywhere, i.e. the baseline could be tightened.
+pub fn improved(baseline: &WarningCounts, current: &WarningCounts) -> bool {
+    baseline
+        .iter()
+        .any(|(code, &allowed)| current.get(code).copied().unwrap_or(0) < allowed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn counts(entries: &[(&str, usize)]) -> WarningCounts {
+        entries
+            .iter()
+            .map(|&(code, count)| (code.to_string(), count))
+            .collect()
+    }
+
+    #[test]
+    fn counts_english_and_localized_warnings() {
+        let text = "\
+Windows Installer XML Toolset Linker version 3.11.2.4516
+C:\\build\\main.wxs(41) : warning LGHT1076 : ICE61: This product should remove only older versions of itself.
+C:\\build\\main.wxs(52) : warning LGHT1076 : ICE61: again.
+light.exe : Warnung LGHT1105 : Die Validierung wurde übersprungen.
+light.exe : avertissement LGHT0204 : ICE57: hors plage.
+light.exe : error LGHT0217 : Error executing ICE action 'ICE01'.
+light.exe : Fehler LGHT0216 : code 0x643
+LGHT1055 mentioned in a warning about LGHT1076
+";
+        assert_eq!(
+            warning_counts(text),
+            counts(&[("LGHT1055", 1), ("LGHT1076", 2), ("LGHT1105", 1)])
+        );
+        assert!(warning_counts("light.exe : warning LGHT12345 : too long\n").is_empty());
+    }
+
+    #[test]
+    fn baseline_round_trips_sorted() {
+        let dir = TempDir::new("baseline");
+        let path = dir.join("warnings.txt");
+        write(&path, &counts(&[("LGHT1105", 1), ("LGHT1076", 2)])).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "LGHT1076 2\nLGHT1105 1\n"
+        );
+        assert_eq!(
+            read(&path).unwrap(),
+            counts(&[("LGHT1076", 2), ("LGHT1105", 1)])
+        );
+
+        assert_eq!(
+            parse("# allowed warnings\n\n  LGHT1076   3 \n"),
+            Ok(counts(&[("LGHT1076", 3)]))
+        );
+        assert_eq!(
+            parse("LGHT1076 2\nICE61 1\n"),
+            Err("line 2: expected '<LGHT code> <count>', found 'ICE61 1'".to_string())
+        );
+        assert!(parse("LGHT1076 many\n").is_err());
+        assert!(read(&dir.join("missing.txt")).is_err());
+    }
+
+    #[test]
+    fn only_new_codes_and_higher_counts_regress() {
+        let baseline = counts(&[("LGHT1076", 2), ("LGHT1105", 1)]);
+        assert!(regressions(&baseline, &baseline).is_empty());
+        assert!(!improved(&baseline, &baseline));
+
+        let current = counts(&[("LGHT1055", 1), ("LGHT1076", 3), ("LGHT1105", 1)]);
+        assert_eq!(
+            regressions(&baseline, &current),
+            ["LGHT1055: new (1)", "LGHT1076: 2 -> 3"]
+        );
+
+        let current = counts(&[("LGHT1076", 1)]);
+        assert!(regressions(&baseline, &current).is_empty());
+        assert!(improved(&baseline, &current));
+    }
+}
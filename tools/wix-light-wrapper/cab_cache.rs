@@ -0,0 +1,56 @@
+//! `LIGHT_WRAPPER_CLEAN_CABCACHE=1`: removes the cabinet cache light was pointed at with `-cc`
+//! once the MSI is linked, so packaging steps don't carry it around or clean it up by hand.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Removes the directory `cache` and everything in it, returning the bytes its files took up.
+/// A cache that doesn't exist (light writes none when nothing is cabbed) reclaims nothing.
+pub fn remove(cache: &Path) -> io::Result<u64> {
+    let size = match size(cache) {
+        Ok(size) => size,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    fs::remove_dir_all(cache)?;
+    Ok(size)
+}
+
+/// The total size of the files under `dir`. Symbolic links are counted, not followed.
+fn size(dir: &Path) -> io::Result<u64> {
+    let mut total = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        total += if metadata.is_dir() {
+            size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn removes_the_cache_and_reports_its_size() {
+        let dir = TempDir::new("cab-cache");
+        let cache = dir.join("cabs");
+        fs::create_dir_all(cache.join("x64")).unwrap();
+        fs::write(cache.join("cab1.cab"), [0u8; 1000]).unwrap();
+        fs::write(cache.join("x64").join("cab2.cab"), [0u8; 24]).unwrap();
+        assert_eq!(remove(&cache).unwrap(), 1024);
+        assert!(!cache.exists());
+        assert_eq!(remove(&cache).unwrap(), 0);
+
+        let file = dir.join("app.msi");
+        fs::write(&file, "msi").unwrap();
+        assert!(remove(&file).is_err());
+        assert!(file.exists());
+    }
+}
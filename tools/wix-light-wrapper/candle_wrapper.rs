@@ -0,0 +1,9 @@
+include!("modules.rs");
+
+fn main() {
+    // Installed as `candle.exe` beside a renamed `candle-real.exe`, like the light wrapper, for
+    // environments that need extra compiler flags (e.g. `-arch x64` or `-d` defines) injected.
+    // It always wraps candle, whatever name it was copied under; flags come from
+    // `WIX_CANDLE_WRAPPER_SUPPRESS`, `CANDLE_WRAPPER_PREPEND_ARGS` or `candle-wrapper.toml`.
+    app::main(Some(wrapper::Tool::named("candle")));
+}
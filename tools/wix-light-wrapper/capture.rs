@@ -0,0 +1,176 @@
+//! Running a child with piped output that is echoed live to the wrapper's own streams while a
+//! copy is kept for inspection.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use crate::wait;
+
+/// Everything the child wrote, per stream.
+#[derive(Debug, Default)]
+pub struct Captured {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl Captured {
+    /// Both streams as (lossy) text, stdout first.
+    pub fn text(&self) -> String {
+        let mut text = String::from_utf8_lossy(&self.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&self.stderr));
+        text
+    }
+}
+
+/// Spawns `command` with stdout/stderr piped, echoing each chunk to the matching stream of this
+/// process as it arrives. Both pipes are drained on their own threads so a child that fills one
+/// of them can never block on the other. When `log` is given, both streams are also appended
+/// to it a whole line at a time, so lines from stdout and stderr interleave without being torn
+/// apart. `limit` is passed on to `wait::wait`.
+pub fn run_teed(
+    command: &mut Command,
+    log: Option<&File>,
+    limit: Option<Duration>,
+) -> io::Result<(ExitStatus, Captured)> {
+    run_teed_to(command, io::stdout(), io::stderr(), log, limit)
+}
+
+fn run_teed_to(
+    command: &mut Command,
+    out: impl Write + Send + 'static,
+    err: impl Write + Send + 'static,
+    log: Option<&File>,
+    limit: Option<Duration>,
+) -> io::Result<(ExitStatus, Captured)> {
+    let out_log = log.map(File::try_clone).transpose()?;
+    let err_log = log.map(File::try_clone).transpose()?;
+    let mut child = command
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+    let stdout = thread::spawn(move || pump(stdout, out, out_log));
+    let stderr = thread::spawn(move || pump(stderr, err, err_log));
+    let status = wait::wait(&mut child, limit)?;
+    let captured = Captured {
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    };
+    Ok((status, captured))
+}
+
+/// Copies `src` to `dst` (and, by whole lines, `log`) until EOF and returns everything read.
+/// Failing writes are ignored so the pipe is still drained.
+fn pump(mut src: impl Read, mut dst: impl Write, mut log: Option<File>) -> Vec<u8> {
+    let mut copy = Vec::new();
+    let mut logged = 0;
+    let mut buf = [0u8; 8192];
+    loop {
+        match src.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                let _ = dst.write_all(&buf[..n]).and_then(|_| dst.flush());
+                copy.extend_from_slice(&buf[..n]);
+                if let Some(log) = &mut log {
+                    if let Some(end) = buf[..n].iter().rposition(|&b| b == b'\n') {
+                        let end = copy.len() - n + end + 1;
+                        let _ = log.write_all(&copy[logged..end]);
+                        logged = end;
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        }
+    }
+    if let Some(log) = &mut log {
+        let _ = log.write_all(&copy[logged..]);
+    }
+    copy
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_both_streams_and_status() {
+        let (status, captured) = run_teed_to(
+            Command::new("/bin/sh")
+                .arg("-c")
+                .arg("echo out; echo err >&2; exit 4"),
+            io::sink(),
+            io::sink(),
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(status.code(), Some(4));
+        assert_eq!(captured.stdout, b"out\n");
+        assert_eq!(captured.stderr, b"err\n");
+        assert_eq!(captured.text(), "out\nerr\n");
+    }
+
+    #[test]
+    fn drains_large_output_without_deadlock() {
+        let (status, captured) = run_teed_to(
+            Command::new("/bin/sh")
+                .arg("-c")
+                .arg("head -c 3000000 /dev/zero >&2; head -c 1000000 /dev/zero"),
+            io::sink(),
+            io::sink(),
+            None,
+            None,
+        )
+        .unwrap();
+        assert!(status.success());
+        assert_eq!(captured.stderr.len(), 3_000_000);
+        assert_eq!(captured.stdout.len(), 1_000_000);
+    }
+
+    #[test]
+    fn tees_both_streams_into_the_log() {
+        let dir = crate::testutil::TempDir::new("capture-log");
+        let path = dir.join("out.log");
+        let log = File::create(&path).unwrap();
+        let (status, _) = run_teed_to(
+            Command::new("/bin/sh")
+                .arg("-c")
+                .arg("echo out; sleep 0.1; echo err >&2"),
+            io::sink(),
+            io::sink(),
+            Some(&log),
+            None,
+        )
+        .unwrap();
+        assert!(status.success());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "out\nerr\n");
+    }
+
+    #[test]
+    fn log_keeps_lines_whole() {
+        let dir = crate::testutil::TempDir::new("capture-log-lines");
+        let path = dir.join("out.log");
+        let log = File::create(&path).unwrap();
+        let (status, _) = run_teed_to(
+            Command::new("/bin/sh").arg("-c").arg(
+                "printf 'light: '; sleep 0.2; echo warning >&2; sleep 0.2; echo done; printf tail",
+            ),
+            io::sink(),
+            io::sink(),
+            Some(&log),
+            None,
+        )
+        .unwrap();
+        assert!(status.success());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "warning\nlight: done\ntail"
+        );
+    }
+}
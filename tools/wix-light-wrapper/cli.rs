@@ -0,0 +1,306 @@
+//! The wrapper's own command-line options. Every argument starting with `--wrapper-` belongs to
+//! the wrapper and is never forwarded to the real tool.
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+const PREFIX: &str = "--wrapper-";
+
+/// Something the wrapper does itself instead of running the real tool.
+#[derive(Debug, PartialEq)]
+pub enum WrapperCommand {
+    /// `--wrapper-install <wix-bin-dir> [--force]`, see `install::install`.
+    Install { dir: PathBuf, force: bool },
+    /// `--wrapper-uninstall <wix-bin-dir>`, see `install::uninstall`.
+    Uninstall { dir: PathBuf },
+    /// `verify --msi <path>`, see `msi::verify`.
+    Verify { msi: PathBuf },
+    /// `doctor` or `--wrapper-doctor`: report how the wrapper is set up instead of running the
+    /// real tool (see `app::run`).
+    Doctor,
+    /// `--wrapper-version`: print `wrapper::version_line` and exit. Takes precedence over
+    /// everything else on the command line.
+    Version,
+}
+
+/// Removes the wrapper's options from `args` and returns the command they ask for, if any.
+/// Unknown `--wrapper-` options are an error rather than being passed through.
+///
+/// The subcommand spellings `install --wix-bin <dir> [--force]` and `uninstall --wix-bin <dir>`
+/// are accepted too, as are `verify --msi <path>` and `doctor`, but only as the whole command
+/// line, so they can't shadow a light argument.
+pub fn take_wrapper_command(args: &mut Vec<OsString>) -> Result<Option<WrapperCommand>, String> {
+    if let Some(command) = subcommand(args)? {
+        args.clear();
+        return Ok(Some(command));
+    }
+    if !args.iter().any(|a| option(a).is_some()) {
+        return Ok(None);
+    }
+    let mut install_dir = None;
+    let mut uninstall_dir = None;
+    let mut force = false;
+    let mut rest = Vec::with_capacity(args.len());
+    let mut iter = std::mem::take(args).into_iter();
+    while let Some(arg) = iter.next() {
+        let Some(option) = option(&arg) else {
+            rest.push(arg);
+            continue;
+        };
+        let (name, inline_value) = match option.split_once('=') {
+            Some((name, value)) => (name, Some(OsString::from(value))),
+            None => (option, None),
+        };
+        match name {
+            "install" | "uninstall" => {
+                let dir = inline_value.or_else(|| iter.next()).ok_or_else(|| {
+                    format!("--wrapper-{name} needs the WiX bin directory to work on")
+                })?;
+                if name == "install" {
+                    install_dir = Some(PathBuf::from(dir));
+                } else {
+                    uninstall_dir = Some(PathBuf::from(dir));
+                }
+            }
+            "force" => force = true,
+            "version" => return Ok(Some(WrapperCommand::Version)),
+            "doctor" => return Ok(Some(WrapperCommand::Doctor)),
+            _ => {
+                return Err(format!(
+                    "unknown wrapper option '{}'",
+                    arg.to_string_lossy()
+                ))
+            }
+        }
+    }
+
+    if install_dir.is_none() && uninstall_dir.is_none() {
+        *args = rest;
+        return Ok(None);
+    }
+    // In install mode nothing is forwarded, so a plain `--force` is accepted as well.
+    for arg in rest {
+        if arg != "--force" || install_dir.is_none() {
+            return Err(format!(
+                "unexpected argument '{}' for the wrapper command",
+                arg.to_string_lossy()
+            ));
+        }
+        force = true;
+    }
+    match (install_dir, uninstall_dir) {
+        (Some(_), Some(_)) => {
+            Err("--wrapper-install and --wrapper-uninstall can't be combined".to_string())
+        }
+        (Some(dir), None) => Ok(Some(WrapperCommand::Install { dir, force })),
+        (None, Some(dir)) => Ok(Some(WrapperCommand::Uninstall { dir })),
+        (None, None) => unreachable!("checked above"),
+    }
+}
+
+/// Removes every `--wrapper-dry-run` from `args` and returns whether there was one. Unlike the
+/// options above it doesn't replace the run: the wrapper still works out the full command, it
+/// just prints it instead of executing it.
+pub fn take_dry_run(args: &mut Vec<OsString>) -> bool {
+    let before = args.len();
+    args.retain(|a| a != "--wrapper-dry-run");
+    args.len() != before
+}
+
+/// The option name after `--wrapper-`, if `arg` is one of the wrapper's options.
+fn option(arg: &OsString) -> Option<&str> {
+    arg.to_str()?.strip_prefix(PREFIX)
+}
+
+/// `install --wix-bin <dir> [--force]`, `uninstall --wix-bin <dir>`, `verify --msi <path>` or
+/// a lone `doctor`.
+fn subcommand(args: &[OsString]) -> Result<Option<WrapperCommand>, String> {
+    let Some((name, rest)) = args.split_first() else {
+        return Ok(None);
+    };
+    let name = match name.to_str() {
+        Some(name @ ("install" | "uninstall")) if rest.iter().any(|a| a == "--wix-bin") => name,
+        Some("verify") if rest.iter().any(|a| a == "--msi") => return verify(rest).map(Some),
+        Some("doctor") if rest.is_empty() => return Ok(Some(WrapperCommand::Doctor)),
+        _ => return Ok(None),
+    };
+    let mut dir = None;
+    let mut force = false;
+    let mut iter = rest.iter();
+    while let Some(arg) = iter.next() {
+        match arg.to_str() {
+            Some("--wix-bin") => {
+                dir = Some(PathBuf::from(
+                    iter.next().ok_or("--wix-bin needs the WiX bin directory")?,
+                ))
+            }
+            Some("--force") if name == "install" => force = true,
+            _ => {
+                return Err(format!(
+                    "unexpected argument '{}' for '{name}'",
+                    arg.to_string_lossy()
+                ))
+            }
+        }
+    }
+    let dir = dir.expect("--wix-bin is present");
+    Ok(Some(if name == "install" {
+        WrapperCommand::Install { dir, force }
+    } else {
+        WrapperCommand::Uninstall { dir }
+    }))
+}
+
+/// The arguments after `verify`: exactly `--msi <path>`.
+fn verify(rest: &[OsString]) -> Result<WrapperCommand, String> {
+    match rest {
+        [flag, msi] if flag == "--msi" => Ok(WrapperCommand::Verify {
+            msi: PathBuf::from(msi),
+        }),
+        [flag] if flag == "--msi" => Err("--msi needs the MSI file to verify".to_string()),
+        _ => Err("'verify' takes just --msi <path>".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<OsString> {
+        items.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn plain_arguments_are_left_alone() {
+        let mut args = strings(&["-out", "app.msi", "--force"]);
+        assert_eq!(take_wrapper_command(&mut args), Ok(None));
+        assert_eq!(args, strings(&["-out", "app.msi", "--force"]));
+    }
+
+    #[test]
+    fn parses_install_with_separate_or_inline_dir() {
+        let mut args = strings(&["--wrapper-install", "C:\\WiX\\bin", "--force"]);
+        assert_eq!(
+            take_wrapper_command(&mut args),
+            Ok(Some(WrapperCommand::Install {
+                dir: PathBuf::from("C:\\WiX\\bin"),
+                force: true
+            }))
+        );
+        let mut args = strings(&["--wrapper-install=wix/bin"]);
+        assert_eq!(
+            take_wrapper_command(&mut args),
+            Ok(Some(WrapperCommand::Install {
+                dir: PathBuf::from("wix/bin"),
+                force: false
+            }))
+        );
+    }
+
+    #[test]
+    fn wrapper_options_are_never_forwarded() {
+        let mut args = strings(&["--wrapper-force", "main.wixobj"]);
+        assert_eq!(take_wrapper_command(&mut args), Ok(None));
+        assert_eq!(args, strings(&["main.wixobj"]));
+
+        let mut args = strings(&["--wrapper-bogus", "main.wixobj"]);
+        let err = take_wrapper_command(&mut args).unwrap_err();
+        assert!(err.contains("--wrapper-bogus"), "{err}");
+        assert!(take_wrapper_command(&mut strings(&["--wrapper-install"])).is_err());
+        assert!(take_wrapper_command(&mut strings(&["--wrapper-install", "d", "x"])).is_err());
+    }
+
+    #[test]
+    fn version_wins_over_everything_else() {
+        let mut args = strings(&["main.wixobj", "--wrapper-version", "--wrapper-bogus"]);
+        assert_eq!(
+            take_wrapper_command(&mut args),
+            Ok(Some(WrapperCommand::Version))
+        );
+    }
+
+    #[test]
+    fn dry_run_switch_is_stripped() {
+        let mut args = strings(&["--wrapper-dry-run", "main.wixobj", "--wrapper-dry-run"]);
+        assert!(take_dry_run(&mut args));
+        assert_eq!(args, strings(&["main.wixobj"]));
+        assert!(!take_dry_run(&mut args));
+        assert_eq!(take_wrapper_command(&mut args), Ok(None));
+    }
+
+    #[test]
+    fn parses_install_and_uninstall_subcommands() {
+        let bin = "C:\\Program Files\\WiX Toolset\\bin";
+        let mut args = strings(&["install", "--wix-bin", bin, "--force"]);
+        assert_eq!(
+            take_wrapper_command(&mut args),
+            Ok(Some(WrapperCommand::Install {
+                dir: PathBuf::from(bin),
+                force: true
+            }))
+        );
+        assert!(args.is_empty());
+        assert_eq!(
+            take_wrapper_command(&mut strings(&["uninstall", "--wix-bin", bin])),
+            Ok(Some(WrapperCommand::Uninstall {
+                dir: PathBuf::from(bin)
+            }))
+        );
+        assert_eq!(
+            take_wrapper_command(&mut strings(&["--wrapper-uninstall", bin])),
+            Ok(Some(WrapperCommand::Uninstall {
+                dir: PathBuf::from(bin)
+            }))
+        );
+        assert!(
+            take_wrapper_command(&mut strings(&["uninstall", "--wix-bin", bin, "--force"]))
+                .is_err()
+        );
+
+        // Without --wix-bin a leading `install` is just an argument for light.
+        let mut args = strings(&["install", "main.wixobj"]);
+        assert_eq!(take_wrapper_command(&mut args), Ok(None));
+        assert_eq!(args, strings(&["install", "main.wixobj"]));
+    }
+
+    #[test]
+    fn parses_the_verify_subcommand() {
+        let mut args = strings(&["verify", "--msi", "target\\release\\bundle\\app.msi"]);
+        assert_eq!(
+            take_wrapper_command(&mut args),
+            Ok(Some(WrapperCommand::Verify {
+                msi: PathBuf::from("target\\release\\bundle\\app.msi")
+            }))
+        );
+        assert!(args.is_empty());
+
+        let err = take_wrapper_command(&mut strings(&["verify", "--msi"])).unwrap_err();
+        assert!(err.contains("needs the MSI"), "{err}");
+        let err =
+            take_wrapper_command(&mut strings(&["verify", "--msi", "a.msi", "-v"])).unwrap_err();
+        assert!(err.contains("just --msi"), "{err}");
+
+        // Without --msi a leading `verify` is just an argument for light.
+        let mut args = strings(&["verify", "main.wixobj"]);
+        assert_eq!(take_wrapper_command(&mut args), Ok(None));
+        assert_eq!(args, strings(&["verify", "main.wixobj"]));
+    }
+
+    #[test]
+    fn parses_doctor_only_on_its_own() {
+        let mut args = strings(&["doctor"]);
+        assert_eq!(
+            take_wrapper_command(&mut args),
+            Ok(Some(WrapperCommand::Doctor))
+        );
+        assert!(args.is_empty());
+        assert_eq!(
+            take_wrapper_command(&mut strings(&["main.wixobj", "--wrapper-doctor"])),
+            Ok(Some(WrapperCommand::Doctor))
+        );
+
+        let mut args = strings(&["doctor", "main.wixobj"]);
+        assert_eq!(take_wrapper_command(&mut args), Ok(None));
+        assert_eq!(args, strings(&["doctor", "main.wixobj"]));
+    }
+}
@@ -0,0 +1,261 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::sha256;
+use crate::toml::{self, Value};
+
+/// Optional settings read from `light-wrapper.toml` (or `wix-light-wrapper.toml`) next to the
+/// wrapper executable, or from `WIX_LIGHT_WRAPPER_CONFIG`. Every field defaults to "not
+/// configured", so a missing or empty file keeps the built-in behavior, and the matching
+/// environment variable overrides any field that is set.
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    /// Flags inserted after the built-in defaults and before the caller's arguments.
+    pub prepend_args: Vec<String>,
+    /// Flags added after the caller's arguments.
+    pub append_args: Vec<String>,
+    /// Path of the real linker; relative paths are taken from the config file's directory.
+    pub real_exe: Option<PathBuf>,
+    /// Acceptable SHA-256 digests of the real linker, lowercase; empty means it isn't checked.
+    pub real_exe_sha256: Vec<String>,
+    /// ICE checks to suppress individually (`-sice:ICE61`) instead of skipping all validation.
+    pub suppress_ices: Vec<String>,
+    /// Suppression flags in place of the defaults, as `WIX_LIGHT_WRAPPER_SUPPRESS` (`["sval"]`).
+    pub suppress: Option<Vec<String>>,
+    /// Passthrough flags ahead of the caller's arguments, as `WIX_LIGHT_WRAPPER_EXTRA_FLAGS`.
+    pub extra_flags: Vec<String>,
+    /// Seconds before a hung real tool is killed, as `LIGHT_WRAPPER_TIMEOUT_SECS`.
+    pub timeout_secs: Option<u64>,
+    /// Whether the first link runs with nothing injected, as `WIX_LIGHT_WRAPPER_ADAPTIVE`.
+    pub adaptive: Option<bool>,
+    /// Patterns of caller arguments to drop (`["-cultures:*", "-loc"]`), as `LIGHT_WRAPPER_STRIP`.
+    pub strip: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct ConfigError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid config '{}': {}",
+            self.path.display(),
+            self.message
+        )
+    }
+}
+
+impl Config {
+    /// Reads the config at `path`; a file that does not exist yields the default config.
+    pub fn load(path: &Path) -> Result<Config, ConfigError> {
+        let error = |message: String| ConfigError {
+            path: path.to_path_buf(),
+            message,
+        };
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(error(e.to_string())),
+        };
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        Config::parse(&text, base_dir).map_err(error)
+    }
+
+    pub fn parse(text: &str, base_dir: &Path) -> Result<Config, String> {
+        let mut config = Config::default();
+        for (key, value, line) in toml::parse(text).map_err(|e| e.to_string())? {
+            let at = |message: String| format!("line {line}: {message}");
+            match key.as_str() {
+                "prepend_args" => config.prepend_args = string_array(&key, value).map_err(at)?,
+                "append_args" => config.append_args = string_array(&key, value).map_err(at)?,
+                "suppress_ices" => config.suppress_ices = string_array(&key, value).map_err(at)?,
+                "real_exe" => {
+                    config.real_exe = Some(base_dir.join(string(&key, value).map_err(at)?))
+                }
+                "real_exe_sha256" => {
+                    let digests = match value {
+                        Value::Array(_) => string_array(&key, value),
+                        other => string(&key, other).map(|list| {
+                            list.split(',')
+                                .map(|digest| digest.trim().to_string())
+                                .collect()
+                        }),
+                    };
+                    config.real_exe_sha256 = digests
+                        .map_err(at)?
+                        .into_iter()
+                        .map(|digest| digest.to_ascii_lowercase())
+                        .collect();
+                    if let Some(bad) = config
+                        .real_exe_sha256
+                        .iter()
+                        .find(|digest| !sha256::is_hex_digest(digest))
+                    {
+                        return Err(at(format!("'{bad}' is not a SHA-256 digest")));
+                    }
+                }
+                "suppress" => config.suppress = Some(string_array(&key, value).map_err(at)?),
+                "extra_flags" => config.extra_flags = string_array(&key, value).map_err(at)?,
+                "strip" => config.strip = string_array(&key, value).map_err(at)?,
+                "timeout_secs" => match value {
+                    Value::Integer(secs) if secs > 0 => config.timeout_secs = Some(secs as u64),
+                    Value::Integer(secs) => {
+                        return Err(at(format!("'{key}' must be at least 1, found {secs}")))
+                    }
+                    other => {
+                        return Err(at(format!(
+                            "'{key}' must be a positive integer, found {}",
+                            other.type_name()
+                        )))
+                    }
+                },
+                "adaptive" => match value {
+                    Value::Boolean(b) => config.adaptive = Some(b),
+                    other => {
+                        return Err(at(format!(
+                            "'{key}' must be a boolean, found {}",
+                            other.type_name()
+                        )))
+                    }
+                },
+                _ => return Err(at(format!("unknown key '{key}'"))),
+            }
+        }
+        Ok(config)
+    }
+}
+
+fn string(key: &str, value: Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s),
+        other => Err(format!(
+            "'{key}' must be a string, found {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn string_array(key: &str, value: Value) -> Result<Vec<String>, String> {
+    match value {
+        Value::Array(items) => items.into_iter().map(|item| string(key, item)).collect(),
+        other => Err(format!(
+            "'{key}' must be an array of strings, found {}",
+            other.type_name()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wrapper;
+    use std::ffi::OsString;
+
+    #[test]
+    fn missing_file_is_default_config() {
+        let path = std::env::temp_dir()
+            .join(format!("light-wrapper-no-config-{}", std::process::id()))
+            .join("light-wrapper.toml");
+        assert_eq!(Config::load(&path).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn empty_file_is_default_config() {
+        assert_eq!(
+            Config::parse("", Path::new(".")).unwrap(),
+            Config::default()
+        );
+        assert_eq!(
+            Config::parse("# only comments\n\n", Path::new(".")).unwrap(),
+            Config::default()
+        );
+    }
+
+    #[test]
+    fn reads_all_keys() {
+        let base = Path::new("wix");
+        let config = Config::parse(
+            "prepend_args = [\"-nologo\", \"-cc\", 'C:\\cab']\n\
+             append_args = [\"-cultures:pt-BR\"]\n\
+             real_exe = \"bin/light.exe\"\n\
+             suppress_ices = [\"ICE61\", \"ICE69\"]\n",
+            base,
+        )
+        .unwrap();
+        assert_eq!(config.prepend_args, ["-nologo", "-cc", "C:\\cab"]);
+        assert_eq!(config.append_args, ["-cultures:pt-BR"]);
+        assert_eq!(config.real_exe, Some(base.join("bin/light.exe")));
+        assert_eq!(config.suppress_ices, ["ICE61", "ICE69"]);
+
+        let config = Config::parse(
+            "suppress = [\"sval\", \"sacl\"]\n\
+             extra_flags = [\"-cultures:en-US\", \"-spdb\"]\n\
+             timeout_secs = 600\n\
+             adaptive = true\n\
+             strip = [\"-cultures:*\", \"-loc\"]\n",
+            base,
+        )
+        .unwrap();
+        assert_eq!(config.strip, ["-cultures:*", "-loc"]);
+        assert_eq!(config.suppress.unwrap(), ["sval", "sacl"]);
+        assert_eq!(config.extra_flags, ["-cultures:en-US", "-spdb"]);
+        assert_eq!(config.timeout_secs, Some(600));
+        assert_eq!(config.adaptive, Some(true));
+
+        let digest = "E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855";
+        let config = Config::parse(&format!("real_exe_sha256 = \"{digest}\"\n"), base).unwrap();
+        assert_eq!(config.real_exe_sha256, [digest.to_ascii_lowercase()]);
+        let config = Config::parse(
+            &format!("real_exe_sha256 = [\"{digest}\", \"{digest}\"]\n"),
+            base,
+        )
+        .unwrap();
+        assert_eq!(config.real_exe_sha256.len(), 2);
+        let err = Config::parse("real_exe_sha256 = \"abc\"\n", base).unwrap_err();
+        assert!(err.contains("'abc' is not a SHA-256 digest"), "{err}");
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_wrong_types() {
+        let err = Config::parse("prepend = []\n", Path::new(".")).unwrap_err();
+        assert!(err.contains("unknown key 'prepend'"), "{err}");
+        let err = Config::parse("append_args = \"-nologo\"\n", Path::new(".")).unwrap_err();
+        assert!(err.contains("array of strings"), "{err}");
+        assert!(Config::parse("real_exe = [\n", Path::new(".")).is_err());
+        let err = Config::parse("timeout_secs = 0\n", Path::new(".")).unwrap_err();
+        assert!(
+            err.contains("'timeout_secs' must be at least 1, found 0"),
+            "{err}"
+        );
+        let err = Config::parse("timeout_secs = \"60\"\n", Path::new(".")).unwrap_err();
+        assert!(err.contains("positive integer, found string"), "{err}");
+        let err = Config::parse("adaptive = \"yes\"\n", Path::new(".")).unwrap_err();
+        assert!(
+            err.contains("'adaptive' must be a boolean, found string"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn config_flags_are_not_duplicated() {
+        let config = Config::parse(
+            "prepend_args = [\"-SVAL\", \"-nologo\"]\nappend_args = [\"-nologo\", \"-spdb\"]\n",
+            Path::new("."),
+        )
+        .unwrap();
+        let incoming = vec![OsString::from("-spdb"), OsString::from("main.wixobj")];
+        let mut prepend: Vec<&str> = vec!["-sval", "-sacl"];
+        prepend.extend(config.prepend_args.iter().map(String::as_str));
+        let append: Vec<&str> = config.append_args.iter().map(String::as_str).collect();
+        assert_eq!(
+            wrapper::build_args(&incoming, &[], &prepend, &[], &append),
+            ["-spdb", "-sval", "-sacl", "-nologo", "main.wixobj"]
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! `LIGHT_WRAPPER_EXPLAIN=1`: a one-line hint for the failures a Tauri bundle most often hits,
+//! printed after light fails. The exit code is never changed, so MSBuild and Tauri still see
+//! exactly what light returned.
+
+/// Light's errors by number, as in `error LGHT0217`, with what usually helps.
+const LIGHT_ERRORS: [(u32, &str); 8] = [
+    (
+        1,
+        "light failed unexpectedly, often because another process holds the output file; \
+         see LIGHT_WRAPPER_RETRIES",
+    ),
+    (
+        91,
+        "a symbol is defined twice, usually a .wixobj linked twice; LIGHT_WRAPPER_DEDUPE=1 \
+         drops repeated inputs",
+    ),
+    (
+        94,
+        "a referenced symbol is not defined; an input or -ext is missing from the link",
+    ),
+    (
+        103,
+        "a file the package refers to was not found; check the -b bind paths",
+    ),
+    (
+        144,
+        "an extension was given twice; LIGHT_WRAPPER_DEDUPE=1 drops repeated -ext pairs",
+    ),
+    (
+        204,
+        "an ICE check found a problem in the package; fix it or skip that check with \
+         LIGHT_WRAPPER_SUPPRESS_ICES",
+    ),
+    (
+        216,
+        "Windows Installer could not run an ICE action, common on locked-down agents; \
+         consider LIGHT_WRAPPER_SUPPRESS_ICES",
+    ),
+    (
+        217,
+        "ICE validation failed; consider LIGHT_WRAPPER_SUPPRESS_ICES",
+    ),
+];
+
+/// Windows status codes a crashed or missing-dependency light exits with.
+const WINDOWS_STATUSES: [(u32, &str); 3] = [
+    (0xC000_0005, "light crashed with an access violation"),
+    (
+        0xC000_0135,
+        "a DLL light needs is missing; WiX v3 needs the .NET Framework 3.5",
+    ),
+    (0xC000_013A, "light was interrupted (Ctrl+C)"),
+];
+
+/// Hints for a link that exited with `code` after printing `output`: one per `LGHTnnnn` error
+/// in the output, in order, then one for the exit code itself. WiX v3 exits with the number
+/// of the error it stopped on, so `217` reads as `LGHT0217` too.
+pub fn explain(code: i32, output: &str) -> Vec<String> {
+    let mut numbers: Vec<u32> = Vec::new();
+    for line in output.lines() {
+        let upper = line.to_ascii_uppercase();
+        let words: Vec<&str> = upper.split(|c: char| !c.is_ascii_alphanumeric()).collect();
+        if !words.contains(&"ERROR") {
+            continue;
+        }
+        let number = words
+            .iter()
+            .filter_map(|word| word.strip_prefix("LGHT"))
+            .find(|digits| digits.len() == 4)
+            .and_then(|digits| digits.parse().ok());
+        numbers.extend(number.filter(|n| !numbers.contains(n)));
+    }
+    if let Ok(number) = u32::try_from(code) {
+        if !numbers.contains(&number) {
+            numbers.push(number);
+        }
+    }
+
+    let mut hints: Vec<String> = numbers
+        .iter()
+        .filter_map(|&number| {
+            let (_, hint) = LIGHT_ERRORS.iter().find(|(n, _)| *n == number)?;
+            Some(format!("LGHT{number:04}: {hint}"))
+        })
+        .collect();
+    if let Some((status, hint)) = WINDOWS_STATUSES.iter().find(|(s, _)| *s == code as u32) {
+        hints.push(format!("exit code {status:#010X}: {hint}"));
+    }
+    hints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explains_errors_in_the_output_and_the_exit_code() {
+        let output = "\
+light.exe : error LGHT0216 : An unexpected Win32 exception with error code 0x643 occurred
+light.exe : error LGHT0217 : Error executing ICE action 'ICE01'.
+light.exe : error LGHT0217 : Error executing ICE action 'ICE02'.
+main.wxs(41) : warning LGHT1076 : ICE61: This product should remove only older versions.
+";
+        assert_eq!(
+            explain(216, output),
+            [
+                format!("LGHT0216: {}", LIGHT_ERRORS[6].1),
+                "LGHT0217: ICE validation failed; consider LIGHT_WRAPPER_SUPPRESS_ICES".to_string(),
+            ]
+        );
+        assert_eq!(
+            explain(217, ""),
+            ["LGHT0217: ICE validation failed; consider LIGHT_WRAPPER_SUPPRESS_ICES"]
+        );
+    }
+
+    #[test]
+    fn explains_windows_status_codes() {
+        assert_eq!(
+            explain(0xC000_0135_u32 as i32, ""),
+            ["exit code 0xC0000135: a DLL light needs is missing; WiX v3 needs the .NET Framework 3.5"]
+        );
+    }
+
+    #[test]
+    fn unknown_failures_get_no_hint() {
+        assert!(explain(1_000_000, "error: something else went wrong\n").is_empty());
+        assert!(explain(2, "light.exe : warning LGHT0217 : validation skipped\n").is_empty());
+    }
+}
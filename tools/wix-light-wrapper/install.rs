@@ -0,0 +1,268 @@
+//! `--wrapper-install` / `--wrapper-uninstall`: puts the running wrapper in place of a WiX tool,
+//! keeping the original as `<tool>-real.exe` beside it, and puts the original back again.
+
+use std::fs;
+use std::path::Path;
+
+use crate::wrapper::{is_wrapper_copy, Tool};
+
+/// Exit code of an uninstall that found nothing to restore, so scripts can tell it apart from a
+/// restore (0) and a failure (1). An install that finds the directory already wrapped exits 0.
+pub const NOTHING_TO_UNINSTALL: i32 = 2;
+
+/// What an install or uninstall did.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    /// The directory was changed; one line per step taken.
+    Changed(Vec<String>),
+    /// The directory already was in the requested state.
+    Unchanged(String),
+}
+
+/// Renames `dir/<tool>.exe` to `<tool>-real.exe` and copies `current_exe` in as `<tool>.exe`,
+/// returning a line for each step taken. A directory that is already wrapped is left alone.
+/// Refuses (before touching anything) when the tool in `dir` is a wrapper with no real tool
+/// beside it, or when `<tool>-real.exe` exists and `force` is not set.
+pub fn install(tool: Tool, current_exe: &Path, dir: &Path, force: bool) -> Result<Outcome, String> {
+    let target = dir.join(tool.exe_name());
+    let real = dir.join(tool.real_exe_name());
+    if read_is_wrapper(&target, current_exe)? {
+        if real.is_file() && !read_is_wrapper(&real, current_exe)? {
+            return Ok(Outcome::Unchanged(format!(
+                "'{}' already wraps '{}'; nothing to do",
+                target.display(),
+                real.display()
+            )));
+        }
+        return Err(format!(
+            "'{}' is already a copy of the wrapper but '{}' is not the real tool; not installing over it",
+            target.display(),
+            real.display()
+        ));
+    }
+    if real.exists() && !force {
+        return Err(format!(
+            "'{}' already exists; pass --force to replace it with '{}'",
+            real.display(),
+            target.display()
+        ));
+    }
+
+    let mut done = Vec::new();
+    fs::rename(&target, &real).map_err(|e| {
+        format!(
+            "unable to rename '{}' to '{}': {e}",
+            target.display(),
+            real.display()
+        )
+    })?;
+    done.push(format!(
+        "renamed '{}' to '{}'",
+        target.display(),
+        real.display()
+    ));
+    // Copy from the wrapper's own path into the freed name; the running image is never written.
+    if let Err(e) = fs::copy(current_exe, &target) {
+        let undo = fs::rename(&real, &target);
+        return Err(format!(
+            "unable to copy '{}' to '{}': {e}{}",
+            current_exe.display(),
+            target.display(),
+            if undo.is_ok() {
+                " (rename undone)"
+            } else {
+                " (the original is left as the real exe)"
+            }
+        ));
+    }
+    done.push(format!(
+        "copied '{}' to '{}'",
+        current_exe.display(),
+        target.display()
+    ));
+    Ok(Outcome::Changed(done))
+}
+
+/// Reverses `install`: deletes the wrapper copy at `dir/<tool>.exe` and renames
+/// `<tool>-real.exe` back to it. A half-restored directory (only `<tool>-real.exe` left) just
+/// gets the rename. A directory that isn't wrapped is left alone, and a `<tool>.exe` that is
+/// not the wrapper is never deleted.
+pub fn uninstall(tool: Tool, current_exe: &Path, dir: &Path) -> Result<Outcome, String> {
+    let target = dir.join(tool.exe_name());
+    let real = dir.join(tool.real_exe_name());
+    let target_is_wrapper = if target.is_file() {
+        Some(read_is_wrapper(&target, current_exe)?)
+    } else {
+        None
+    };
+    if !real.is_file() {
+        return match target_is_wrapper {
+            Some(false) => Ok(Outcome::Unchanged(format!(
+                "'{}' is not wrapped; nothing to do",
+                target.display()
+            ))),
+            Some(true) => Err(format!(
+                "'{}' is the wrapper but '{}' is missing; leaving it in place",
+                target.display(),
+                real.display()
+            )),
+            None => Err(format!(
+                "neither '{}' nor '{}' exists",
+                target.display(),
+                real.display()
+            )),
+        };
+    }
+
+    let mut done = Vec::new();
+    match target_is_wrapper {
+        Some(false) => {
+            return Err(format!(
+                "'{}' is not a copy of the wrapper; not deleting it",
+                target.display()
+            ))
+        }
+        Some(true) => {
+            fs::remove_file(&target)
+                .map_err(|e| format!("unable to delete '{}': {e}", target.display()))?;
+            done.push(format!("deleted '{}'", target.display()));
+        }
+        None => {}
+    }
+    fs::rename(&real, &target).map_err(|e| {
+        format!(
+            "unable to rename '{}' to '{}': {e}",
+            real.display(),
+            target.display()
+        )
+    })?;
+    done.push(format!(
+        "renamed '{}' to '{}'",
+        real.display(),
+        target.display()
+    ));
+    Ok(Outcome::Changed(done))
+}
+
+fn read_is_wrapper(path: &Path, current_exe: &Path) -> Result<bool, String> {
+    is_wrapper_copy(path, current_exe)
+        .map_err(|e| format!("unable to read '{}': {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+    use crate::wrapper::MARKER;
+
+    /// A stand-in wrapper build in `dir`, recognisable by the embedded marker.
+    fn fake_wrapper(dir: &TempDir) -> std::path::PathBuf {
+        let path = dir.join("light_wrapper.exe");
+        let mut bytes = b"MZ build 2 ".to_vec();
+        bytes.extend_from_slice(MARKER.as_bytes());
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn swaps_the_wrapper_in_and_keeps_the_original() {
+        let dir = TempDir::new("install");
+        let wrapper = fake_wrapper(&dir);
+        let bin = dir.join("bin");
+        fs::create_dir(&bin).unwrap();
+        fs::write(bin.join("light.exe"), b"genuine light").unwrap();
+
+        let done = install(Tool::LIGHT, &wrapper, &bin, false).unwrap();
+        assert!(
+            matches!(&done, Outcome::Changed(steps) if steps.len() == 2),
+            "{done:?}"
+        );
+        assert_eq!(
+            fs::read(bin.join("light-real.exe")).unwrap(),
+            b"genuine light"
+        );
+        assert_eq!(
+            fs::read(bin.join("light.exe")).unwrap(),
+            fs::read(&wrapper).unwrap()
+        );
+
+        // Installing again, even with --force, must not bury the real linker under a wrapper.
+        let done = install(Tool::LIGHT, &wrapper, &bin, true).unwrap();
+        assert!(matches!(done, Outcome::Unchanged(_)), "{done:?}");
+        assert_eq!(
+            fs::read(bin.join("light-real.exe")).unwrap(),
+            b"genuine light"
+        );
+
+        let done = uninstall(Tool::LIGHT, &wrapper, &bin).unwrap();
+        assert!(
+            matches!(&done, Outcome::Changed(steps) if steps.len() == 2),
+            "{done:?}"
+        );
+        assert!(!bin.join("light-real.exe").exists());
+        assert_eq!(fs::read(bin.join("light.exe")).unwrap(), b"genuine light");
+        let done = uninstall(Tool::LIGHT, &wrapper, &bin).unwrap();
+        assert!(matches!(done, Outcome::Unchanged(_)), "{done:?}");
+    }
+
+    #[test]
+    fn refuses_wrapper_without_real_tool() {
+        let dir = TempDir::new("install-orphan");
+        let wrapper = fake_wrapper(&dir);
+        fs::copy(&wrapper, dir.join("light.exe")).unwrap();
+        let err = install(Tool::LIGHT, &wrapper, &dir.join(""), true).unwrap_err();
+        assert!(err.contains("already a copy of the wrapper"), "{err}");
+        let err = uninstall(Tool::LIGHT, &wrapper, &dir.join("")).unwrap_err();
+        assert!(err.contains("light-real.exe' is missing"), "{err}");
+        assert!(dir.join("light.exe").exists());
+    }
+
+    #[test]
+    fn uninstall_never_deletes_a_real_linker() {
+        let dir = TempDir::new("uninstall-genuine");
+        let wrapper = fake_wrapper(&dir);
+        fs::write(dir.join("light.exe"), b"genuine light").unwrap();
+        fs::write(dir.join("light-real.exe"), b"older light").unwrap();
+        let err = uninstall(Tool::LIGHT, &wrapper, &dir.join("")).unwrap_err();
+        assert!(err.contains("not a copy of the wrapper"), "{err}");
+        assert_eq!(fs::read(dir.join("light.exe")).unwrap(), b"genuine light");
+    }
+
+    #[test]
+    fn recognises_other_builds_of_the_wrapper() {
+        let dir = TempDir::new("install-other-build");
+        let wrapper = fake_wrapper(&dir);
+        let other = dir.join("light.exe");
+        let mut bytes = b"MZ build 1 ".to_vec();
+        bytes.extend_from_slice(MARKER.as_bytes());
+        fs::write(&other, bytes).unwrap();
+        assert!(is_wrapper_copy(&other, &wrapper).unwrap());
+        fs::write(&other, b"genuine light").unwrap();
+        assert!(!is_wrapper_copy(&other, &wrapper).unwrap());
+    }
+
+    #[test]
+    fn existing_real_exe_needs_force() {
+        let dir = TempDir::new("install-force");
+        let wrapper = fake_wrapper(&dir);
+        fs::write(dir.join("light.exe"), b"new light").unwrap();
+        fs::write(dir.join("light-real.exe"), b"old light").unwrap();
+        let root = dir.join("");
+
+        let err = install(Tool::LIGHT, &wrapper, &root, false).unwrap_err();
+        assert!(err.contains("pass --force"), "{err}");
+        assert_eq!(fs::read(dir.join("light.exe")).unwrap(), b"new light");
+
+        install(Tool::LIGHT, &wrapper, &root, true).unwrap();
+        assert_eq!(fs::read(dir.join("light-real.exe")).unwrap(), b"new light");
+    }
+
+    #[test]
+    fn missing_tool_is_reported() {
+        let dir = TempDir::new("install-missing");
+        let wrapper = fake_wrapper(&dir);
+        let err =
+            install(Tool::named("candle"), &wrapper, &dir.join("nowhere"), false).unwrap_err();
+        assert!(err.contains("candle.exe"), "{err}");
+    }
+}
@@ -0,0 +1,171 @@
+//! Passing a cancelled build on to the real tool. Without this, Ctrl+C (or a CI agent's
+//! SIGTERM) ends the wrapper while `light-real.exe` keeps running and holds the half-written
+//! MSI open. The wrapper instead forwards the interrupt and keeps waiting, so the real tool can
+//! clean up and its exit status is still propagated; a second interrupt kills it outright.
+
+use std::process::Child;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+/// Process id of the real tool being waited for, or 0 between runs.
+static CHILD: AtomicU32 = AtomicU32::new(0);
+
+/// Interrupts received while a child was running.
+static INTERRUPTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Registers `child` as the process interrupts are forwarded to, until the guard is dropped.
+pub fn watch(child: &Child) -> Watched {
+    CHILD.store(child.id(), Ordering::SeqCst);
+    Watched(child.id())
+}
+
+pub struct Watched(u32);
+
+impl Drop for Watched {
+    fn drop(&mut self) {
+        let _ = CHILD.compare_exchange(self.0, 0, Ordering::SeqCst, Ordering::SeqCst);
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::os::raw::c_int;
+
+    pub const SIGINT: c_int = 2;
+    pub const SIGKILL: c_int = 9;
+    pub const SIGTERM: c_int = 15;
+
+    extern "C" {
+        pub fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+        pub fn kill(pid: c_int, sig: c_int) -> c_int;
+        pub fn _exit(status: c_int) -> !;
+    }
+}
+
+/// Installs the SIGINT/SIGTERM handlers. Call once, before the first child is spawned.
+#[cfg(unix)]
+pub fn install() {
+    extern "C" fn on_signal(sig: std::os::raw::c_int) {
+        match CHILD.load(Ordering::SeqCst) {
+            // Nothing to wait for: end the wrapper the way the signal would have.
+            0 => unsafe { sys::_exit(128 + sig) },
+            pid => {
+                let first = INTERRUPTS.fetch_add(1, Ordering::SeqCst) == 0;
+                forward(pid, sig, first);
+            }
+        }
+    }
+    unsafe {
+        sys::signal(sys::SIGINT, on_signal);
+        sys::signal(sys::SIGTERM, on_signal);
+    }
+}
+
+/// Sends `sig` to `pid`, or SIGKILL when the tool already had its chance to stop. Only calls
+/// `kill`, so it is safe inside a signal handler.
+#[cfg(unix)]
+fn forward(pid: u32, sig: std::os::raw::c_int, first: bool) {
+    let sig = if first { sig } else { sys::SIGKILL };
+    unsafe {
+        sys::kill(pid as std::os::raw::c_int, sig);
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    pub const CTRL_C_EVENT: u32 = 0;
+    pub const CTRL_BREAK_EVENT: u32 = 1;
+
+    pub type HandlerRoutine = unsafe extern "system" fn(u32) -> i32;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        pub fn SetConsoleCtrlHandler(handler: Option<HandlerRoutine>, add: i32) -> i32;
+    }
+}
+
+/// Installs the console-control handler. Call once, before the first child is spawned.
+#[cfg(windows)]
+pub fn install() {
+    // Runs on a thread of its own, not in a signal context. The real tool shares the console
+    // and gets the same Ctrl+C itself; the wrapper only has to stay alive until it exits, and
+    // ends its process tree on a repeated Ctrl+C. Closing the console is left to the default
+    // handling, which gives every attached process the same few seconds.
+    unsafe extern "system" fn on_control(event: u32) -> i32 {
+        if event != sys::CTRL_C_EVENT && event != sys::CTRL_BREAK_EVENT {
+            return 0;
+        }
+        match CHILD.load(Ordering::SeqCst) {
+            0 => 0,
+            pid => {
+                if INTERRUPTS.fetch_add(1, Ordering::SeqCst) > 0 {
+                    let _ = std::process::Command::new("taskkill")
+                        .args(["/T", "/F", "/PID", &pid.to_string()])
+                        .stdin(std::process::Stdio::null())
+                        .stdout(std::process::Stdio::null())
+                        .stderr(std::process::Stdio::null())
+                        .status();
+                }
+                1
+            }
+        }
+    }
+    unsafe {
+        sys::SetConsoleCtrlHandler(Some(on_control), 1);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::{Command, Stdio};
+    use std::thread;
+    use std::time::Duration;
+
+    /// A child that reports SIGTERM with exit code 7, or ignores it.
+    fn child(on_term: &str) -> Child {
+        let mut child = Command::new("/bin/sh")
+            .arg("-c")
+            .arg(format!(
+                "trap '{on_term}' TERM; echo ready; while :; do sleep 0.05; done"
+            ))
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        // Wait until the trap is in place.
+        let mut ready = [0u8; 6];
+        let stdout = child.stdout.as_mut().unwrap();
+        std::io::Read::read_exact(stdout, &mut ready).unwrap();
+        child
+    }
+
+    #[test]
+    fn first_interrupt_is_forwarded() {
+        let mut child = child("exit 7");
+        forward(child.id(), sys::SIGTERM, true);
+        assert_eq!(child.wait().unwrap().code(), Some(7));
+    }
+
+    #[test]
+    fn repeated_interrupt_kills_the_child() {
+        let mut child = child("");
+        forward(child.id(), sys::SIGTERM, true);
+        thread::sleep(Duration::from_millis(200));
+        assert!(child.try_wait().unwrap().is_none());
+        forward(child.id(), sys::SIGTERM, false);
+        assert_eq!(child.wait().unwrap().signal(), Some(sys::SIGKILL));
+    }
+
+    #[test]
+    fn watched_child_is_released_when_done() {
+        let mut child = child("exit 7");
+        {
+            let _watched = watch(&child);
+            assert_ne!(CHILD.load(Ordering::SeqCst), 0);
+        }
+        // Another test may have registered its own child meanwhile, but never this one.
+        assert_ne!(CHILD.load(Ordering::SeqCst), child.id());
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+}
@@ -0,0 +1,92 @@
+//! `LIGHT_WRAPPER_LOCK`: one wrapped invocation at a time per lock file. Parallel bundling jobs
+//! on one agent otherwise drive light at the same cabinet cache and fail with sharing
+//! violations. The lock is the operating system's (`flock`, `LockFileEx`), so it is also
+//! released when the wrapper dies without cleaning up.
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An acquired lock, held until dropped.
+pub struct Lock {
+    _file: File,
+}
+
+/// Locks `path` exclusively, creating the file if needed, and waits up to `timeout` for
+/// another holder to let go; `on_wait` is called once if it has to. Once that has passed an
+/// `io::ErrorKind::TimedOut` error is returned.
+pub fn acquire(path: &Path, timeout: Duration, on_wait: impl FnOnce()) -> io::Result<Lock> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?;
+    let started = Instant::now();
+    let mut on_wait = Some(on_wait);
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(Lock { _file: file }),
+            Err(TryLockError::Error(e)) => return Err(e),
+            Err(TryLockError::WouldBlock) => {}
+        }
+        let elapsed = started.elapsed();
+        if elapsed >= timeout {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "another invocation still held it after {}s",
+                    timeout.as_secs()
+                ),
+            ));
+        }
+        if let Some(on_wait) = on_wait.take() {
+            on_wait();
+        }
+        thread::sleep(POLL_INTERVAL.min(timeout - elapsed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn second_holder_waits_until_the_first_is_dropped() {
+        let dir = TempDir::new("lock");
+        let path = dir.join("light.lock");
+        let first = acquire(&path, Duration::ZERO, || {}).unwrap();
+
+        let mut waited = false;
+        let err = acquire(&path, Duration::from_millis(300), || waited = true)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(waited);
+
+        let releaser = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            drop(first);
+        });
+        acquire(&path, Duration::from_secs(10), || {}).unwrap();
+        releaser.join().unwrap();
+    }
+
+    #[test]
+    fn free_lock_is_taken_without_waiting() {
+        let dir = TempDir::new("lock-free");
+        let path = dir.join("light.lock");
+        let mut waited = false;
+        drop(acquire(&path, Duration::ZERO, || waited = true).unwrap());
+        acquire(&path, Duration::ZERO, || waited = true).unwrap();
+        assert!(!waited);
+        assert!(path.is_file());
+
+        let missing = dir.join("missing").join("light.lock");
+        assert!(acquire(&missing, Duration::ZERO, || {}).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::json::Json;
+
+/// Size after which the log is rolled over to `<name>.1`, replacing any previous roll.
+pub const MAX_LOG_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Appends `record` to the JSON-lines log as a single line, creating the file on first use.
+/// A log already larger than `max_bytes` is rolled over first so it never grows unbounded.
+pub fn append_record(path: &Path, record: &Json, max_bytes: u64) -> io::Result<()> {
+    if fs::metadata(path).is_ok_and(|m| m.len() > max_bytes) {
+        let mut rolled = OsString::from(path.as_os_str());
+        rolled.push(".1");
+        fs::rename(path, rolled)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{record}")
+}
+
+/// RFC 3339 UTC timestamp with millisecond precision, e.g. `2024-05-01T09:30:00.250Z`.
+pub fn utc_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let rem = secs % 86_400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Days since 1970-01-01 to a proleptic Gregorian `(year, month, day)`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn formats_utc_timestamps() {
+        assert_eq!(utc_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        let leap_day = UNIX_EPOCH + Duration::from_millis(951_827_696_789);
+        assert_eq!(utc_timestamp(leap_day), "2000-02-29T12:34:56.789Z");
+        let new_year = UNIX_EPOCH + Duration::from_secs(1_735_689_599);
+        assert_eq!(utc_timestamp(new_year), "2024-12-31T23:59:59.000Z");
+    }
+
+    #[test]
+    fn appends_one_line_per_record() {
+        let path =
+            std::env::temp_dir().join(format!("light-wrapper-log-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        append_record(&path, &Json::object(vec![("n", 1.into())]), MAX_LOG_BYTES).unwrap();
+        append_record(&path, &Json::object(vec![("n", 2.into())]), MAX_LOG_BYTES).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text, "{\"n\":1}\n{\"n\":2}\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rolls_over_oversized_logs() {
+        let path =
+            std::env::temp_dir().join(format!("light-wrapper-roll-{}.log", std::process::id()));
+        let rolled = path.with_extension("log.1");
+        let record = Json::object(vec![("n", 1.into())]);
+        append_record(&path, &record, 10).unwrap();
+        append_record(&path, &record, 10).unwrap();
+        append_record(&path, &record, 10).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{\"n\":1}\n");
+        assert_eq!(
+            std::fs::read_to_string(&rolled).unwrap(),
+            "{\"n\":1}\n{\"n\":1}\n"
+        );
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&rolled).unwrap();
+    }
+}
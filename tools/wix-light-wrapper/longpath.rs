@@ -0,0 +1,199 @@
+//! Windows' `\\?\` extended-length paths, for projects nested deeply enough that the real tool's
+//! path or the MSI's passes `MAX_PATH`. With the prefix, Windows takes a path as-is, neither
+//! limiting its length nor normalizing it, so it is only applied to paths normalized here.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::wrapper;
+
+/// Paths this long or longer need the prefix unless long paths are enabled for the process.
+pub const MAX_PATH: usize = 260;
+
+/// Flags whose value is a path: `-out app.msi`, `-cc C:\cab` and `-b C:\src` (also attached,
+/// as in `-out:app.msi`, and `-b name=C:\src` keeps its bind path name).
+const PATH_FLAGS: [&str; 3] = ["-out", "-b", "-cc"];
+
+/// `path` with the `\\?\` prefix, normalized as Windows would have: `C:/x/../y` becomes
+/// `\\?\C:\y` and `\\server\share\y` becomes `\\?\UNC\server\share\y`. `None` for anything
+/// but an absolute drive or UNC path, including relative paths, URLs and already prefixed ones.
+pub fn extended(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let is_separator = |b: u8| b == b'\\' || b == b'/';
+    let (root, rest) = if bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && is_separator(bytes[2])
+    {
+        (format!(r"\\?\{}\", &path[..2]), &path[3..])
+    } else if bytes.len() > 2 && is_separator(bytes[0]) && is_separator(bytes[1]) {
+        if matches!(bytes[2], b'?' | b'.') {
+            return None;
+        }
+        (r"\\?\UNC\".to_string(), &path[2..])
+    } else {
+        return None;
+    };
+    let mut parts: Vec<&str> = Vec::new();
+    for part in rest.split(['\\', '/']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    Some(root + &parts.join("\\"))
+}
+
+/// The real tool's path as it should be spawned: on Windows one of `MAX_PATH` or more is
+/// canonicalized, which gives the `\\?\` form. Shorter paths, and other platforms, are left
+/// as they are, so messages show them as configured.
+pub fn real_exe(path: &Path) -> PathBuf {
+    if !cfg!(windows) || path.as_os_str().len() < MAX_PATH {
+        return path.to_path_buf();
+    }
+    path.canonicalize().unwrap_or_else(|_| {
+        path.to_str()
+            .and_then(extended)
+            .map_or_else(|| path.to_path_buf(), PathBuf::from)
+    })
+}
+
+/// `LIGHT_WRAPPER_LONGPATH=1`: gives the values of `PATH_FLAGS` and bare `.wixobj`/`.wixlib`
+/// inputs the `\\?\` prefix where they are absolute paths (see `extended`). Arguments inside
+/// response files are left alone.
+pub fn rewrite_args(args: &mut [OsString]) {
+    let mut value_of_flag = false;
+    for arg in args.iter_mut() {
+        let Some(text) = arg.to_str() else {
+            value_of_flag = false;
+            continue;
+        };
+        let rewritten = if std::mem::take(&mut value_of_flag) {
+            bind_path(text)
+        } else if PATH_FLAGS.iter().any(|f| wrapper::flag_matches(text, f)) {
+            match text.split_once(':') {
+                Some((flag, value)) => bind_path(value).map(|value| format!("{flag}:{value}")),
+                None => {
+                    value_of_flag = true;
+                    None
+                }
+            }
+        } else if is_input(text) {
+            extended(text)
+        } else {
+            None
+        };
+        if let Some(rewritten) = rewritten {
+            *arg = rewritten.into();
+        }
+    }
+}
+
+/// `extended` for a flag's value, keeping the `name=` of a named bind path.
+fn bind_path(value: &str) -> Option<String> {
+    if let Some(path) = extended(value) {
+        return Some(path);
+    }
+    let (name, path) = value.split_once('=')?;
+    extended(path).map(|path| format!("{name}={path}"))
+}
+
+fn is_input(arg: &str) -> bool {
+    let arg = arg.to_ascii_lowercase();
+    !arg.starts_with(['-', '@']) && (arg.ends_with(".wixobj") || arg.ends_with(".wixlib"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn os_strings(items: &[&str]) -> Vec<OsString> {
+        items.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn absolute_paths_get_the_prefix_normalized() {
+        assert_eq!(
+            extended(r"C:\build\target\app.msi").as_deref(),
+            Some(r"\\?\C:\build\target\app.msi")
+        );
+        assert_eq!(
+            extended("d:/build/./obj/../target//app.msi").as_deref(),
+            Some(r"\\?\d:\build\target\app.msi")
+        );
+        assert_eq!(
+            extended(r"\\agent\share\out\app.msi").as_deref(),
+            Some(r"\\?\UNC\agent\share\out\app.msi")
+        );
+        for unchanged in [
+            r"\\?\C:\app.msi",
+            r"\\.\pipe\light",
+            r"target\app.msi",
+            r"C:app.msi",
+            r"\app.msi",
+            "/tmp/app.msi",
+            "https://example.com/app.msi",
+            "",
+        ] {
+            assert_eq!(extended(unchanged), None, "{unchanged}");
+        }
+    }
+
+    #[test]
+    fn rewrites_path_values_and_inputs_past_max_path() {
+        let deep = format!(r"C:\ci\{}target\release", "nested\\".repeat(40));
+        assert!(deep.len() > MAX_PATH);
+        let msi = format!(r"{deep}\bundle\msi\app.msi");
+        let obj = format!(r"{deep}\wix\x64\main.wixobj");
+        let mut args = os_strings(&[
+            "-out",
+            &msi,
+            &format!("-out:{msi}"),
+            &format!("/cc:{deep}\\cab"),
+            "-b",
+            &format!("assets={deep}\\assets"),
+            "-b",
+            r"relative\dir",
+            &obj,
+            "relative.wixobj",
+            "-loc",
+            r"C:\abs\en-us.wxl",
+            "-ext",
+            "WixUIExtension",
+        ]);
+        rewrite_args(&mut args);
+        assert_eq!(
+            args,
+            os_strings(&[
+                "-out",
+                &format!(r"\\?\{msi}"),
+                &format!(r"-out:\\?\{msi}"),
+                &format!(r"/cc:\\?\{deep}\cab"),
+                "-b",
+                &format!(r"assets=\\?\{deep}\assets"),
+                "-b",
+                r"relative\dir",
+                &format!(r"\\?\{obj}"),
+                "relative.wixobj",
+                "-loc",
+                r"C:\abs\en-us.wxl",
+                "-ext",
+                "WixUIExtension",
+            ])
+        );
+
+        // Already prefixed, nothing changes the second time.
+        let once = args.clone();
+        rewrite_args(&mut args);
+        assert_eq!(args, once);
+    }
+
+    #[test]
+    fn short_real_exe_paths_are_left_alone() {
+        let path = Path::new("/opt/wix/light-real.exe");
+        assert_eq!(real_exe(path), path);
+    }
+}
@@ -0,0 +1,31 @@
+// The module tree both wrapper binaries are built from. Each entry point includes this file,
+// so a module added here is compiled into all of them.
+
+mod annotate;
+mod app;
+mod baseline;
+mod cab_cache;
+mod capture;
+mod cli;
+mod config;
+mod explain;
+mod install;
+mod interrupt;
+mod json;
+mod lock;
+mod log;
+mod longpath;
+mod msi;
+mod profile;
+mod report;
+mod response_file;
+mod sha256;
+mod sign;
+#[cfg(test)]
+mod testutil;
+mod toml;
+mod toolset;
+mod validation;
+mod vars;
+mod wait;
+mod wrapper;
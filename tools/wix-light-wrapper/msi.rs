@@ -0,0 +1,586 @@
+//! `verify --msi app.msi`: a quick check that a linked MSI is at least a readable database,
+//! since with `-sval` WiX's own validation never looked at it. The OLE compound file (MS-CFB)
+//! and the MSI string pool are read by hand, so the wrapper stays free of dependencies.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+const SIGNATURE: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+const END_OF_CHAIN: u32 = 0xFFFF_FFFE;
+const FREE_SECTOR: u32 = 0xFFFF_FFFF;
+const NO_ENTRY: u32 = 0xFFFF_FFFF;
+const MINI_SECTOR_SIZE: usize = 64;
+const STREAM: u8 = 2;
+
+/// What `verify` found in an MSI.
+#[derive(Debug, PartialEq)]
+pub struct MsiInfo {
+    /// The names listed in `_Tables`.
+    pub tables: Vec<String>,
+    pub product_code: String,
+    pub product_version: String,
+}
+
+/// Opens `path` as an MSI database: a compound file with `_Tables` and `_Columns` streams and
+/// a `Property` table naming the ProductCode and ProductVersion. The error says what is wrong.
+pub fn verify(path: &Path) -> Result<MsiInfo, String> {
+    let file = File::open(path).map_err(|e| format!("unable to open '{}': {e}", path.display()))?;
+    read_database(file).map_err(|e| format!("'{}' is not a readable MSI: {e}", path.display()))
+}
+
+fn read_database(reader: impl Read + Seek) -> Result<MsiInfo, String> {
+    let mut file = CompoundFile::open(reader)?;
+    let tables = file.table("_Tables")?;
+    file.table("_Columns")?;
+    let pool = StringPool::new(&file.table("_StringPool")?, file.table("_StringData")?)?;
+    let tables = tables
+        .chunks(pool.ref_size)
+        .map(|id| pool.get(id).map(str::to_string))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Two string columns, `Property` and `Value`, stored one column after the other.
+    let property = file.table("Property")?;
+    let rows = property.len() / (2 * pool.ref_size);
+    let (names, values) = property.split_at(rows * pool.ref_size);
+    let mut product_code = None;
+    let mut product_version = None;
+    for (name, value) in names
+        .chunks(pool.ref_size)
+        .zip(values.chunks(pool.ref_size))
+    {
+        match pool.get(name)? {
+            "ProductCode" => product_code = Some(pool.get(value)?.to_string()),
+            "ProductVersion" => product_version = Some(pool.get(value)?.to_string()),
+            _ => {}
+        }
+    }
+    Ok(MsiInfo {
+        tables,
+        product_code: product_code.ok_or("its Property table has no ProductCode")?,
+        product_version: product_version.ok_or("its Property table has no ProductVersion")?,
+    })
+}
+
+/// The stream name MSI gives table `name`: a 0x4840 marker, then the name packed two
+/// characters to a UTF-16 unit using a 64-character alphabet.
+fn table_stream_name(name: &str) -> Vec<u16> {
+    fn packed(c: u8) -> u16 {
+        match c {
+            b'0'..=b'9' => u16::from(c - b'0'),
+            b'A'..=b'Z' => u16::from(c - b'A') + 10,
+            b'a'..=b'z' => u16::from(c - b'a') + 36,
+            b'.' => 62,
+            _ => 63,
+        }
+    }
+    let mut encoded = vec![0x4840];
+    for pair in name.as_bytes().chunks(2) {
+        encoded.push(match pair {
+            [a, b] => 0x3800 + packed(*a) + (packed(*b) << 6),
+            [a] => 0x4800 + packed(*a),
+            _ => unreachable!("chunks of two"),
+        });
+    }
+    encoded
+}
+
+struct Entry {
+    name: Vec<u16>,
+    kind: u8,
+    left: u32,
+    right: u32,
+    child: u32,
+    start: u32,
+    size: u64,
+}
+
+/// An OLE compound file, with the sector tables and directory loaded.
+struct CompoundFile<R> {
+    reader: R,
+    sector_shift: u32,
+    fat: Vec<u32>,
+    mini_fat: Vec<u32>,
+    mini_stream: Vec<u8>,
+    mini_cutoff: u64,
+    entries: Vec<Entry>,
+}
+
+impl<R: Read + Seek> CompoundFile<R> {
+    fn open(mut reader: R) -> Result<CompoundFile<R>, String> {
+        let mut header = [0u8; 512];
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| "it is shorter than a compound file header")?;
+        if header[..8] != SIGNATURE {
+            return Err("it is not an OLE compound file".to_string());
+        }
+        let u32_at = |offset: usize| u32_le(&header[offset..]);
+        let major = u16::from_le_bytes([header[0x1A], header[0x1B]]);
+        let sector_shift = u32::from(u16::from_le_bytes([header[0x1E], header[0x1F]]));
+        if !matches!((major, sector_shift), (3, 9) | (4, 12)) {
+            return Err(format!(
+                "unsupported compound file version {major} with {}-byte sectors",
+                1u64 << sector_shift.min(63)
+            ));
+        }
+        let mut file = CompoundFile {
+            reader,
+            sector_shift,
+            fat: Vec::new(),
+            mini_fat: Vec::new(),
+            mini_stream: Vec::new(),
+            mini_cutoff: u64::from(u32_at(0x38)),
+            entries: Vec::new(),
+        };
+
+        // The FAT's own sectors: 109 listed in the header, any further ones in a DIFAT chain
+        // whose sectors end with the next one's number.
+        let fat_sectors = u32_at(0x2C) as usize;
+        let mut locations: Vec<u32> = header[0x4C..].chunks(4).map(u32_le).collect();
+        let mut difat = u32_at(0x44);
+        for _ in 0..u32_at(0x48) {
+            if locations.len() >= fat_sectors || difat == END_OF_CHAIN || difat == FREE_SECTOR {
+                break;
+            }
+            let sector = file.sector(difat)?;
+            let (listed, next) = sector.split_at(sector.len() - 4);
+            locations.extend(listed.chunks(4).map(u32_le));
+            difat = u32_le(next);
+        }
+        if locations.len() < fat_sectors {
+            return Err("its FAT is incomplete".to_string());
+        }
+        for &location in &locations[..fat_sectors] {
+            let sector = file.sector(location)?;
+            file.fat.extend(sector.chunks(4).map(u32_le));
+        }
+
+        let directory = file.chain(u32_at(0x30), None)?;
+        file.entries = directory
+            .chunks_exact(128)
+            .map(|entry| {
+                // A byte count of UTF-16 units, terminator included.
+                let name_len = usize::from(u16::from_le_bytes([entry[0x40], entry[0x41]]));
+                if name_len % 2 != 0 {
+                    return Err(format!(
+                        "a directory entry has a name of odd length {name_len}"
+                    ));
+                }
+                let size = u64::from_le_bytes(entry[0x78..0x80].try_into().unwrap());
+                Ok(Entry {
+                    name: entry[..name_len.clamp(2, 64) - 2]
+                        .chunks(2)
+                        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                        .collect(),
+                    kind: entry[0x42],
+                    left: u32_le(&entry[0x44..]),
+                    right: u32_le(&entry[0x48..]),
+                    child: u32_le(&entry[0x4C..]),
+                    start: u32_le(&entry[0x74..]),
+                    // Version 3 files may leave garbage in the high half.
+                    size: if major == 3 { size & 0xFFFF_FFFF } else { size },
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        let Some(root) = file.entries.first() else {
+            return Err("its directory is empty".to_string());
+        };
+        let (root_start, root_size) = (root.start, root.size);
+        file.mini_stream = file.chain(root_start, Some(root_size))?;
+        let mini_fat = file.chain(u32_at(0x3C), None)?;
+        file.mini_fat = mini_fat.chunks(4).map(u32_le).collect();
+        Ok(file)
+    }
+
+    /// The contents of table `name`, from the stream of that name in the root storage.
+    fn table(&mut self, name: &str) -> Result<Vec<u8>, String> {
+        let wanted = table_stream_name(name);
+        let mut pending = vec![self.entries[0].child];
+        let mut visited = 0;
+        while let Some(index) = pending.pop() {
+            let Some(entry) = self.entries.get(index as usize) else {
+                continue;
+            };
+            visited += 1;
+            if visited > self.entries.len() {
+                return Err("its directory tree is cyclic".to_string());
+            }
+            if entry.kind == STREAM && entry.name == wanted {
+                let (start, size) = (entry.start, entry.size);
+                return if size < self.mini_cutoff {
+                    self.mini_chain(start, size)
+                } else {
+                    self.chain(start, Some(size))
+                };
+            }
+            pending.extend(
+                [entry.left, entry.right]
+                    .into_iter()
+                    .filter(|&i| i != NO_ENTRY),
+            );
+        }
+        Err(format!("it has no {name} stream"))
+    }
+
+    fn sector(&mut self, id: u32) -> Result<Vec<u8>, String> {
+        let mut sector = vec![0u8; 1 << self.sector_shift];
+        self.reader
+            .seek(SeekFrom::Start((u64::from(id) + 1) << self.sector_shift))
+            .and_then(|_| self.reader.read_exact(&mut sector))
+            .map_err(|_| format!("sector {id} lies past the end of the file"))?;
+        Ok(sector)
+    }
+
+    /// The sectors chained from `start` in the FAT, cut to `size` when given.
+    fn chain(&mut self, start: u32, size: Option<u64>) -> Result<Vec<u8>, String> {
+        let mut data = Vec::new();
+        let mut id = start;
+        while id != END_OF_CHAIN {
+            if data.len() >> self.sector_shift > self.fat.len() {
+                return Err("a sector chain does not end".to_string());
+            }
+            data.extend(self.sector(id)?);
+            id = *self
+                .fat
+                .get(id as usize)
+                .ok_or_else(|| format!("sector {id} is not in the FAT"))?;
+        }
+        truncate(data, size)
+    }
+
+    /// The mini sectors chained from `start` in the mini FAT, cut to `size`.
+    fn mini_chain(&self, start: u32, size: u64) -> Result<Vec<u8>, String> {
+        let mut data = Vec::new();
+        let mut id = start;
+        while id != END_OF_CHAIN && (data.len() as u64) < size {
+            if data.len() / MINI_SECTOR_SIZE > self.mini_fat.len() {
+                return Err("a mini sector chain does not end".to_string());
+            }
+            let offset = id as usize * MINI_SECTOR_SIZE;
+            let sector = self
+                .mini_stream
+                .get(offset..offset + MINI_SECTOR_SIZE)
+                .ok_or_else(|| format!("mini sector {id} lies past the mini stream"))?;
+            data.extend_from_slice(sector);
+            id = *self
+                .mini_fat
+                .get(id as usize)
+                .ok_or_else(|| format!("mini sector {id} is not in the mini FAT"))?;
+        }
+        truncate(data, Some(size))
+    }
+}
+
+fn truncate(mut data: Vec<u8>, size: Option<u64>) -> Result<Vec<u8>, String> {
+    if let Some(size) = size {
+        if (data.len() as u64) < size {
+            return Err(format!(
+                "a stream of {size} bytes has only {} stored",
+                data.len()
+            ));
+        }
+        data.truncate(size as usize);
+    }
+    Ok(data)
+}
+
+fn u32_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// The database's strings: `_StringPool` holds a length and reference count per string id,
+/// `_StringData` the strings themselves, back to back.
+struct StringPool {
+    strings: Vec<String>,
+    /// Bytes per string reference in the tables: 2, or 3 in databases with many strings.
+    ref_size: usize,
+}
+
+impl StringPool {
+    fn new(pool: &[u8], data: Vec<u8>) -> Result<StringPool, String> {
+        let words: Vec<u16> = pool
+            .chunks_exact(2)
+            .map(|w| u16::from_le_bytes([w[0], w[1]]))
+            .collect();
+        if words.len() < 2 {
+            return Err("its string pool has no header".to_string());
+        }
+        let ref_size = if words[1] & 0x8000 != 0 { 3 } else { 2 };
+        // Id 0 is the null string.
+        let mut strings = vec![String::new()];
+        let mut offset = 0;
+        let mut entries = words[2..].chunks(2);
+        while let Some(entry) = entries.next() {
+            let &[len, refs] = entry else { break };
+            // Strings longer than 65535 bytes have a zero length; the next entry holds it.
+            let len = match (len, refs) {
+                (0, 0) => 0,
+                (0, _) => match entries.next() {
+                    Some(&[low, high]) => usize::from(low) | (usize::from(high) << 16),
+                    _ => return Err("its string pool is truncated".to_string()),
+                },
+                (len, _) => usize::from(len),
+            };
+            let bytes = data
+                .get(offset..offset + len)
+                .ok_or("its string data is shorter than the pool says")?;
+            strings.push(String::from_utf8_lossy(bytes).into_owned());
+            offset += len;
+        }
+        Ok(StringPool { strings, ref_size })
+    }
+
+    fn get(&self, reference: &[u8]) -> Result<&str, String> {
+        let id = reference
+            .iter()
+            .rev()
+            .fold(0usize, |id, &b| (id << 8) | usize::from(b));
+        self.strings
+            .get(id)
+            .map(String::as_str)
+            .ok_or_else(|| format!("string id {id} is not in the string pool"))
+    }
+}
+
+/// A minimal version-3 compound file holding `streams` in its root storage: streams under
+/// 4096 bytes go to the mini stream, as the format requires, larger ones to regular sectors.
+#[cfg(test)]
+pub fn compound_file(streams: &[(Vec<u16>, Vec<u8>)]) -> Vec<u8> {
+    const SECTOR: usize = 512;
+    // Sector 0 holds the FAT; `sectors` is everything after it.
+    let mut fat = vec![0xFFFF_FFFD];
+    let mut sectors = Vec::new();
+    let mut store = |fat: &mut Vec<u32>, data: &[u8]| -> u32 {
+        if data.is_empty() {
+            return END_OF_CHAIN;
+        }
+        let start = fat.len() as u32;
+        let count = data.len().div_ceil(SECTOR) as u32;
+        fat.extend((1..=count).map(|i| if i == count { END_OF_CHAIN } else { start + i }));
+        sectors.extend_from_slice(data);
+        sectors.resize(sectors.len().div_ceil(SECTOR) * SECTOR, 0);
+        start
+    };
+
+    let mut mini_stream = Vec::new();
+    let mut mini_fat: Vec<u32> = Vec::new();
+    let mut starts = Vec::new();
+    for (_, data) in streams {
+        if data.len() >= 4096 {
+            starts.push(store(&mut fat, data));
+        } else if data.is_empty() {
+            starts.push(END_OF_CHAIN);
+        } else {
+            let start = mini_fat.len() as u32;
+            let count = data.len().div_ceil(MINI_SECTOR_SIZE) as u32;
+            mini_fat.extend((1..=count).map(|i| if i == count { END_OF_CHAIN } else { start + i }));
+            mini_stream.extend_from_slice(data);
+            mini_stream.resize(
+                mini_stream.len().div_ceil(MINI_SECTOR_SIZE) * MINI_SECTOR_SIZE,
+                0,
+            );
+            starts.push(start);
+        }
+    }
+    let root_start = store(&mut fat, &mini_stream);
+    mini_fat.resize(mini_fat.len().div_ceil(128) * 128, FREE_SECTOR);
+    let mini_fat_bytes: Vec<u8> = mini_fat.iter().flat_map(|id| id.to_le_bytes()).collect();
+    let mini_fat_start = store(&mut fat, &mini_fat_bytes);
+
+    let entry = |name: &[u16], kind: u8, right: u32, child: u32, start: u32, size: usize| {
+        let mut entry = [0u8; 128];
+        for (i, unit) in name.iter().enumerate() {
+            entry[i * 2..i * 2 + 2].copy_from_slice(&unit.to_le_bytes());
+        }
+        entry[0x40..0x42].copy_from_slice(&(((name.len() + 1) * 2) as u16).to_le_bytes());
+        entry[0x42] = kind;
+        entry[0x44..0x48].copy_from_slice(&NO_ENTRY.to_le_bytes());
+        entry[0x48..0x4C].copy_from_slice(&right.to_le_bytes());
+        entry[0x4C..0x50].copy_from_slice(&child.to_le_bytes());
+        entry[0x74..0x78].copy_from_slice(&start.to_le_bytes());
+        entry[0x78..0x80].copy_from_slice(&(size as u64).to_le_bytes());
+        entry
+    };
+    let root_name: Vec<u16> = "Root Entry".encode_utf16().collect();
+    let first_child = if streams.is_empty() { NO_ENTRY } else { 1 };
+    let mut directory = entry(
+        &root_name,
+        5,
+        NO_ENTRY,
+        first_child,
+        root_start,
+        mini_stream.len(),
+    )
+    .to_vec();
+    // Each stream is the right sibling of the one before: a valid, if lopsided, tree.
+    for (i, ((name, data), start)) in streams.iter().zip(&starts).enumerate() {
+        let right = if i + 1 < streams.len() {
+            i as u32 + 2
+        } else {
+            NO_ENTRY
+        };
+        directory.extend(entry(name, STREAM, right, NO_ENTRY, *start, data.len()));
+    }
+    let directory_start = store(&mut fat, &directory);
+    assert!(
+        fat.len() <= SECTOR / 4,
+        "one FAT sector is enough for tests"
+    );
+    fat.resize(SECTOR / 4, FREE_SECTOR);
+
+    let mut file = Vec::with_capacity(SECTOR * 2 + sectors.len());
+    file.extend(SIGNATURE);
+    file.extend([0; 16]);
+    file.extend(0x3Eu16.to_le_bytes());
+    file.extend(3u16.to_le_bytes());
+    file.extend(0xFFFEu16.to_le_bytes());
+    file.extend(9u16.to_le_bytes());
+    file.extend(6u16.to_le_bytes());
+    file.extend([0; 10]);
+    for field in [
+        1,
+        directory_start,
+        0,
+        4096,
+        mini_fat_start,
+        (mini_fat_bytes.len() / SECTOR) as u32,
+        END_OF_CHAIN,
+        0,
+        0,
+    ] {
+        file.extend(field.to_le_bytes());
+    }
+    file.resize(SECTOR, 0xFF);
+    file.extend(fat.iter().flat_map(|id| id.to_le_bytes()));
+    file.extend(sectors);
+    file
+}
+
+/// An MSI database with just enough in it for `verify`: the system tables and a `Property`
+/// table holding `properties`, written with 3-byte string references if `long_refs`.
+#[cfg(test)]
+pub fn database(properties: &[(&str, &str)], long_refs: bool) -> Vec<u8> {
+    let mut strings: Vec<&str> = vec!["Property", "Value"];
+    for (name, value) in properties {
+        strings.extend([*name, *value]);
+    }
+    let ref_size = if long_refs { 3 } else { 2 };
+    let reference = |s: &str| {
+        let id = strings.iter().position(|&known| known == s).unwrap() as u32 + 1;
+        id.to_le_bytes()[..ref_size].to_vec()
+    };
+    let mut pool = vec![0xE4, 0x04, 0, if long_refs { 0x80 } else { 0 }];
+    let mut data = Vec::new();
+    for s in &strings {
+        pool.extend((s.len() as u16).to_le_bytes());
+        pool.extend(1u16.to_le_bytes());
+        data.extend(s.as_bytes());
+    }
+    let mut property = Vec::new();
+    property.extend(properties.iter().flat_map(|(name, _)| reference(name)));
+    property.extend(properties.iter().flat_map(|(_, value)| reference(value)));
+    // `_Columns` is only checked for, so its rows can stay empty.
+    compound_file(&[
+        (table_stream_name("_StringPool"), pool),
+        (table_stream_name("_StringData"), data),
+        (table_stream_name("_Tables"), reference("Property")),
+        (table_stream_name("_Columns"), Vec::new()),
+        (table_stream_name("Property"), property),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const PRODUCT: [(&str, &str); 3] = [
+        ("Manufacturer", "GuimFinancial"),
+        ("ProductCode", "{8A3F1E52-41C6-4C59-9E0B-5D1D6E2A7B11}"),
+        ("ProductVersion", "0.1.4"),
+    ];
+
+    fn info(database: Vec<u8>) -> Result<MsiInfo, String> {
+        read_database(Cursor::new(database))
+    }
+
+    #[test]
+    fn encodes_table_stream_names_like_msi() {
+        // The name of `_Tables` as it shows up in every MSI's directory.
+        assert_eq!(
+            String::from_utf16(&table_stream_name("_Tables")).unwrap(),
+            "\u{4840}\u{3F7F}\u{4164}\u{422F}\u{4836}"
+        );
+    }
+
+    #[test]
+    fn reads_the_product_code_and_version() {
+        for long_refs in [false, true] {
+            assert_eq!(
+                info(database(&PRODUCT, long_refs)),
+                Ok(MsiInfo {
+                    tables: vec!["Property".to_string()],
+                    product_code: "{8A3F1E52-41C6-4C59-9E0B-5D1D6E2A7B11}".to_string(),
+                    product_version: "0.1.4".to_string(),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn reads_streams_stored_in_regular_sectors() {
+        let manufacturer = "x".repeat(5000);
+        let mut properties = PRODUCT.to_vec();
+        properties[0].1 = &manufacturer;
+        let info = info(database(&properties, false)).unwrap();
+        assert_eq!(info.product_version, "0.1.4");
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_msi_databases() {
+        let mut image = b"MZ\x90\x00 a PE image, not an MSI".to_vec();
+        image.resize(4096, 0);
+        assert_eq!(info(image).unwrap_err(), "it is not an OLE compound file");
+        let err = info(SIGNATURE.to_vec()).unwrap_err();
+        assert_eq!(err, "it is shorter than a compound file header");
+
+        let err = info(compound_file(&[(table_stream_name("_Tables"), vec![1, 0])])).unwrap_err();
+        assert_eq!(err, "it has no _Columns stream");
+
+        let err = info(database(&PRODUCT[..2], false)).unwrap_err();
+        assert_eq!(err, "its Property table has no ProductVersion");
+
+        let mut odd_name = database(&PRODUCT, false);
+        let root: Vec<u8> = "Root Entry"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let at = odd_name
+            .windows(root.len())
+            .position(|window| window == root)
+            .unwrap();
+        odd_name[at + 0x40] = 21;
+        let err = info(odd_name).unwrap_err();
+        assert_eq!(err, "a directory entry has a name of odd length 21");
+
+        let mut truncated = database(&PRODUCT, false);
+        truncated.truncate(1024);
+        let err = info(truncated).unwrap_err();
+        assert!(err.contains("past the end of the file"), "{err}");
+    }
+
+    #[test]
+    fn verify_names_the_file() {
+        let dir = crate::testutil::TempDir::new("msi");
+        let msi = dir.join("app.msi");
+        std::fs::write(&msi, b"not an msi").unwrap();
+        let err = verify(&msi).unwrap_err();
+        assert!(
+            err.starts_with(&format!("'{}' is not a readable MSI: ", msi.display())),
+            "{err}"
+        );
+        let err = verify(&dir.join("missing.msi")).unwrap_err();
+        assert!(err.starts_with("unable to open"), "{err}");
+    }
+}
@@ -0,0 +1,90 @@
+//! What the wrapper does for each tool it can stand in for, picked by the name it was installed
+//! under (see `wrapper::Tool`). Wrapping another tool is a matter of adding a row to `PROFILES`.
+
+/// Everything tool-specific about wrapping one tool.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ToolProfile {
+    /// The executable stem the wrapper is installed under, e.g. `light` for `light.exe`.
+    pub name: &'static str,
+    /// What the real tool is, for messages: `WiX linker`.
+    pub role: &'static str,
+    /// Suppression flags injected when the tool's `WIX_*_WRAPPER_SUPPRESS` is unset, in that
+    /// variable's comma-separated form.
+    pub injected_flags: &'static str,
+    /// The renamed original the wrapper forwards to, e.g. `light-real.exe`.
+    pub real_name: &'static str,
+    /// Replaces `LIGHT` in the tool's environment variable names (`CANDLE_WRAPPER_VERBOSE`).
+    /// Without one the upper-cased name is appended instead (`LIGHT_WRAPPER_VERBOSE_FOO`).
+    pub var_word: Option<&'static str>,
+    /// A WiX tool: its version is probed and requested flags are checked against it.
+    pub wix: bool,
+    /// Suppression and validation switches the tool accepts in WiX v3; later versions take
+    /// none of them. `-ice:` and `-sice:` take an ICE name and `-sw` an optional warning number.
+    /// Only the wrapper's own defaults are checked against these.
+    pub v3_flags: &'static [&'static str],
+    /// Light's ICE handling: the first link keeps validation on and one that fails validation
+    /// is retried with `-sval`; `WIX_LIGHT_WRAPPER_ADAPTIVE` and `LIGHT_WRAPPER_ALWAYS_SVAL`
+    /// apply.
+    pub validation_retry: bool,
+}
+
+/// The suppression and validation switches in WiX v3's `light -?`.
+const LIGHT_V3_FLAGS: &[&str] = &[
+    "-ice:", "-sa", "-sacl", "-sadmin", "-sadv", "-sf", "-sh", "-sice:", "-sl", "-sloc", "-sma",
+    "-spdb", "-spsd", "-ss", "-sts", "-sui", "-sv", "-sval", "-sw",
+];
+
+pub const LIGHT: ToolProfile = ToolProfile {
+    name: "light",
+    role: "WiX linker",
+    injected_flags: "sval,sacl",
+    real_name: "light-real.exe",
+    var_word: Some("LIGHT"),
+    wix: true,
+    v3_flags: LIGHT_V3_FLAGS,
+    validation_retry: true,
+};
+
+/// Candle has no validation step to skip, so it injects nothing unless configured.
+pub const CANDLE: ToolProfile = ToolProfile {
+    name: "candle",
+    role: "WiX compiler",
+    injected_flags: "",
+    real_name: "candle-real.exe",
+    var_word: Some("CANDLE"),
+    wix: true,
+    v3_flags: &["-sfdvital", "-ss", "-sw"],
+    validation_retry: false,
+};
+
+/// Heat, the harvester, likewise injects only what `WIX_HEAT_WRAPPER_SUPPRESS` (e.g.
+/// `sreg,scom`) or `heat-wrapper.toml` asks for.
+pub const HEAT: ToolProfile = ToolProfile {
+    name: "heat",
+    role: "WiX harvester",
+    injected_flags: "",
+    real_name: "heat-real.exe",
+    var_word: Some("HEAT"),
+    wix: true,
+    v3_flags: &["-scom", "-sfrag", "-srd", "-sreg", "-suid", "-svb6", "-sw"],
+    validation_retry: false,
+};
+
+pub const PROFILES: [&ToolProfile; 3] = [&LIGHT, &CANDLE, &HEAT];
+
+/// The profile for a tool without a row in `PROFILES`: plain passthrough to `<name>-real.exe`,
+/// with nothing injected unless configured and none of the WiX handling.
+pub fn passthrough(name: &str) -> &'static ToolProfile {
+    // Picked once per process, so the profile can live as long as it does.
+    let name: &'static str = Box::leak(name.into());
+    Box::leak(Box::new(ToolProfile {
+        name,
+        role: Box::leak(format!("'{name}' tool").into_boxed_str()),
+        injected_flags: "",
+        real_name: Box::leak(format!("{name}-real.exe").into_boxed_str()),
+        var_word: None,
+        wix: false,
+        v3_flags: &[],
+        validation_retry: false,
+    }))
+}
@@ -0,0 +1,160 @@
+//! The `LIGHT_WRAPPER_REPORT` document: one JSON object describing a finished link, including a
+//! fingerprint of the MSI it produced, for build provenance.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::json::Json;
+use crate::log;
+use crate::sha256;
+use crate::wrapper;
+
+/// What one wrapped invocation ran and what came of it.
+pub struct InvocationReport<'a> {
+    pub real_exe: &'a Path,
+    /// The final argument vector handed to the real tool.
+    pub args: &'a [OsString],
+    /// The arguments the wrapper added to the ones it was called with.
+    pub injected: Vec<&'a OsString>,
+    pub started: SystemTime,
+    pub finished: SystemTime,
+    /// The real tool's exit code; `None` when it could not be run or was killed.
+    pub exit_code: Option<i32>,
+}
+
+impl InvocationReport<'_> {
+    /// The output file named in the arguments (see `wrapper::output_path`).
+    pub fn output_path(&self) -> Option<PathBuf> {
+        wrapper::output_path(self.args)
+    }
+
+    /// The report as JSON. The output's size and hash are only filled in for a successful
+    /// link; after a failure whatever sits at the output path is not this run's.
+    pub fn to_json(&self) -> Json {
+        let output = self.output_path();
+        let (size, hash) = match &output {
+            Some(path) if self.exit_code == Some(0) => (
+                fs::metadata(path).ok().map(|m| m.len() as i64),
+                sha256::file_hex(path).ok(),
+            ),
+            _ => (None, None),
+        };
+        Json::object(vec![
+            ("wrapper_version", Json::string(wrapper::VERSION)),
+            (
+                "real_exe",
+                Json::string(self.real_exe.display().to_string()),
+            ),
+            ("args", Json::strings(&lossy(self.args))),
+            (
+                "injected",
+                Json::strings(&lossy(self.injected.iter().copied())),
+            ),
+            ("started", Json::string(log::utc_timestamp(self.started))),
+            ("finished", Json::string(log::utc_timestamp(self.finished))),
+            ("exit_code", self.exit_code.into()),
+            ("output", output.map(|p| p.display().to_string()).into()),
+            ("output_size", size.into()),
+            ("output_sha256", hash.into()),
+        ])
+    }
+
+    /// Writes the report to `path`, replacing any earlier one.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, format!("{}\n", self.to_json()))
+    }
+}
+
+fn lossy<'a>(args: impl IntoIterator<Item = &'a OsString>) -> Vec<String> {
+    args.into_iter()
+        .map(|a| a.to_string_lossy().into_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+    use std::time::Duration;
+
+    fn os_strings(items: &[&str]) -> Vec<OsString> {
+        items.iter().map(OsString::from).collect()
+    }
+
+    fn report<'a>(args: &'a [OsString], exit_code: Option<i32>) -> InvocationReport<'a> {
+        InvocationReport {
+            real_exe: Path::new("light-real.exe"),
+            args,
+            injected: vec![&args[0]],
+            started: SystemTime::UNIX_EPOCH,
+            finished: SystemTime::UNIX_EPOCH + Duration::from_secs(61),
+            exit_code,
+        }
+    }
+
+    #[test]
+    fn finds_the_output_path() {
+        let args = os_strings(&["-sval", "a.wixobj", "-out", "bin\\app.msi"]);
+        assert_eq!(
+            report(&args, Some(0)).output_path(),
+            Some(PathBuf::from("bin\\app.msi"))
+        );
+        let args = os_strings(&["-sval", "/OUT:app.msi"]);
+        assert_eq!(
+            report(&args, Some(0)).output_path(),
+            Some(PathBuf::from("app.msi"))
+        );
+        let args = os_strings(&["-sval", "-outdir", "bin"]);
+        assert_eq!(report(&args, Some(0)).output_path(), None);
+        let args = os_strings(&["-sval", "-out"]);
+        assert_eq!(report(&args, Some(0)).output_path(), None);
+    }
+
+    #[test]
+    fn fingerprints_the_output_of_a_successful_link() {
+        let dir = TempDir::new("report");
+        let msi = dir.join("app.msi");
+        fs::write(&msi, b"abc").unwrap();
+        let args = os_strings(&["-sval", "-out", msi.to_str().unwrap()]);
+        let json = report(&args, Some(0)).to_json().to_string();
+        assert!(
+            json.starts_with(&format!(
+                r#"{{"wrapper_version":"{}","real_exe":"light-real.exe","args":["-sval","-out","#,
+                wrapper::VERSION
+            )),
+            "{json}"
+        );
+        assert!(
+            json.contains(r#""injected":["-sval"],"started":"1970-01-01T00:00:00.000Z","finished":"1970-01-01T00:01:01.000Z","exit_code":0"#),
+            "{json}"
+        );
+        assert!(
+            json.ends_with(r#","output_size":3,"output_sha256":"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"}"#),
+            "{json}"
+        );
+    }
+
+    #[test]
+    fn failed_link_reports_no_output_size() {
+        let dir = TempDir::new("report-failed");
+        let msi = dir.join("app.msi");
+        fs::write(&msi, b"stale").unwrap();
+        let args = os_strings(&["-sval", "-out", msi.to_str().unwrap()]);
+        for exit_code in [Some(1), None] {
+            let json = report(&args, exit_code).to_json().to_string();
+            assert!(
+                json.ends_with(r#","output_size":null,"output_sha256":null}"#),
+                "{json}"
+            );
+        }
+        let args = os_strings(&["-sval", "a.wixobj"]);
+        let json = report(&args, Some(0)).to_json().to_string();
+        assert!(
+            json.ends_with(r#""output":null,"output_size":null,"output_sha256":null}"#),
+            "{json}"
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! WiX response files: an `@path` argument stands for the arguments listed in that file.
+//!
+//! The wrapper reads them to see which flags the caller already passed; the `@path` argument
+//! itself is forwarded unchanged and light expands it as usual. Only when the final command line
+//! would be too long for Windows does the wrapper write a response file of its own.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Command lines longer than this (in characters) are passed through a temporary response
+/// file instead, safely below the 32 767 characters `CreateProcess` accepts.
+pub const MAX_COMMAND_LINE: usize = 30_000;
+
+/// `args` with each readable `@path` argument replaced by the tokens in that file. An
+/// argument whose file can't be read (or isn't valid Unicode) is kept as-is, leaving the error
+/// to the real tool.
+pub fn expand(args: &[OsString]) -> Vec<OsString> {
+    let mut expanded = Vec::with_capacity(args.len());
+    for arg in args {
+        let path = arg.to_str().and_then(|arg| arg.strip_prefix('@'));
+        match path.and_then(|path| read(Path::new(path))) {
+            Some(tokens) => expanded.extend(tokens.into_iter().map(OsString::from)),
+            None => expanded.push(arg.clone()),
+        }
+    }
+    expanded
+}
+
+fn read(path: &Path) -> Option<Vec<String>> {
+    let bytes = fs::read(path).ok()?;
+    let text = String::from_utf8_lossy(&bytes);
+    Some(tokenize(text.trim_start_matches('\u{feff}')))
+}
+
+/// Splits response-file text into arguments: whitespace (including CR/LF line breaks)
+/// separates them, and `"` starts or ends a quoted section so paths may contain spaces.
+/// Quotes are removed and backslashes are always literal, so `"C:\out dir\"` is `C:\out dir\`.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_token = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+    if in_token {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// A response file written by the wrapper, deleted again when dropped.
+#[derive(Debug)]
+pub struct TempResponseFile {
+    path: PathBuf,
+}
+
+/// Numbers the wrapper's response files within the process.
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+/// How many taken names `TempResponseFile::write` skips before giving up.
+const MAX_ATTEMPTS: usize = 100;
+
+fn temp_path(n: usize) -> PathBuf {
+    std::env::temp_dir().join(format!("light-wrapper-{}-{n}.rsp", std::process::id()))
+}
+
+impl TempResponseFile {
+    /// Writes `args` one per line into a new file in the temp directory. Any `@file` arguments
+    /// are expanded first, since light does not follow response files named inside another.
+    /// The file is UTF-8, so an argument that isn't valid Unicode is written lossily.
+    ///
+    /// The temp directory is shared, so the file is always created afresh (on Unix readable by
+    /// the current user only) and a name that is already taken, even by a dangling link, is
+    /// skipped rather than written through.
+    pub fn write(args: &[OsString]) -> io::Result<TempResponseFile> {
+        let mut text = String::new();
+        for arg in expand(args) {
+            text.push_str(&quote(&arg.to_string_lossy()));
+            text.push_str("\r\n");
+        }
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        for _ in 0..MAX_ATTEMPTS {
+            let path = temp_path(NEXT.fetch_add(1, Ordering::Relaxed));
+            let mut file = match options.open(&path) {
+                Ok(file) => file,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            };
+            // Removed again on failure by the drop.
+            let written = TempResponseFile { path };
+            file.write_all(text.as_bytes())?;
+            return Ok(written);
+        }
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "no free response file name in {}",
+                std::env::temp_dir().display()
+            ),
+        ))
+    }
+
+    /// The `@path` argument that stands for the file's contents.
+    pub fn argument(&self) -> String {
+        format!("@{}", self.path.display())
+    }
+}
+
+impl Drop for TempResponseFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Quotes an argument for a response file when it is empty or contains whitespace.
+fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.chars().any(char::is_whitespace) {
+        return arg.to_string();
+    }
+    format!("\"{arg}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn strings(items: &[&str]) -> Vec<OsString> {
+        items.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn tokenizes_quoted_arguments_across_lines() {
+        assert_eq!(
+            tokenize("-sval\r\n-out \"C:\\My Build\\app.msi\"\r\n  -dName=\"a b\" \"\"\r\n"),
+            ["-sval", "-out", "C:\\My Build\\app.msi", "-dName=a b", ""]
+        );
+        assert_eq!(tokenize("\"C:\\out dir\\\" x"), ["C:\\out dir\\", "x"]);
+        assert!(tokenize(" \r\n\t").is_empty());
+    }
+
+    #[test]
+    fn expands_response_files_in_place() {
+        let dir = TempDir::new("rsp");
+        let rsp = dir.join("light.rsp");
+        fs::write(&rsp, "\u{feff}-SVAL\r\n\"main file.wixobj\"\r\n").unwrap();
+        let args = strings(&["-nologo", &format!("@{}", rsp.display()), "-spdb"]);
+        assert_eq!(
+            expand(&args),
+            ["-nologo", "-SVAL", "main file.wixobj", "-spdb"]
+        );
+    }
+
+    #[test]
+    fn temp_response_file_round_trips_and_is_removed() {
+        let dir = TempDir::new("rsp-temp");
+        let inner = dir.join("inner.rsp");
+        fs::write(&inner, "-spdb \"b c.wixobj\"").unwrap();
+        let args = strings(&[
+            "-sval",
+            "-out",
+            "C:\\My Build\\app.msi",
+            &format!("@{}", inner.display()),
+            "",
+        ]);
+        let file = TempResponseFile::write(&args).unwrap();
+        let argument = file.argument();
+        let path = PathBuf::from(argument.strip_prefix('@').unwrap());
+        assert_eq!(
+            expand(&[argument.into()]),
+            [
+                "-sval",
+                "-out",
+                "C:\\My Build\\app.msi",
+                "-spdb",
+                "b c.wixobj",
+                ""
+            ]
+        );
+        drop(file);
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn temp_response_file_never_writes_through_a_taken_name() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new("rsp-taken");
+        let victim = dir.join("victim");
+        fs::write(&victim, "untouched").unwrap();
+        let next = NEXT.load(Ordering::Relaxed);
+        let planted: Vec<PathBuf> = (next..next + 3).map(temp_path).collect();
+        for path in &planted {
+            std::os::unix::fs::symlink(&victim, path).unwrap();
+        }
+        let file = TempResponseFile::write(&strings(&["-sval"])).unwrap();
+        let path = PathBuf::from(file.argument().strip_prefix('@').unwrap());
+        for path in &planted {
+            fs::remove_file(path).unwrap();
+        }
+        assert!(!planted.contains(&path));
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "untouched");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "-sval\r\n");
+        let mode = fs::symlink_metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn missing_response_file_is_left_alone() {
+        let dir = TempDir::new("rsp-missing");
+        let args = strings(&["-nologo", "@"]);
+        assert_eq!(expand(&args), args);
+        let args = strings(&[&format!("@{}", dir.join("missing.rsp").display())]);
+        assert_eq!(expand(&args), args);
+    }
+}
@@ -0,0 +1,112 @@
+//! `LIGHT_WRAPPER_SIGNTOOL_ARGS`: Authenticode-signs the MSI once light has produced it, so
+//! nothing downstream ever sees an unsigned installer.
+
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Words that mark an option (or `name=value` setting) as carrying a secret.
+const SECRET_WORDS: [&str; 4] = ["pass", "pwd", "secret", "token"];
+
+/// `signtool sign <sign_args> <msi>`. Output goes where the wrapper's own does.
+pub fn command(signtool: &Path, sign_args: &[String], msi: &Path) -> Command {
+    let mut command = Command::new(signtool);
+    command
+        .arg("sign")
+        .args(sign_args)
+        .arg(msi)
+        .stdin(Stdio::null());
+    command
+}
+
+/// `args` with anything that looks like a password replaced by `***`, for logging: the value
+/// after signtool's `/p` or an option such as `--password`, and the value part of
+/// `password=...` or `/token:...`.
+pub fn redacted(args: &[OsString]) -> Vec<OsString> {
+    let mut redacted = Vec::with_capacity(args.len());
+    let mut hide_next = false;
+    for arg in args {
+        let text = arg.to_string_lossy();
+        if std::mem::take(&mut hide_next) {
+            redacted.push(OsString::from("***"));
+            continue;
+        }
+        let name = text.trim_start_matches(['-', '/']);
+        if let Some(at) = name.find([':', '=']).filter(|&at| is_secret(&name[..at])) {
+            let cut = text.len() - name.len() + at + 1;
+            redacted.push(format!("{}***", &text[..cut]).into());
+            continue;
+        }
+        let is_option = name.len() < text.len();
+        hide_next = is_option && (name.eq_ignore_ascii_case("p") || is_secret(name));
+        redacted.push(arg.clone());
+    }
+    redacted
+}
+
+fn is_secret(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SECRET_WORDS.iter().any(|word| name.contains(word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn os_strings(items: &[&str]) -> Vec<OsString> {
+        items.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn passwords_are_redacted() {
+        let args = os_strings(&[
+            "sign",
+            "/fd",
+            "sha256",
+            "/f",
+            "cert.pfx",
+            "/P",
+            "hunter2",
+            "--key-password",
+            "s3cret",
+            "pfx_pass=hunter2",
+            "/token:abc",
+            "/tr",
+            "http://timestamp.digicert.com",
+            "C:\\My Apps\\app.msi",
+        ]);
+        assert_eq!(
+            redacted(&args),
+            os_strings(&[
+                "sign",
+                "/fd",
+                "sha256",
+                "/f",
+                "cert.pfx",
+                "/P",
+                "***",
+                "--key-password",
+                "***",
+                "pfx_pass=***",
+                "/token:***",
+                "/tr",
+                "http://timestamp.digicert.com",
+                "C:\\My Apps\\app.msi",
+            ])
+        );
+    }
+
+    #[test]
+    fn command_signs_the_msi_last() {
+        let command = command(
+            Path::new("signtool.exe"),
+            &["/a".to_string(), "/fd".to_string(), "sha256".to_string()],
+            Path::new("C:\\My Apps\\app.msi"),
+        );
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(
+            args,
+            ["sign", "/a", "/fd", "sha256", "C:\\My Apps\\app.msi"]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Helpers shared by the tests that exercise the spawn path.
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Scratch directory removed again when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(label: &str) -> TempDir {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "light-wrapper-{label}-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(unix)]
+pub fn make_executable(path: &std::path::Path) {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// Stand-in for the real linker: a shell script that records the argv of every run, one
+/// argument per line followed by an end marker, and the directory it ran in, and then runs a
+/// configurable script tail.
+#[cfg(unix)]
+pub struct Recorder {
+    record: PathBuf,
+}
+
+#[cfg(unix)]
+const END_OF_RUN: &str = "--end-of-run--";
+
+/// What WiX v3's light prints first for `-?`.
+#[cfg(unix)]
+pub const V3_BANNER: &str = "Windows Installer XML Toolset Linker version 3.11.2.4516";
+
+#[cfg(unix)]
+impl Recorder {
+    /// A stub that always exits with `exit_code`.
+    pub fn install(dir: &TempDir, name: &str, exit_code: i32) -> Recorder {
+        Recorder::with_script(dir, name, &format!("exit {exit_code}"))
+    }
+
+    /// A stub that runs the shell snippet `tail` after recording its arguments. It answers the
+    /// wrapper's `-?` version probe as WiX v3 without recording that run.
+    pub fn with_script(dir: &TempDir, name: &str, tail: &str) -> Recorder {
+        Recorder::with_banner(dir, name, V3_BANNER, tail)
+    }
+
+    /// A stub that prints `banner` for the `-?` version probe, otherwise as `with_script`.
+    pub fn with_banner(dir: &TempDir, name: &str, banner: &str, tail: &str) -> Recorder {
+        let record = dir.join(&format!("{name}.args"));
+        let script = dir.join(name);
+        fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\nif [ \"$#\" = 1 ] && [ \"$1\" = '-?' ]; then printf '%s\\n' '{banner}'; exit 0; fi\nfor arg in \"$@\"; do printf '%s\\n' \"$arg\" >> '{record}'; done\necho '{END_OF_RUN}' >> '{record}'\npwd -P > '{cwd}'\n{tail}\n",
+                record = record.display(),
+                cwd = record.with_extension("cwd").display()
+            ),
+        )
+        .unwrap();
+        make_executable(&script);
+        Recorder { record }
+    }
+
+    pub fn was_run(&self) -> bool {
+        self.record.exists()
+    }
+
+    /// Arguments of every run so far, oldest first, byte for byte.
+    pub fn runs(&self) -> Vec<Vec<OsString>> {
+        use std::os::unix::ffi::OsStringExt;
+        let bytes = fs::read(&self.record).unwrap_or_else(|e| panic!("stub was not run ({e})"));
+        let mut runs = vec![Vec::new()];
+        let bytes = bytes.strip_suffix(b"\n").unwrap_or(&bytes);
+        for line in bytes.split(|&b| b == b'\n') {
+            if line == END_OF_RUN.as_bytes() {
+                runs.push(Vec::new());
+            } else {
+                runs.last_mut()
+                    .unwrap()
+                    .push(OsString::from_vec(line.to_vec()));
+            }
+        }
+        runs.pop();
+        runs
+    }
+
+    /// The working directory of the stub's last run.
+    pub fn cwd(&self) -> PathBuf {
+        let cwd = fs::read_to_string(self.record.with_extension("cwd")).unwrap();
+        PathBuf::from(cwd.trim_end_matches('\n'))
+    }
+
+    /// Arguments the stub received on its last run.
+    pub fn args(&self) -> Vec<OsString> {
+        self.runs().pop().unwrap()
+    }
+}
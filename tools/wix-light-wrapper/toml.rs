@@ -0,0 +1,337 @@
+//! Just enough TOML for the wrapper's config file: `[tables]`, `key = value` pairs with
+//! basic/literal strings, integers, booleans and (possibly multi-line) arrays, plus comments.
+//! Keys inside a table are returned with a dotted `table.key` name.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Integer(_) => "integer",
+            Value::Boolean(_) => "boolean",
+            Value::Array(_) => "array",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Parses `text` into its `(key, value, line)` entries in document order.
+pub fn parse(text: &str) -> Result<Vec<(String, Value, usize)>, ParseError> {
+    let mut parser = Parser {
+        chars: text.trim_start_matches('\u{feff}').chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    let mut table = String::new();
+    let mut entries: Vec<(String, Value, usize)> = Vec::new();
+
+    loop {
+        parser.skip_whitespace_and_comments(true);
+        let Some(c) = parser.peek() else { break };
+        let line = parser.line;
+        if c == '[' {
+            parser.pos += 1;
+            parser.skip_inline_whitespace();
+            table = parser.key()?;
+            parser.skip_inline_whitespace();
+            parser.expect(']')?;
+        } else {
+            let key = parser.key()?;
+            let key = if table.is_empty() {
+                key
+            } else {
+                format!("{table}.{key}")
+            };
+            parser.skip_inline_whitespace();
+            parser.expect('=')?;
+            parser.skip_inline_whitespace();
+            let value = parser.value()?;
+            if entries.iter().any(|(k, _, _)| *k == key) {
+                return Err(parser.error_at(line, format!("duplicate key '{key}'")));
+            }
+            entries.push((key, value, line));
+        }
+        parser.end_of_line()?;
+    }
+
+    Ok(entries)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn error_at(&self, line: usize, message: String) -> ParseError {
+        ParseError { line, message }
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        self.error_at(self.line, message.into())
+    }
+
+    fn expect(&mut self, want: char) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(c) if c == want => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("expected '{want}', found '{c}'"))),
+            None => Err(self.error(format!("expected '{want}', found end of file"))),
+        }
+    }
+
+    fn skip_inline_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.bump();
+            }
+        }
+    }
+
+    fn skip_whitespace_and_comments(&mut self, newlines: bool) {
+        loop {
+            self.skip_inline_whitespace();
+            self.skip_comment();
+            match self.peek() {
+                Some('\r' | '\n') if newlines => {
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), ParseError> {
+        self.skip_inline_whitespace();
+        self.skip_comment();
+        match self.peek() {
+            None | Some('\n') => Ok(()),
+            Some('\r') if self.chars.get(self.pos + 1) == Some(&'\n') => Ok(()),
+            Some(c) => Err(self.error(format!("unexpected '{c}' after value"))),
+        }
+    }
+
+    fn key(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some('"') => self.basic_string(),
+            Some('\'') => self.literal_string(),
+            _ => {
+                let start = self.pos;
+                while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                {
+                    self.bump();
+                }
+                if start == self.pos {
+                    return Err(self.error("expected a key"));
+                }
+                Ok(self.chars[start..self.pos].iter().collect())
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        match self.peek() {
+            Some('"') => self.basic_string().map(Value::String),
+            Some('\'') => self.literal_string().map(Value::String),
+            Some('[') => self.array(),
+            Some('t' | 'f') => {
+                let word = self.word();
+                match word.as_str() {
+                    "true" => Ok(Value::Boolean(true)),
+                    "false" => Ok(Value::Boolean(false)),
+                    _ => Err(self.error(format!("invalid value '{word}'"))),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '+' => {
+                let word = self.word();
+                word.replace('_', "")
+                    .parse::<i64>()
+                    .map(Value::Integer)
+                    .map_err(|_| self.error(format!("invalid integer '{word}'")))
+            }
+            Some(c) => Err(self.error(format!("invalid value starting with '{c}'"))),
+            None => Err(self.error("expected a value, found end of file")),
+        }
+    }
+
+    fn word(&mut self) -> String {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '+')
+        {
+            self.bump();
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn array(&mut self) -> Result<Value, ParseError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_whitespace_and_comments(true);
+            if self.peek() == Some(']') {
+                self.bump();
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_whitespace_and_comments(true);
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                }
+                Some(']') => {}
+                Some(c) => return Err(self.error(format!("expected ',' or ']', found '{c}'"))),
+                None => return Err(self.error("unterminated array")),
+            }
+        }
+    }
+
+    /// Next character inside a single-line string.
+    fn string_char(&mut self) -> Result<char, ParseError> {
+        match self.peek() {
+            None | Some('\n') => Err(self.error("unterminated string")),
+            Some(c) => {
+                self.pos += 1;
+                Ok(c)
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, ParseError> {
+        self.expect('\'')?;
+        let mut out = String::new();
+        loop {
+            match self.string_char()? {
+                '\'' => return Ok(out),
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, ParseError> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            match self.string_char()? {
+                '"' => return Ok(out),
+                '\\' => match self.bump() {
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('r') => out.push('\r'),
+                    Some('u') => {
+                        let hex: String = (0..4).filter_map(|_| self.bump()).collect();
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| self.error(format!("invalid escape '\\u{hex}'")))?;
+                        out.push(c);
+                    }
+                    Some(c) => return Err(self.error(format!("invalid escape '\\{c}'"))),
+                    None => return Err(self.error("unterminated string")),
+                },
+                c => out.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_scalars_arrays_and_tables() {
+        let text = "# comment\n\
+                    real_exe = 'C:\\WiX\\bin\\light.exe'\n\
+                    prepend_args = [\"-nologo\", # inline comment\n  \"-cc\", \"C:\\\\cab\",\n]\n\
+                    [limits]\n\
+                    timeout = 600\n\
+                    enabled = true\n";
+        let entries = parse(text).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    "real_exe".to_string(),
+                    Value::String("C:\\WiX\\bin\\light.exe".to_string()),
+                    2
+                ),
+                (
+                    "prepend_args".to_string(),
+                    Value::Array(vec![
+                        Value::String("-nologo".to_string()),
+                        Value::String("-cc".to_string()),
+                        Value::String("C:\\cab".to_string()),
+                    ]),
+                    3
+                ),
+                ("limits.timeout".to_string(), Value::Integer(600), 7),
+                ("limits.enabled".to_string(), Value::Boolean(true), 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_and_comment_only_documents_have_no_entries() {
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("\u{feff}\r\n# nothing here\r\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn reports_line_of_syntax_errors() {
+        let err = parse("a = 1\nb = \"open\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(err.message.contains("unterminated"));
+
+        let err = parse("a = 1\na = 2\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(err.message.contains("duplicate"));
+
+        assert!(parse("a = [1, 2\n").is_err());
+        assert!(parse("a = yes\n").is_err());
+        assert!(parse("a = 1 2\n").is_err());
+    }
+}
@@ -0,0 +1,301 @@
+//! Which WiX toolset the real tool belongs to, read from the file version in its PE version
+//! resource or, failing that, from the banner it prints for `-?`. WiX v4 and later
+//! (`wix.exe build`) reject the v3-style `-sval`/`-sacl`.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use crate::wait;
+use crate::wrapper::{self, Tool};
+
+/// `VS_FIXEDFILEINFO.dwSignature`, little-endian.
+const FIXED_FILE_INFO_SIGNATURE: [u8; 4] = [0xbd, 0x04, 0xef, 0xfe];
+
+/// How far past the `VS_VERSION_INFO` key the fixed info may start (header plus padding).
+const MAX_KEY_TO_INFO: usize = 8;
+
+/// How long the `-?` probe may take before the version is given up on.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A `major.minor.build.revision` toolset version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WixVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub build: u16,
+    pub revision: u16,
+}
+
+impl WixVersion {
+    /// What a real tool whose version can't be told is taken to be.
+    pub const V3: WixVersion = WixVersion {
+        major: 3,
+        minor: 0,
+        build: 0,
+        revision: 0,
+    };
+}
+
+impl fmt::Display for WixVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}.{}",
+            self.major, self.minor, self.build, self.revision
+        )
+    }
+}
+
+/// The toolset version of `real_exe`: its version resource if it has one, otherwise whatever
+/// `probe` (the tool run with `-?`) prints. `None` when neither tells.
+pub fn detect_wix_version(real_exe: &Path, probe: Command) -> Option<WixVersion> {
+    read_version_resource(real_exe).or_else(|| run_probe(probe).ok().flatten())
+}
+
+/// The file version of the executable at `path`, or `None` when it can't be read or carries
+/// no version resource (a script, or a build without one).
+pub fn read_version_resource(path: &Path) -> Option<WixVersion> {
+    file_version(&fs::read(path).ok()?)
+}
+
+/// Runs `probe` with its output captured and reads the version from what it printed.
+fn run_probe(mut probe: Command) -> io::Result<Option<WixVersion>> {
+    let mut child = probe
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let reader = thread::spawn(move || {
+        let mut text = String::new();
+        let _ = stdout.read_to_string(&mut text);
+        text
+    });
+    wait::wait(&mut child, Some(PROBE_TIMEOUT))?;
+    Ok(parse_banner(&reader.join().unwrap_or_default()))
+}
+
+/// The version in a tool's banner: `Windows Installer XML Toolset Linker version 3.11.2.4516`
+/// for v3, or a bare `5.0.2+aa65968` as `wix --version` prints it.
+pub fn parse_banner(text: &str) -> Option<WixVersion> {
+    text.lines().find_map(|line| {
+        let line = line.trim();
+        let lower = line.to_ascii_lowercase();
+        match lower.find("version ") {
+            Some(at) => parse_version(line[at + "version ".len()..].split_whitespace().next()?),
+            None => parse_version(line),
+        }
+    })
+}
+
+/// `3.11.2.4516`, `5.0.2+aa65968` or `4.0`: up to four dotted numbers, anything after a `+`
+/// or `-` ignored. At least a major and minor version are required.
+pub fn parse_version(text: &str) -> Option<WixVersion> {
+    let numbers = text.split(['+', '-']).next()?;
+    let parts: Vec<u16> = numbers
+        .split('.')
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    if parts.len() < 2 || parts.len() > 4 {
+        return None;
+    }
+    let part = |i: usize| parts.get(i).copied().unwrap_or(0);
+    Some(WixVersion {
+        major: part(0),
+        minor: part(1),
+        build: part(2),
+        revision: part(3),
+    })
+}
+
+/// The suppression switches `tool` accepts at `version`, in the form the wrapper injects them
+/// (see `profile::ToolProfile::v3_flags`). WiX v4 and later have none: `wix build` spells its
+/// switches differently.
+pub fn supported_flags(tool: Tool, version: WixVersion) -> &'static [&'static str] {
+    match version.major {
+        0..=3 => tool.profile().v3_flags,
+        _ => &[],
+    }
+}
+
+/// Whether `flag` (e.g. `-sval`, `-sice:ICE61`, `-sw1076`) is one `tool` accepts at `version`.
+pub fn supports(tool: Tool, version: WixVersion, flag: &str) -> bool {
+    supported_flags(tool, version).iter().any(|&supported| {
+        wrapper::flag_matches(flag, supported)
+            || (supported == "-sw"
+                && flag
+                    .get(..3)
+                    .is_some_and(|head| wrapper::flag_matches(head, "-sw"))
+                && flag.len() > 3
+                && flag[3..].bytes().all(|b| b.is_ascii_digit()))
+    })
+}
+
+/// Finds the `VS_FIXEDFILEINFO` block that follows the UTF-16 `VS_VERSION_INFO` key in `image`.
+pub fn file_version(image: &[u8]) -> Option<WixVersion> {
+    let key: Vec<u8> = "VS_VERSION_INFO\0"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    let mut rest = image;
+    while let Some(at) = find(rest, &key) {
+        rest = &rest[at + key.len()..];
+        let window = &rest[..rest.len().min(MAX_KEY_TO_INFO + 16)];
+        if let Some(info) = find(window, &FIXED_FILE_INFO_SIGNATURE) {
+            if window.len() < info + 16 {
+                continue;
+            }
+            let word = |offset: usize| {
+                let b = &window[info + offset..info + offset + 4];
+                u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+            };
+            let (ms, ls) = (word(8), word(12));
+            return Some(WixVersion {
+                major: (ms >> 16) as u16,
+                minor: ms as u16,
+                build: (ls >> 16) as u16,
+                revision: ls as u16,
+            });
+        }
+    }
+    None
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// A version resource as the resource compiler lays it out, for tests: the key, padding, and
+/// the start of `VS_FIXEDFILEINFO`.
+#[cfg(test)]
+pub fn version_resource(major: u16, minor: u16, build: u16, revision: u16) -> Vec<u8> {
+    let mut bytes = vec![0x34, 0x03, 0x34, 0x00, 0x00, 0x00];
+    bytes.extend(
+        "VS_VERSION_INFO\0"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes),
+    );
+    bytes.extend([0, 0]);
+    bytes.extend(FIXED_FILE_INFO_SIGNATURE);
+    bytes.extend(0x0001_0000u32.to_le_bytes());
+    bytes.extend(((u32::from(major) << 16) | u32::from(minor)).to_le_bytes());
+    bytes.extend(((u32::from(build) << 16) | u32::from(revision)).to_le_bytes());
+    bytes.extend([0; 36]);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_fixed_file_version() {
+        let mut image = b"MZ\x90\x00 not much of a PE image ".to_vec();
+        image.extend(version_resource(3, 11, 2, 4516));
+        image.extend(b"trailing sections");
+        let version = file_version(&image).unwrap();
+        assert_eq!(
+            version,
+            WixVersion {
+                major: 3,
+                minor: 11,
+                build: 2,
+                revision: 4516
+            }
+        );
+        assert_eq!(version.to_string(), "3.11.2.4516");
+    }
+
+    #[test]
+    fn skips_a_key_without_fixed_info() {
+        let mut image: Vec<u8> = "VS_VERSION_INFO\0 mentioned in a string table"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        image.extend(version_resource(5, 0, 2, 0));
+        assert_eq!(file_version(&image).unwrap().to_string(), "5.0.2.0");
+    }
+
+    #[test]
+    fn unversioned_files_have_no_version() {
+        assert_eq!(file_version(b"#!/bin/sh\nexit 0\n"), None);
+        let mut truncated = version_resource(4, 0, 0, 0);
+        truncated.truncate(truncated.len() - 40);
+        assert_eq!(file_version(&truncated), None);
+        assert_eq!(
+            read_version_resource(Path::new("/nonexistent/light-real.exe")),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_version_banners() {
+        let v3 = "Windows Installer XML Toolset Linker version 3.11.2.4516\r\n\
+                  Copyright (c) .NET Foundation and contributors. All rights reserved.\r\n";
+        assert_eq!(parse_banner(v3).unwrap().to_string(), "3.11.2.4516");
+        assert_eq!(
+            parse_banner("\n5.0.2+aa65968\n").unwrap().to_string(),
+            "5.0.2.0"
+        );
+        assert_eq!(
+            parse_banner("WiX Toolset version 4.0.1-preview.1\n")
+                .unwrap()
+                .major,
+            4
+        );
+        assert_eq!(
+            parse_banner("usage: light.exe [-?] [-b bindPath]\n5\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn v4_supports_none_of_the_v3_switches() {
+        let v3 = parse_version("3.11").unwrap();
+        let v4 = parse_version("4.0").unwrap();
+        for flag in ["-sval", "/SACL", "-sice:ICE61", "-sw", "-sw1076"] {
+            assert!(supports(Tool::LIGHT, v3, flag), "{flag}");
+            assert!(!supports(Tool::LIGHT, v4, flag), "{flag}");
+        }
+        assert!(supports(Tool::LIGHT, v3, "-ice:ICE03"));
+        assert!(!supports(Tool::LIGHT, v4, "-ice:ICE03"));
+        assert!(!supports(Tool::LIGHT, v3, "-swx"));
+        assert!(!supports(Tool::LIGHT, v3, "-nologo"));
+        assert!(supported_flags(Tool::LIGHT, v4).is_empty());
+
+        // Heat has suppression switches of its own.
+        let heat = Tool::from_exe(Path::new("heat.exe"));
+        assert!(supports(heat, v3, "-sreg"));
+        assert!(supports(heat, v3, "-sw5150"));
+        assert!(!supports(heat, v3, "-sval"));
+        assert!(!supports(Tool::LIGHT, v3, "-sreg"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reads_the_version_a_probe_prints() {
+        use crate::testutil::{make_executable, TempDir};
+
+        let dir = TempDir::new("toolset-probe");
+        let exe = dir.join("light-real.exe");
+        fs::write(
+            &exe,
+            "#!/bin/sh\necho 'WiX Toolset version 5.0.2+aa65968'\n",
+        )
+        .unwrap();
+        make_executable(&exe);
+        let detect = || detect_wix_version(&exe, Command::new(&exe));
+        assert_eq!(detect().unwrap().to_string(), "5.0.2.0");
+
+        fs::write(&exe, "#!/bin/sh\necho usage\n").unwrap();
+        assert_eq!(detect(), None);
+    }
+}
@@ -0,0 +1,120 @@
+//! What the real tool reported about ICE validation, so a build that links with `-sval` still
+//! records which checks it went without.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// The warning and error lines in a WiX log that name an `ICE` check or an `LGHT` code.
+#[derive(Debug, Default, PartialEq)]
+pub struct ValidationSummary {
+    pub warnings: usize,
+    pub errors: usize,
+    /// Distinct codes seen on those lines, e.g. `ICE03` and `LGHT0204`, sorted.
+    pub codes: BTreeSet<String>,
+}
+
+impl ValidationSummary {
+    pub fn is_empty(&self) -> bool {
+        self.warnings == 0 && self.errors == 0
+    }
+}
+
+/// `1 error, 2 warnings (ICE03, ICE61, LGHT0204)`.
+impl fmt::Display for ValidationSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        for (count, noun) in [(self.errors, "error"), (self.warnings, "warning")] {
+            match count {
+                0 => {}
+                1 => parts.push(format!("1 {noun}")),
+                n => parts.push(format!("{n} {noun}s")),
+            }
+        }
+        write!(f, "{}", parts.join(", "))?;
+        if !self.codes.is_empty() {
+            let codes: Vec<&str> = self.codes.iter().map(String::as_str).collect();
+            write!(f, " ({})", codes.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts the `error`/`warning` lines of `text` that carry an `ICEnn` or `LGHTnnnn` code and
+/// collects the distinct codes. Lines without a code, or that are neither, are ignored.
+pub fn summarize_output(text: &str) -> ValidationSummary {
+    let mut summary = ValidationSummary::default();
+    for line in text.lines() {
+        let upper = line.to_ascii_uppercase();
+        let codes = codes_in(&upper);
+        if codes.is_empty() {
+            continue;
+        }
+        if upper.contains("ERROR") {
+            summary.errors += 1;
+        } else if upper.contains("WARNING") {
+            summary.warnings += 1;
+        } else {
+            continue;
+        }
+        summary.codes.extend(codes);
+    }
+    summary
+}
+
+/// `ICE` followed by digits, or `LGHT` followed by exactly four, as whole words of `upper`.
+fn codes_in(upper: &str) -> Vec<String> {
+    upper
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| {
+            let digits = |rest: &str| !rest.is_empty() && rest.bytes().all(|b| b.is_ascii_digit());
+            match (word.strip_prefix("ICE"), word.strip_prefix("LGHT")) {
+                (Some(rest), _) => digits(rest),
+                (_, Some(rest)) => rest.len() == 4 && digits(rest),
+                _ => false,
+            }
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_LOG: &str = "\
+Windows Installer XML Toolset Linker version 3.11.2.4516
+light.exe : error LGHT0217 : Error executing ICE action 'ICE01'. The most common cause of this kind of ICE failure is an incorrectly registered scripting engine.
+light.exe : error LGHT0217 : Error executing ICE action 'ICE02'.
+C:\\build\\main.wxs(41) : warning LGHT1076 : ICE61: This product should remove only older versions of itself.
+light.exe : error LGHT0216 : An unexpected Win32 exception with error code 0x643 occurred: Action - 'ICE03'
+";
+
+    #[test]
+    fn summarizes_a_failed_validation_log() {
+        let summary = summarize_output(SAMPLE_LOG);
+        assert_eq!(summary.errors, 3);
+        assert_eq!(summary.warnings, 1);
+        assert_eq!(
+            summary.to_string(),
+            "3 errors, 1 warning (ICE01, ICE02, ICE03, ICE61, LGHT0216, LGHT0217, LGHT1076)"
+        );
+    }
+
+    #[test]
+    fn ignores_lines_without_codes() {
+        let summary = summarize_output(
+            "Linking app.msi\nwarning: nothing to do\nerror in step ICE\nICE61 mentioned in passing\n",
+        );
+        assert!(summary.is_empty(), "{summary:?}");
+        assert_eq!(summary, ValidationSummary::default());
+    }
+
+    #[test]
+    fn formats_singular_counts() {
+        let summary = summarize_output("light.exe : warning LGHT0217 : validation skipped\n");
+        assert_eq!(summary.to_string(), "1 warning (LGHT0217)");
+        let summary =
+            summarize_output("warning lght1076 : ice61: old versions\nwarning LGHT1076\n");
+        assert_eq!(summary.to_string(), "2 warnings (ICE61, LGHT1076)");
+    }
+}
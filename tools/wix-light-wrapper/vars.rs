@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+use std::env;
+
+use crate::wrapper;
+
+/// Snapshot of the environment variables the wrapper reads, so the whole run can be driven
+/// from tests without touching the real process environment.
+#[derive(Debug, Default, Clone)]
+pub struct Vars {
+    vars: HashMap<String, String>,
+}
+
+impl Vars {
+    pub fn from_process() -> Vars {
+        Vars {
+            vars: env::vars_os()
+                .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)))
+                .collect(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn from_pairs(pairs: &[(&str, &str)]) -> Vars {
+        Vars {
+            vars: pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    /// The variable's value; an empty value counts as unset. Names are case-insensitive on
+    /// Windows, where the environment has e.g. `Path` rather than `PATH`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        let value = match self.vars.get(name) {
+            Some(value) => Some(value),
+            None if cfg!(windows) => self
+                .vars
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value),
+            None => None,
+        };
+        value.map(String::as_str).filter(|v| !v.is_empty())
+    }
+
+    /// True when the variable is set to `1`.
+    pub fn enabled(&self, name: &str) -> bool {
+        self.get(name) == Some("1")
+    }
+
+    /// The variable's value split into arguments; empty when unset.
+    pub fn args(&self, name: &str) -> Vec<String> {
+        wrapper::split_args(self.get(name).unwrap_or_default())
+    }
+}
@@ -0,0 +1,94 @@
+//! Waiting for the real tool with an optional time limit.
+
+use std::io;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::interrupt;
+
+/// Exit code of a wrapper whose real tool was killed for running too long, as `timeout(1)` uses.
+pub const TIMED_OUT: i32 = 124;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Waits for `child` to exit. Without a `limit` this is a plain blocking `wait`; with one, a
+/// child still running once it has passed is killed (with its whole process tree on Windows)
+/// and an `io::ErrorKind::TimedOut` error is returned. Interrupts arriving meanwhile are passed
+/// on to the child (see `interrupt`).
+pub fn wait(child: &mut Child, limit: Option<Duration>) -> io::Result<ExitStatus> {
+    let _watched = interrupt::watch(child);
+    let Some(limit) = limit else {
+        return child.wait();
+    };
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        let elapsed = started.elapsed();
+        if elapsed >= limit {
+            kill_tree(child);
+            let _ = child.wait();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "still running after {:.1}s (limit {}s), killed it",
+                    elapsed.as_secs_f64(),
+                    limit.as_secs()
+                ),
+            ));
+        }
+        thread::sleep(POLL_INTERVAL.min(limit - elapsed));
+    }
+}
+
+/// Kills `child` and, on Windows, everything it started (light runs ICE validation and
+/// cabinet builds in child processes that would otherwise keep going).
+fn kill_tree(child: &mut Child) {
+    if cfg!(windows) {
+        let _ = Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &child.id().to_string()])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+    let _ = child.kill();
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn sh(script: &str) -> Child {
+        Command::new("/bin/sh")
+            .arg("-c")
+            .arg(script)
+            .spawn()
+            .unwrap()
+    }
+
+    #[test]
+    fn without_a_limit_waits_for_exit() {
+        let status = wait(&mut sh("sleep 0.1; exit 3"), None).unwrap();
+        assert_eq!(status.code(), Some(3));
+    }
+
+    #[test]
+    fn child_finishing_in_time_is_not_killed() {
+        let status = wait(&mut sh("exit 5"), Some(Duration::from_secs(10))).unwrap();
+        assert_eq!(status.code(), Some(5));
+    }
+
+    #[test]
+    fn hung_child_is_killed_at_the_limit() {
+        let started = Instant::now();
+        let mut child = sh("exec sleep 30");
+        let err = wait(&mut child, Some(Duration::from_millis(200))).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(err.to_string().contains("limit 0s"), "{err}");
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(child.try_wait().unwrap().is_some());
+    }
+}
@@ -0,0 +1,1966 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+
+use crate::profile::{self, ToolProfile};
+use crate::response_file;
+
+/// Embedded in every build of the wrapper, so tooling (and `--wrapper-install`) can tell a copy
+/// apart from a genuine WiX tool by searching the executable for it.
+pub const MARKER: &str = "wix-light-wrapper: this executable is the flag-injecting wrapper";
+
+/// The wrapper's own version, which `--wrapper-version` prints. The wrapper is built with plain
+/// `rustc`, so there is no package version to take it from; bump it here with each change to
+/// what gets injected.
+pub const VERSION: &str = "0.1.0";
+
+/// The tool a wrapper copy stands in for, picked from the name it was installed under and
+/// described by its `profile::ToolProfile`.
+///
+/// The same binary can be copied as `light.exe`, `candle.exe` or `heat.exe`; it then forwards
+/// to `light-real.exe`, `candle-real.exe` or `heat-real.exe` beside it. For candle, every
+/// `LIGHT` in an environment variable name reads `CANDLE` instead (`CANDLE_WRAPPER_VERBOSE`,
+/// `WIX_CANDLE_WRAPPER_SUPPRESS`), and the config file is `candle-wrapper.toml`; heat's are
+/// spelled `HEAT` and `heat-wrapper.toml`.
+///
+/// Copied under any other name, say `foo.exe`, it wraps `foo-real.exe` with the same
+/// flag-injection and passthrough behavior, minus the WiX specifics (no default flags, no
+/// validation retry, no version probe). Its variables are the light ones with the upper-cased
+/// name appended (`WIX_LIGHT_WRAPPER_SUPPRESS_FOO`, `LIGHT_WRAPPER_VERBOSE_FOO`), and its
+/// config file is `foo-wrapper.toml`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tool(&'static ToolProfile);
+
+impl Tool {
+    pub const LIGHT: Tool = Tool(&profile::LIGHT);
+
+    /// The tool in `profile::PROFILES` called `name` (lower-case), or a passthrough profile
+    /// for any other name.
+    pub fn named(name: &str) -> Tool {
+        match profile::PROFILES.iter().find(|p| p.name == name) {
+            Some(profile) => Tool(profile),
+            None => Tool(profile::passthrough(name)),
+        }
+    }
+
+    /// The tool named like the executable; `light` also for names starting with it (the
+    /// `light_wrapper` build output).
+    pub fn from_exe(current_exe: &Path) -> Tool {
+        let stem = current_exe
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        if stem.is_empty() || stem.starts_with("light") {
+            return Tool::LIGHT;
+        }
+        Tool::named(&stem)
+    }
+
+    pub fn profile(self) -> &'static ToolProfile {
+        self.0
+    }
+
+    pub fn name(self) -> &'static str {
+        self.0.name
+    }
+
+    /// Whether this is one of the WiX tools, with their flags and version resource.
+    pub fn is_wix(self) -> bool {
+        self.0.wix
+    }
+
+    /// What the real tool is, for messages: `WiX linker`.
+    fn role(self) -> &'static str {
+        self.0.role
+    }
+
+    /// The executable name the tool is installed under, e.g. `light.exe`.
+    pub fn exe_name(self) -> String {
+        format!("{}.exe", self.name())
+    }
+
+    pub fn real_exe_name(self) -> String {
+        self.0.real_name.to_string()
+    }
+
+    /// `name-wrapper.<extension>`, e.g. `light-wrapper.toml`, for files kept next to the wrapper.
+    pub fn wrapper_file_name(self, extension: &str) -> String {
+        format!("{}-wrapper.{extension}", self.name())
+    }
+
+    /// Suppression flags injected when the tool's `WIX_*_WRAPPER_SUPPRESS` is unset.
+    pub fn default_suppress(self) -> &'static str {
+        self.0.injected_flags
+    }
+
+    /// The tool's spelling of a `LIGHT`-based environment variable name.
+    pub fn var(self, light_name: &str) -> String {
+        match self.0.var_word {
+            Some(word) => light_name.replacen("LIGHT", word, 1),
+            None => {
+                let suffix: String = self
+                    .name()
+                    .chars()
+                    .map(|c| {
+                        if c.is_ascii_alphanumeric() {
+                            c.to_ascii_uppercase()
+                        } else {
+                            '_'
+                        }
+                    })
+                    .collect();
+                format!("{light_name}_{suffix}")
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WrapperError {
+    RealExeMissing(Tool, PathBuf),
+    InvokesItself(PathBuf),
+    /// The real tool found is itself a copy of the wrapper, which would spawn wrappers forever.
+    RealExeIsWrapper(Tool, PathBuf),
+    /// Nothing usable was found; every candidate looked at, with the reason it was skipped.
+    RealExeNotFound(Tool, Vec<(PathBuf, &'static str)>),
+}
+
+impl fmt::Display for WrapperError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WrapperError::RealExeMissing(tool, path) => write!(
+                f,
+                "expected real {} at '{}' but it does not exist",
+                tool.role(),
+                path.display()
+            ),
+            WrapperError::InvokesItself(path) => write!(
+                f,
+                "refusing to invoke itself: '{}' is the wrapper executable",
+                path.display()
+            ),
+            WrapperError::RealExeIsWrapper(tool, path) => write!(
+                f,
+                "refusing to run: '{}' is another copy of this wrapper, not the real {}; \
+                 put the original {} back under that name",
+                path.display(),
+                tool.role(),
+                tool.exe_name()
+            ),
+            WrapperError::RealExeNotFound(tool, rejected) => {
+                write!(f, "could not find the real {}; looked at:", tool.role())?;
+                for (path, reason) in rejected {
+                    write!(f, "\n  '{}' ({reason})", path.display())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Flags compare case-insensitively, the way WiX parses them.
+pub fn flag_eq(arg: &str, flag: &str) -> bool {
+    arg.eq_ignore_ascii_case(flag)
+}
+
+/// Whether the argument `candidate` is the flag `canonical` as WiX would read it: `/` and `-`
+/// introduce a flag alike and case doesn't matter. A `canonical` ending in `:` or `=` (such as
+/// `-cc:`) is a prefix matching any attached value; any other flag also matches with a value
+/// attached after `:` (`-out:app.msi` is `-out`). Non-flag arguments must match exactly.
+pub fn flag_matches(candidate: &str, canonical: &str) -> bool {
+    let (Some(candidate), Some(canonical)) = (switch_name(candidate), switch_name(canonical))
+    else {
+        return flag_eq(candidate, canonical);
+    };
+    let Some(head) = candidate.get(..canonical.len()) else {
+        return false;
+    };
+    if !head.eq_ignore_ascii_case(canonical) {
+        return false;
+    }
+    let rest = &candidate[canonical.len()..];
+    canonical.ends_with([':', '=']) || rest.is_empty() || rest.starts_with(':')
+}
+
+/// `arg` without its leading `-` or `/`, if it is a flag.
+fn switch_name(arg: &str) -> Option<&str> {
+    arg.strip_prefix('-')
+        .or_else(|| arg.strip_prefix('/'))
+        .filter(|name| !name.is_empty())
+}
+
+/// Whether `flag` was passed among `args`, in any of the spellings `flag_matches` accepts.
+/// Arguments that aren't valid Unicode are compared lossily; the flags are plain ASCII.
+pub fn has_flag(flag: &str, args: &[OsString]) -> bool {
+    args.iter()
+        .any(|a| flag_matches(&a.to_string_lossy(), flag))
+}
+
+/// Flags whose value is the next argument and which light rejects (or doubles up on) when the
+/// same pair is passed twice, e.g. LGHT0144 for `-ext WixUIExtension`.
+const PAIRED_FLAGS: [&str; 3] = ["ext", "loc", "b"];
+
+/// The positions of arguments that repeat an earlier one: an `-ext`/`-loc`/`-b` flag together
+/// with its value, or a `.wixobj` input (which would define every symbol twice). Comparison
+/// ignores case and the `-`/`/` spelling; the first occurrence is never reported. Arguments
+/// that aren't valid Unicode are never counted as duplicates.
+pub fn duplicate_args(args: &[OsString]) -> Vec<Range<usize>> {
+    let mut seen = HashSet::new();
+    let mut duplicates = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        let Some(lower) = args[i].to_str().map(str::to_ascii_lowercase) else {
+            i += 1;
+            continue;
+        };
+        let paired = switch_name(&lower).filter(|name| PAIRED_FLAGS.contains(name));
+        let (key, range) = match (paired, args.get(i + 1).and_then(|v| v.to_str())) {
+            (Some(name), Some(value)) => {
+                (format!("-{name} {}", value.to_ascii_lowercase()), i..i + 2)
+            }
+            _ if lower.ends_with(".wixobj") => (lower, i..i + 1),
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        i = range.end;
+        if !seen.insert(key) {
+            duplicates.push(range);
+        }
+    }
+    duplicates
+}
+
+/// Light's flags whose value is the next argument, as in `-loc en-us.wxl`.
+const VALUE_FLAGS: [&str; 11] = [
+    "b",
+    "builtoutputsfile",
+    "cc",
+    "contentsfile",
+    "ext",
+    "loc",
+    "o",
+    "out",
+    "outputsfile",
+    "pdbout",
+    "wixprojectfile",
+];
+
+/// Drops every argument that matches one of the `strip` patterns, returning what was removed.
+/// A pattern is a flag as `flag_matches` reads it (`-loc` also drops `/LOC` and `-loc:x`) or,
+/// ending in `*`, a prefix (`-cultures:*`, `-sw*`). A flag that takes the next argument as its
+/// value, like `-loc en-us.wxl`, is dropped together with it, so the value isn't left behind
+/// to be read as an input.
+pub fn strip_args(args: &mut Vec<OsString>, strip: &[String]) -> Vec<OsString> {
+    let mut kept = Vec::with_capacity(args.len());
+    let mut removed = Vec::new();
+    let mut incoming = std::mem::take(args).into_iter();
+    while let Some(arg) = incoming.next() {
+        let Some(text) = arg
+            .to_str()
+            .filter(|text| strip.iter().any(|p| strip_matches(text, p)))
+        else {
+            kept.push(arg);
+            continue;
+        };
+        let takes_value = switch_name(text).is_some_and(|name| {
+            VALUE_FLAGS
+                .iter()
+                .any(|flag| flag.eq_ignore_ascii_case(name))
+        });
+        removed.push(arg);
+        if takes_value {
+            removed.extend(incoming.next());
+        }
+    }
+    *args = kept;
+    removed
+}
+
+fn strip_matches(arg: &str, pattern: &str) -> bool {
+    let Some(prefix) = pattern.strip_suffix('*') else {
+        return flag_matches(arg, pattern);
+    };
+    let (arg, prefix) = match (switch_name(arg), switch_name(prefix)) {
+        (Some(arg), Some(prefix)) => (arg, prefix),
+        _ => (arg, prefix),
+    };
+    arg.get(..prefix.len())
+        .is_some_and(|head| head.eq_ignore_ascii_case(prefix))
+}
+
+/// The output file light is told to write, from `-out app.msi`, `-o app.msi` or the attached
+/// `-out:app.msi`, also when given inside an `@file` response file. Quotes around the path are
+/// removed, so `-out:"C:\My Apps\app.msi"` names `C:\My Apps\app.msi`.
+pub fn output_path(args: &[OsString]) -> Option<PathBuf> {
+    path_value(args, &["-out", "-o"])
+}
+
+/// The cabinet cache light is told to keep, from `-cc <dir>` or `-cc:<dir>`, read as
+/// `output_path` reads `-out`.
+pub fn cab_cache_path(args: &[OsString]) -> Option<PathBuf> {
+    path_value(args, &["-cc"])
+}
+
+/// The path value of the first of `flags` among `args`, separate or attached.
+fn path_value(args: &[OsString], flags: &[&str]) -> Option<PathBuf> {
+    let expanded = response_file::expand(args);
+    let mut args = expanded.iter().map(|a| a.to_string_lossy());
+    while let Some(arg) = args.next() {
+        if !flags.iter().any(|flag| flag_matches(&arg, flag)) {
+            continue;
+        }
+        let path = match arg.split_once(':') {
+            Some((_, path)) => path.to_string(),
+            None => args.next()?.into_owned(),
+        };
+        let path = path
+            .strip_prefix('"')
+            .and_then(|p| p.strip_suffix('"'))
+            .unwrap_or(&path);
+        return (!path.is_empty()).then(|| PathBuf::from(path));
+    }
+    None
+}
+
+/// What `normalize_suppressions` changed in the arguments, or found worth a warning.
+#[derive(Debug, Default, PartialEq)]
+pub struct Suppressions {
+    /// Repeated `-sval`/`-sacl` copies that were dropped.
+    pub dropped: Vec<OsString>,
+    /// `-sice:` flags left in place although a `-sval` already skips all of validation.
+    pub redundant: Vec<OsString>,
+}
+
+/// Collapses repeated `-sval` and `-sacl` flags to their first occurrence, in any spelling
+/// `flag_matches` accepts, and finds the `-sice:` flags a `-sval` makes redundant. Nothing
+/// else is removed or moved.
+pub fn normalize_suppressions(args: &mut Vec<OsString>) -> Suppressions {
+    let mut found = Suppressions::default();
+    let mut seen = [false; 2];
+    args.retain(|arg| {
+        let arg_str = arg.to_string_lossy();
+        let Some(i) = ["-sval", "-sacl"]
+            .iter()
+            .position(|flag| flag_matches(&arg_str, flag))
+        else {
+            return true;
+        };
+        if std::mem::replace(&mut seen[i], true) {
+            found.dropped.push(arg.clone());
+            return false;
+        }
+        true
+    });
+    if seen[0] {
+        found.redundant = args
+            .iter()
+            .filter(|arg| flag_matches(&arg.to_string_lossy(), "-sice:"))
+            .cloned()
+            .collect();
+    }
+    found
+}
+
+/// Expands a comma-separated suppression list such as `sval,sacl,ice:ICE03` into the flags
+/// `-sval -sacl -ice:ICE03`. Blank entries are ignored and a leading `-` is accepted.
+pub fn suppress_flags(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| format!("-{}", entry.trim_start_matches('-')))
+        .collect()
+}
+
+/// True when light's output shows ICE validation failing: `LGHT0217` (validation could not
+/// run at all) or an error line naming an ICE check, e.g. `error LGHT0204 : ICE03: ...`.
+pub fn is_validation_failure(output: &str) -> bool {
+    output.lines().any(|line| {
+        let upper = line.to_ascii_uppercase();
+        upper.contains("LGHT0217") || (upper.contains("ERROR") && mentions_ice(&upper))
+    })
+}
+
+/// Looks for an `ICE` identifier followed by digits, e.g. `ICE03` or `ICE61`.
+fn mentions_ice(upper: &str) -> bool {
+    upper.match_indices("ICE").any(|(i, _)| {
+        upper[i + 3..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_digit())
+    })
+}
+
+/// True when light's output shows a file held open by another process, e.g. an antivirus
+/// scanner still reading the fresh `.msi`: Windows errors 32 (`being used by another process`)
+/// and 33 (`has locked a portion of the file`), as text or as their `0x8007002x` HRESULTs.
+pub fn is_sharing_violation(output: &str) -> bool {
+    let lower = output.to_ascii_lowercase();
+    [
+        "being used by another process",
+        "has locked a portion of the file",
+        "0x80070020",
+        "0x80070021",
+    ]
+    .iter()
+    .any(|pattern| lower.contains(pattern))
+}
+
+/// One `-sice:<ID>` flag per ICE identifier, which makes light skip just those checks while
+/// the rest of validation still runs. Identifiers are trimmed and upper-cased; blanks are ignored.
+pub fn sice_flags<S: AsRef<str>>(ices: &[S]) -> Vec<String> {
+    ices.iter()
+        .map(|ice| ice.as_ref().trim().to_ascii_uppercase())
+        .filter(|ice| !ice.is_empty())
+        .map(|ice| format!("-sice:{ice}"))
+        .collect()
+}
+
+/// Splits an environment-variable value into arguments.
+///
+/// - Unquoted whitespace (spaces, tabs, newlines) separates tokens; leading, trailing and
+///   repeated whitespace produces no empty tokens.
+/// - A `"` starts or ends a quoted section anywhere in a token, so `-dFoo="a b"` is the single
+///   token `-dFoo=a b`. The quotes themselves are removed; `""` yields an empty token.
+/// - `\"` is a literal quote. Every other backslash is kept as-is, so Windows paths need no
+///   escaping.
+pub fn split_args(value: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut quoted = false;
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'"') => {
+                chars.next();
+                current.push('"');
+                in_token = true;
+            }
+            '"' => {
+                quoted = !quoted;
+                in_token = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+    if in_token {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Renders the executable and each argument on its own indented line as a quoted, escaped
+/// string literal, so every token (including empty and whitespace-only ones) is unambiguous.
+/// Non-Unicode arguments show as their `OsStr` debug form.
+pub fn display_tokens(exe: &Path, args: &[OsString]) -> String {
+    let mut out = format!("  {:?}\n", exe.display().to_string());
+    for arg in args {
+        out.push_str(&format!("  {arg:?}\n"));
+    }
+    out
+}
+
+/// Renders a command line for diagnostics, quoting arguments that are empty or contain
+/// whitespace or quotes so the output can be pasted back into a shell. Arguments that aren't
+/// valid Unicode are shown lossily.
+pub fn display_command(exe: &Path, args: &[OsString]) -> String {
+    let mut line = quote_arg(&exe.display().to_string());
+    for arg in args {
+        line.push(' ');
+        line.push_str(&quote_arg(&arg.to_string_lossy()));
+    }
+    line
+}
+
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.chars().any(|c| c.is_whitespace() || c == '"') {
+        return arg.to_string();
+    }
+    format!("\"{}\"", arg.replace('"', "\\\""))
+}
+
+/// Builds the forwarded argument list: the incoming arguments unchanged, with every flag in
+/// `prepend` that the caller did not already pass (in order) inserted just before the first
+/// `.wixobj`/`.wixlib` input, and any missing `append` flags after them all. Options ahead of
+/// the inputs, such as `-out app.msi`, keep their place; with no recognizable input the flags
+/// go in front. Flags inside `@file` response files count as passed, but nothing is ever
+/// written into them. The incoming arguments are forwarded as the OS handed them over, valid
+/// Unicode or not.
+///
+/// `opaque` arguments (see `split_opaque`) follow `incoming`, ahead of the appended flags, and
+/// are never looked at: a `-sval.wixobj` among them neither counts as a passed `-sval` nor as
+/// an input to insert before.
+///
+/// `extra` (the configured passthrough flags) is forwarded exactly as given right after the
+/// inserted `prepend` flags: none of it is left out for being passed already, and a `prepend`
+/// flag among it is not inserted a second time.
+pub fn build_args(
+    incoming: &[OsString],
+    opaque: &[OsString],
+    prepend: &[&str],
+    extra: &[OsString],
+    append: &[&str],
+) -> Vec<OsString> {
+    let passed = response_file::expand(incoming);
+    let at = first_input(incoming).unwrap_or(0);
+    let mut args: Vec<OsString> = Vec::with_capacity(
+        incoming.len() + opaque.len() + prepend.len() + extra.len() + append.len(),
+    );
+    args.extend_from_slice(&incoming[..at]);
+    for flag in prepend {
+        if !has_flag(flag, &passed) && !has_flag(flag, &args) && !has_flag(flag, extra) {
+            args.push(flag.into());
+        }
+    }
+    args.extend_from_slice(extra);
+    args.extend_from_slice(&incoming[at..]);
+    let mut appended: Vec<OsString> = Vec::new();
+    for flag in append {
+        if !has_flag(flag, &passed) && !has_flag(flag, &args) && !has_flag(flag, &appended) {
+            appended.push(flag.into());
+        }
+    }
+    args.extend_from_slice(opaque);
+    args.extend(appended);
+    args
+}
+
+/// Splits off the arguments the wrapper must treat as opaque: everything after a literal `--`
+/// (which is dropped) or, with `from` set, from that position on, whichever comes first.
+///
+/// Light has no end-of-options marker of its own and parses every argument starting with `-`
+/// or `/` as a switch, so `--` is only ever the wrapper's: it is removed, and what follows is
+/// forwarded as-is, still subject to light's own parsing. It keeps the wrapper's flag handling
+/// (injection, `LIGHT_WRAPPER_STRIP_ARGS`, de-duplication, path rewriting) from mistaking a
+/// generated name like `-sval-1a2b.wixobj` for a flag; light itself still reads it as one.
+pub fn split_opaque(args: &mut Vec<OsString>, from: Option<usize>) -> Vec<OsString> {
+    let marker = args.iter().position(|arg| arg == "--");
+    match (marker, from) {
+        (Some(marker), from) if from.is_none_or(|from| marker < from) => {
+            let opaque = args.split_off(marker + 1);
+            args.pop();
+            opaque
+        }
+        (_, Some(from)) => args.split_off(from.min(args.len())),
+        _ => Vec::new(),
+    }
+}
+
+/// The position of the first `.wixobj`/`.wixlib` input, counting an `@file` argument whose
+/// response file lists one as that input.
+fn first_input(incoming: &[OsString]) -> Option<usize> {
+    let is_input = |arg: &OsString| {
+        let arg = arg.to_string_lossy().to_ascii_lowercase();
+        !arg.starts_with(['-', '@']) && (arg.ends_with(".wixobj") || arg.ends_with(".wixlib"))
+    };
+    incoming.iter().position(|arg| {
+        is_input(arg)
+            || arg.to_str().is_some_and(|a| a.starts_with('@'))
+                && response_file::expand(std::slice::from_ref(arg))
+                    .iter()
+                    .any(is_input)
+    })
+}
+
+/// `current_exe` with a symbolic link followed, so a wrapper reached through one (a symlink
+/// farm, or a platform that reports the link's path) looks for the real tool, its config and
+/// its caches beside the file the link points to. Anything else, including a link that can't
+/// be followed, is returned as it is.
+pub fn followed_exe(current_exe: &Path) -> PathBuf {
+    let is_link = fs::symlink_metadata(current_exe).is_ok_and(|m| m.file_type().is_symlink());
+    if !is_link {
+        return current_exe.to_path_buf();
+    }
+    current_exe
+        .canonicalize()
+        .unwrap_or_else(|_| current_exe.to_path_buf())
+}
+
+/// Where `resolve_real_exe` looks when no explicit path is configured.
+#[derive(Debug, Default)]
+pub struct Search {
+    /// `WIX_LIGHT_WRAPPER_REAL`: tried first, but skipped (not an error) when it doesn't exist.
+    pub preferred: Option<PathBuf>,
+    /// `%WIX%\bin`, where the WiX installer puts the tools.
+    pub wix_bin: Option<PathBuf>,
+    /// The `PATH` entries, in order.
+    pub path: Vec<PathBuf>,
+}
+
+/// The real tool is `override_exe` when configured; an explicit path is used as given or
+/// rejected, never replaced by a search. Otherwise the first of these that exists and is not
+/// the running wrapper itself wins:
+///
+/// 1. `search.preferred`;
+/// 2. `<tool>-real.exe` next to the wrapper, or (except on Windows, e.g. a Wine cross-build)
+///    `<tool>-real` without the extension;
+/// 3. `<tool>-real.exe` in a `PATH` directory other than the wrapper's own (a wrapped toolset
+///    cached elsewhere);
+/// 4. `<tool>.exe` in `%WIX%\bin`;
+/// 5. `<tool>.exe` in a `PATH` directory.
+pub fn resolve_real_exe(
+    tool: Tool,
+    current_exe: &Path,
+    override_exe: Option<&Path>,
+    search: &Search,
+) -> Result<PathBuf, WrapperError> {
+    if let Some(real_exe) = override_exe {
+        if !real_exe.exists() {
+            return Err(WrapperError::RealExeMissing(tool, real_exe.to_path_buf()));
+        }
+        if is_same_file(real_exe, current_exe) {
+            return Err(WrapperError::InvokesItself(real_exe.to_path_buf()));
+        }
+        if is_wrapper_copy(real_exe, current_exe).unwrap_or(false) {
+            return Err(WrapperError::RealExeIsWrapper(tool, real_exe.to_path_buf()));
+        }
+        return Ok(real_exe.to_path_buf());
+    }
+
+    let own_dir = current_exe.parent().unwrap_or_else(|| Path::new("."));
+    let mut candidates: Vec<PathBuf> = search.preferred.iter().cloned().collect();
+    candidates.push(own_dir.join(tool.real_exe_name()));
+    if cfg!(not(windows)) {
+        candidates.push(own_dir.join(format!("{}-real", tool.name())));
+    }
+    candidates.extend(
+        search
+            .path
+            .iter()
+            .filter(|dir| !is_same_file(dir, own_dir))
+            .map(|dir| dir.join(tool.real_exe_name())),
+    );
+    candidates.extend(search.wix_bin.iter().map(|dir| dir.join(tool.exe_name())));
+    candidates.extend(search.path.iter().map(|dir| dir.join(tool.exe_name())));
+
+    let mut rejected = Vec::new();
+    for candidate in candidates {
+        if !candidate.is_file() {
+            rejected.push((candidate, "does not exist"));
+        } else if is_same_file(&candidate, current_exe) {
+            rejected.push((candidate, "is the wrapper executable"));
+        } else if is_wrapper_copy(&candidate, current_exe).unwrap_or(false) {
+            return Err(WrapperError::RealExeIsWrapper(tool, candidate));
+        } else {
+            return Ok(candidate);
+        }
+    }
+    Err(WrapperError::RealExeNotFound(tool, rejected))
+}
+
+/// The real tool found by `resolve_cached`, with the WiX version recorded for it by
+/// `record_version`: `3.11.2.4516`, or `unknown` when the probe told nothing. `None` until the
+/// version has been probed.
+#[derive(Debug, PartialEq)]
+pub struct Resolved {
+    pub real_exe: PathBuf,
+    pub version: Option<String>,
+}
+
+/// [`resolve_real_exe`], remembered in `cache` under `key` (see `resolution_key`) so the
+/// further links of one bundle skip the search. A remembered tool is only reused while it
+/// keeps its size and modification time and is no copy of `current_exe`; otherwise, or when
+/// the key changed, the search runs again. Failed searches aren't remembered, and a cache that
+/// can't be written is not kept.
+///
+/// The cache is usually in a temp directory other users can write to, so on Unix a record
+/// is only trusted when it is a plain file of the current user's that nobody else may write,
+/// and it is always written as a new file with no access for others.
+pub fn resolve_cached(
+    cache: &Path,
+    key: &str,
+    current_exe: &Path,
+    resolve: impl FnOnce() -> Result<PathBuf, WrapperError>,
+) -> Result<Resolved, WrapperError> {
+    let key = crate::sha256::hex(key.as_bytes());
+    if let Some(resolved) = read_record(cache, &key) {
+        if !is_wrapper_copy(&resolved.real_exe, current_exe).unwrap_or(true) {
+            return Ok(resolved);
+        }
+    }
+    let real_exe = resolve()?;
+    if let Some(stamp) = file_stamp(&real_exe) {
+        write_record(
+            cache,
+            &format!("{key}\t{stamp}\t\t{}\n", real_exe.display()),
+        );
+    }
+    Ok(Resolved {
+        real_exe,
+        version: None,
+    })
+}
+
+/// Adds the probed `version` to the record `resolve_cached` wrote for `real_exe`.
+pub fn record_version(cache: &Path, key: &str, real_exe: &Path, version: &str) {
+    let key = crate::sha256::hex(key.as_bytes());
+    if let Some(stamp) = file_stamp(real_exe) {
+        write_record(
+            cache,
+            &format!("{key}\t{stamp}\t{version}\t{}\n", real_exe.display()),
+        );
+    }
+}
+
+/// A `<key> <stamp> <version> <path>` record, tab-separated, when it is trusted (see
+/// `resolve_cached`), has `key` and still matches the file it names.
+fn read_record(cache: &Path, key: &str) -> Option<Resolved> {
+    if !is_private_file(cache) {
+        return None;
+    }
+    let text = fs::read_to_string(cache).ok()?;
+    let mut fields = text.trim_end_matches('\n').splitn(4, '\t');
+    let (cached_key, stamp, version, path) = (
+        fields.next()?,
+        fields.next()?,
+        fields.next()?,
+        fields.next()?,
+    );
+    let real_exe = PathBuf::from(path);
+    (cached_key == key && file_stamp(&real_exe).as_deref() == Some(stamp)).then(|| Resolved {
+        real_exe,
+        version: Some(version.to_string()).filter(|v| !v.is_empty()),
+    })
+}
+
+/// Replaces `cache` with a new file holding `record`. Removing it first means a file someone
+/// else left there is never written through, and one that can't be removed is left alone.
+fn write_record(cache: &Path, record: &str) {
+    use std::io::Write;
+    let _ = fs::remove_file(cache);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    if let Ok(mut file) = options.open(cache) {
+        let _ = file.write_all(record.as_bytes());
+    }
+}
+
+/// Whether `path` is a plain file (not a link) that, on Unix, belongs to the current user and
+/// can't be written by anyone else.
+fn is_private_file(path: &Path) -> bool {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        extern "C" {
+            fn geteuid() -> u32;
+        }
+        let uid = unsafe { geteuid() };
+        if metadata.uid() != uid || metadata.mode() & 0o022 != 0 {
+            return false;
+        }
+    }
+    metadata.file_type().is_file()
+}
+
+/// What a search for `tool`'s real executable depends on: the wrapper itself (its path, size
+/// and modification time, so an upgraded wrapper searches afresh), `override_exe` and the
+/// `search` settings. The candidates tried ahead of `PATH` are part of it with their stamps,
+/// so one that appears or changes later (a new sibling `light-real.exe`, say) wins over what
+/// an earlier search found.
+pub fn resolution_key(
+    tool: Tool,
+    current_exe: &Path,
+    override_exe: Option<&Path>,
+    search: &Search,
+) -> Option<String> {
+    let stamp = file_stamp(current_exe)?;
+    let own_dir = current_exe.parent().unwrap_or_else(|| Path::new("."));
+    let ahead: Vec<String> = override_exe
+        .map(Path::to_path_buf)
+        .into_iter()
+        .chain(search.preferred.clone())
+        .chain([
+            own_dir.join(tool.real_exe_name()),
+            own_dir.join(format!("{}-real", tool.name())),
+        ])
+        .map(|candidate| {
+            let stamp = file_stamp(&candidate).unwrap_or_else(|| "-".to_string());
+            format!("{}={stamp}", candidate.display())
+        })
+        .collect();
+    Some(format!(
+        "{}\t{}\t{stamp}\t{search:?}\t{}",
+        tool.name(),
+        current_exe.display(),
+        ahead.join("\t")
+    ))
+}
+
+/// `<size>:<modified ms>` of the file at `path`, which changes when it is replaced.
+fn file_stamp(path: &Path) -> Option<String> {
+    let metadata = fs::metadata(path).ok().filter(|m| m.is_file())?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?;
+    Some(format!("{}:{}", metadata.len(), modified.as_millis()))
+}
+
+/// Why the real tool at `path` looks like it can't be started, if it does: on Unix a file
+/// nobody may execute (say, unpacked from an archive that dropped the mode bits), on Windows
+/// one that isn't an `.exe`. Only a hint: starting it is still attempted.
+pub fn executable_problem(path: &Path) -> Option<String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(path).ok()?.permissions().mode();
+        (mode & 0o111 == 0).then(|| {
+            format!(
+                "'{}' is not executable (mode {:o}); fix it with chmod +x",
+                path.display(),
+                mode & 0o777
+            )
+        })
+    }
+    #[cfg(windows)]
+    {
+        let is_exe = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("exe"));
+        (!is_exe).then(|| {
+            format!(
+                "'{}' is not an .exe; point the wrapper at the real tool's executable",
+                path.display()
+            )
+        })
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// True when `path` is a copy of the wrapper (any build of it), not the WiX tool itself: it is
+/// byte for byte `current_exe`, or it carries `MARKER`.
+pub fn is_wrapper_copy(path: &Path, current_exe: &Path) -> io::Result<bool> {
+    let bytes = fs::read(path)?;
+    if fs::read(current_exe).is_ok_and(|own| own == bytes) {
+        return Ok(true);
+    }
+    Ok(bytes
+        .windows(MARKER.len())
+        .any(|window| window == MARKER.as_bytes()))
+}
+
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Exit code when the real tool's SHA-256 is none of the expected ones; shells use 126 for a
+/// command that was found but can't be run.
+pub const INTEGRITY_MISMATCH: i32 = 126;
+
+/// The code the wrapper exits with for the real tool's `status`. Exit codes pass through
+/// unchanged (on Windows that includes large values such as NTSTATUS codes); a Unix child
+/// killed by a signal maps to `128 + signal`, the way shells report it.
+pub fn exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    1
+}
+
+/// What `--wrapper-version` prints, e.g. `guimfinancial-light-wrapper 1.2.3`, followed by the
+/// marker so scripts can match on either.
+pub fn version_line(tool: Tool) -> String {
+    format!("guimfinancial-{}-wrapper {VERSION} ({MARKER})", tool.name())
+}
+
+/// The real tool invocation, marked with `WIX_LIGHT_WRAPPER_ACTIVE=1` and
+/// `LIGHT_WRAPPER_DEPTH=<depth + 1>` so a nested wrapper copy can detect the recursion. With
+/// `wine` set the tool is started through it, as `wine <real_exe> <args>`. Stdio is left for
+/// the caller to set up.
+pub fn build_command(
+    tool: Tool,
+    real_exe: &Path,
+    args: &[OsString],
+    wine: Option<&Path>,
+    depth: u32,
+) -> Command {
+    let mut command = match wine {
+        Some(wine) => {
+            let mut command = Command::new(wine);
+            command.arg(real_exe);
+            command
+        }
+        None => Command::new(real_exe),
+    };
+    command
+        .args(args)
+        .env(tool.var("WIX_LIGHT_WRAPPER_ACTIVE"), "1")
+        .env(tool.var("LIGHT_WRAPPER_DEPTH"), (depth + 1).to_string());
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn os_strings(items: &[&str]) -> Vec<OsString> {
+        items.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn injects_defaults_ahead_of_the_first_input() {
+        let args = build_args(
+            &os_strings(&["-out", "app.msi", "-ext", "WixUIExtension", "main.wixobj"]),
+            &[],
+            &["-sval", "-sacl"],
+            &[],
+            &[],
+        );
+        assert_eq!(
+            args,
+            os_strings(&[
+                "-out",
+                "app.msi",
+                "-ext",
+                "WixUIExtension",
+                "-sval",
+                "-sacl",
+                "main.wixobj"
+            ])
+        );
+        let args = build_args(
+            &os_strings(&[
+                "-out",
+                "lib\\app.msi",
+                "C:\\obj\\Setup.WixLib",
+                "main.wixobj",
+            ]),
+            &[],
+            &["-sval"],
+            &[],
+            &[],
+        );
+        assert_eq!(
+            args,
+            os_strings(&[
+                "-out",
+                "lib\\app.msi",
+                "-sval",
+                "C:\\obj\\Setup.WixLib",
+                "main.wixobj"
+            ])
+        );
+    }
+
+    #[test]
+    fn injects_defaults_in_front_without_a_recognizable_input() {
+        let args = build_args(
+            &os_strings(&["main.wixobj", "product.wixlib"]),
+            &[],
+            &["-sval", "-sacl"],
+            &[],
+            &[],
+        );
+        assert_eq!(
+            args,
+            os_strings(&["-sval", "-sacl", "main.wixobj", "product.wixlib"])
+        );
+        let args = build_args(
+            &os_strings(&["-out", "app.msi", "main.wxs"]),
+            &[],
+            &["-sval"],
+            &[],
+            &[],
+        );
+        assert_eq!(args, os_strings(&["-sval", "-out", "app.msi", "main.wxs"]));
+    }
+
+    #[test]
+    fn skips_flags_already_present() {
+        let args = build_args(
+            &os_strings(&["-sacl", "main.wixobj"]),
+            &[],
+            &["-sval", "-sacl"],
+            &[],
+            &[],
+        );
+        assert_eq!(args, os_strings(&["-sacl", "-sval", "main.wixobj"]));
+    }
+
+    #[test]
+    fn matches_flags_case_insensitively() {
+        let args = build_args(
+            &os_strings(&["-SVAL", "-SAcl", "main.wixobj"]),
+            &[],
+            &["-sval", "-sacl"],
+            &[],
+            &[],
+        );
+        assert_eq!(args, os_strings(&["-SVAL", "-SAcl", "main.wixobj"]));
+    }
+
+    #[test]
+    fn flag_matches_slash_form_and_mixed_case() {
+        assert!(flag_matches("/sval", "-sval"));
+        assert!(flag_matches("/SVal", "-sval"));
+        assert!(flag_matches("-SVAL", "/sval"));
+        assert!(!flag_matches("/svalx", "-sval"));
+        assert!(!flag_matches("-sacl", "-sval"));
+        assert!(!flag_matches("/", "-"));
+        assert!(!flag_matches("main.wixobj", "-main.wixobj"));
+        assert!(flag_matches("main.wixobj", "MAIN.wixobj"));
+    }
+
+    #[test]
+    fn flag_matches_attached_values() {
+        assert!(flag_matches("-cc:C:\\cab", "-cc:"));
+        assert!(flag_matches("/CC:cache", "-cc:"));
+        assert!(flag_matches("-cc:", "-cc:"));
+        assert!(!flag_matches("-c", "-cc:"));
+        assert!(flag_matches("-out", "-out"));
+        assert!(flag_matches("-out:app.msi", "/out"));
+        assert!(!flag_matches("-outdir", "-out"));
+        assert!(!flag_matches("-dName=app", "-d"));
+        assert!(!flag_matches("-dName=app", "-dOther="));
+        assert!(flag_matches("-dname=app", "-dName="));
+    }
+
+    #[test]
+    fn slash_flags_are_not_injected_again() {
+        let args = build_args(
+            &os_strings(&["/sval", "/SACL", "main.wixobj"]),
+            &[],
+            &["-sval", "-sacl", "-spdb"],
+            &[],
+            &[],
+        );
+        assert_eq!(
+            args,
+            os_strings(&["/sval", "/SACL", "-spdb", "main.wixobj"])
+        );
+    }
+
+    #[test]
+    fn finds_repeated_extension_and_input_arguments() {
+        let args = os_strings(&[
+            "-ext",
+            "WixUIExtension",
+            "main.wixobj",
+            "-loc",
+            "pt-BR.wxl",
+            "/EXT",
+            "wixuiextension",
+            "-ext",
+            "WixUtilExtension",
+            "Main.WIXOBJ",
+            "-b",
+            "main.wixobj",
+            "-b",
+            "main.wixobj",
+        ]);
+        assert_eq!(duplicate_args(&args), vec![5..7, 9..10, 12..14]);
+        assert!(duplicate_args(&os_strings(&["-ext", "A", "-loc", "A", "-ext"])).is_empty());
+    }
+
+    #[test]
+    fn does_not_repeat_duplicate_defaults() {
+        let args = build_args(
+            &os_strings(&["main.wixobj"]),
+            &[],
+            &["-sval", "-sw1076", "-SVAL"],
+            &[],
+            &[],
+        );
+        assert_eq!(args, os_strings(&["-sval", "-sw1076", "main.wixobj"]));
+    }
+
+    #[test]
+    fn appends_missing_flags_after_incoming() {
+        let args = build_args(
+            &os_strings(&["-cultures:pt-BR", "main.wixobj"]),
+            &[],
+            &[],
+            &[],
+            &["-CULTURES:PT-BR", "-spdb"],
+        );
+        assert_eq!(
+            args,
+            os_strings(&["-cultures:pt-BR", "main.wixobj", "-spdb"])
+        );
+    }
+
+    #[test]
+    fn flags_in_response_files_are_not_injected_again() {
+        let dir = crate::testutil::TempDir::new("wrapper-rsp");
+        let rsp = dir.join("light.rsp");
+        std::fs::write(&rsp, "-sval\r\n-spdb\r\nmain.wixobj\r\n").unwrap();
+        let incoming = os_strings(&[&format!("@{}", rsp.display())]);
+        let args = build_args(&incoming, &[], &["-sval", "-sacl"], &[], &["-spdb"]);
+        assert_eq!(args, [OsString::from("-sacl"), incoming[0].clone()]);
+
+        // Options ahead of a response file listing the inputs keep their place.
+        let incoming = os_strings(&["-out", "app.msi", &format!("@{}", rsp.display())]);
+        let args = build_args(&incoming, &[], &["-sval", "-sacl"], &[], &[]);
+        assert_eq!(
+            args,
+            [
+                incoming[0].clone(),
+                incoming[1].clone(),
+                OsString::from("-sacl"),
+                incoming[2].clone()
+            ]
+        );
+
+        // A response file of options only is no input; the flags go before what follows it.
+        let opts = dir.join("opts.rsp");
+        std::fs::write(&opts, "-cultures:pt-BR\r\n").unwrap();
+        let incoming = os_strings(&[&format!("@{}", opts.display()), "main.wixobj"]);
+        let args = build_args(&incoming, &[], &["-sval"], &[], &[]);
+        assert_eq!(
+            args,
+            [
+                incoming[0].clone(),
+                OsString::from("-sval"),
+                incoming[1].clone()
+            ]
+        );
+        assert_eq!(
+            std::fs::read_to_string(&rsp).unwrap(),
+            "-sval\r\n-spdb\r\nmain.wixobj\r\n"
+        );
+    }
+
+    #[test]
+    fn override_exe_replaces_sibling_lookup() {
+        let dir =
+            std::env::temp_dir().join(format!("light-wrapper-override-{}", std::process::id()));
+        let err = resolve_real_exe(
+            Tool::LIGHT,
+            &dir.join("light.exe"),
+            Some(&dir.join("custom.exe")),
+            &Search::default(),
+        )
+        .unwrap_err();
+        assert!(
+            matches!(&err, WrapperError::RealExeMissing(Tool::LIGHT, p) if p == &dir.join("custom.exe"))
+        );
+    }
+
+    #[test]
+    fn split_args_keeps_quoted_values_together() {
+        assert_eq!(
+            split_args(r#"-cultures:en-US -dFoo="a b" "-b" "C:\Program Files\WiX""#),
+            strings(&[
+                "-cultures:en-US",
+                "-dFoo=a b",
+                "-b",
+                "C:\\Program Files\\WiX"
+            ])
+        );
+        assert_eq!(
+            split_args(r#"-dQuote=\"x\" """#),
+            strings(&["-dQuote=\"x\"", ""])
+        );
+    }
+
+    #[test]
+    fn split_args_ignores_empty_and_surrounding_whitespace() {
+        assert!(split_args("").is_empty());
+        assert!(split_args(" \t\r\n").is_empty());
+        assert_eq!(
+            split_args("  -spdb\t -sval  \n"),
+            strings(&["-spdb", "-sval"])
+        );
+    }
+
+    #[test]
+    fn strip_args_removes_matches_case_insensitively() {
+        let mut args = os_strings(&["-SPDB", "-out", "app.msi", "-spdb", "main.wixobj"]);
+        let removed = strip_args(&mut args, &strings(&["-spdb", "-nologo"]));
+        assert_eq!(args, os_strings(&["-out", "app.msi", "main.wixobj"]));
+        assert_eq!(removed, os_strings(&["-SPDB", "-spdb"]));
+    }
+
+    #[test]
+    fn strip_args_takes_values_along_and_matches_prefixes() {
+        let mut args = os_strings(&[
+            "-cultures:en-US",
+            "/LOC",
+            "en-us.wxl",
+            "-loc:pt-br.wxl",
+            "-sw1076",
+            "-swall",
+            "-out",
+            "app.msi",
+            "main.wixobj",
+        ]);
+        let removed = strip_args(&mut args, &strings(&["-cultures:*", "-loc", "-sw1*"]));
+        assert_eq!(
+            args,
+            os_strings(&["-swall", "-out", "app.msi", "main.wixobj"])
+        );
+        assert_eq!(
+            removed,
+            os_strings(&[
+                "-cultures:en-US",
+                "/LOC",
+                "en-us.wxl",
+                "-loc:pt-br.wxl",
+                "-sw1076"
+            ])
+        );
+
+        // A value flag at the very end takes nothing with it; plain inputs match exactly.
+        let mut args = os_strings(&["main.wixobj", "extra.wixobj", "-ext"]);
+        let removed = strip_args(&mut args, &strings(&["extra.wixobj", "-ext", "main*"]));
+        assert!(args.is_empty());
+        assert_eq!(
+            removed,
+            os_strings(&["main.wixobj", "extra.wixobj", "-ext"])
+        );
+    }
+
+    #[test]
+    fn finds_the_output_path_in_every_spelling() {
+        let out = |args: &[&str]| output_path(&os_strings(args));
+        assert_eq!(
+            out(&["a.wixobj", "-out", "C:\\My Apps\\app.msi"]),
+            Some(PathBuf::from("C:\\My Apps\\app.msi"))
+        );
+        assert_eq!(out(&["-o", "app.msi"]), Some(PathBuf::from("app.msi")));
+        assert_eq!(out(&["/O:app.msi"]), Some(PathBuf::from("app.msi")));
+        assert_eq!(
+            out(&["-out:\"C:\\My Apps\\app.msi\""]),
+            Some(PathBuf::from("C:\\My Apps\\app.msi"))
+        );
+        assert_eq!(out(&["-outdir", "bin", "-ox"]), None);
+        assert_eq!(out(&["a.wixobj", "-out"]), None);
+
+        let dir = crate::testutil::TempDir::new("wrapper-out-rsp");
+        let rsp = dir.join("light.rsp");
+        std::fs::write(&rsp, "main.wixobj -out \"C:\\My Apps\\app.msi\"\r\n").unwrap();
+        assert_eq!(
+            out(&[&format!("@{}", rsp.display())]),
+            Some(PathBuf::from("C:\\My Apps\\app.msi"))
+        );
+    }
+
+    #[test]
+    fn finds_the_cab_cache_separate_or_attached() {
+        let cache = |args: &[&str]| cab_cache_path(&os_strings(args));
+        assert_eq!(
+            cache(&["-cc", "C:\\build\\cab cache", "-reusecab", "main.wixobj"]),
+            Some(PathBuf::from("C:\\build\\cab cache"))
+        );
+        assert_eq!(
+            cache(&["/CC:\"obj\\cabs\""]),
+            Some(PathBuf::from("obj\\cabs"))
+        );
+        assert_eq!(cache(&["-out", "app.msi", "-ccx", "main.wixobj"]), None);
+    }
+
+    #[test]
+    fn opaque_arguments_are_forwarded_but_never_scanned() {
+        let mut args = os_strings(&["-out", "app.msi", "--", "-sval-1a2b.wixobj", "--", "x"]);
+        let opaque = split_opaque(&mut args, None);
+        assert_eq!(args, os_strings(&["-out", "app.msi"]));
+        assert_eq!(opaque, os_strings(&["-sval-1a2b.wixobj", "--", "x"]));
+
+        // A flag-looking input after the marker is neither a passed flag nor an input.
+        assert_eq!(
+            build_args(&args, &opaque, &["-sval", "-sacl"], &[], &["-spdb"]),
+            os_strings(&[
+                "-sval",
+                "-sacl",
+                "-out",
+                "app.msi",
+                "-sval-1a2b.wixobj",
+                "--",
+                "x",
+                "-spdb"
+            ])
+        );
+        let mut args = os_strings(&["main.wixobj", "-sacl", "-sval"]);
+        let opaque = split_opaque(&mut args, Some(1));
+        assert_eq!(
+            build_args(&args, &opaque, &["-sval", "-sacl"], &[], &["-sacl"]),
+            os_strings(&["-sval", "-sacl", "main.wixobj", "-sacl", "-sval"])
+        );
+
+        // The earlier of the two wins; an index past the end leaves nothing opaque.
+        let mut args = os_strings(&["a.wixobj", "b.wixobj", "--", "c.wixobj"]);
+        assert_eq!(
+            split_opaque(&mut args, Some(1)),
+            os_strings(&["b.wixobj", "--", "c.wixobj"])
+        );
+        let mut args = os_strings(&["a.wixobj", "--", "b.wixobj", "c.wixobj"]);
+        assert_eq!(
+            split_opaque(&mut args, Some(3)),
+            os_strings(&["b.wixobj", "c.wixobj"])
+        );
+        assert_eq!(args, os_strings(&["a.wixobj"]));
+        let mut args = os_strings(&["a.wixobj"]);
+        assert!(split_opaque(&mut args, Some(5)).is_empty());
+        assert!(split_opaque(&mut args, None).is_empty());
+        assert_eq!(args, os_strings(&["a.wixobj"]));
+    }
+
+    #[test]
+    fn repeated_suppressions_collapse_to_the_first() {
+        let mut args = os_strings(&[
+            "-sval",
+            "-out",
+            "app.msi",
+            "/SVAL",
+            "-sacl",
+            "main.wixobj",
+            "-Sacl",
+            "-sval",
+        ]);
+        let found = normalize_suppressions(&mut args);
+        assert_eq!(
+            args,
+            os_strings(&["-sval", "-out", "app.msi", "-sacl", "main.wixobj"])
+        );
+        assert_eq!(found.dropped, os_strings(&["/SVAL", "-Sacl", "-sval"]));
+        assert!(found.redundant.is_empty());
+    }
+
+    #[test]
+    fn sice_next_to_sval_is_reported_but_kept() {
+        let mut args = os_strings(&["-sice:ICE03", "main.wixobj", "/sval", "-SICE:ice61"]);
+        let found = normalize_suppressions(&mut args);
+        assert_eq!(
+            args,
+            os_strings(&["-sice:ICE03", "main.wixobj", "/sval", "-SICE:ice61"])
+        );
+        assert!(found.dropped.is_empty());
+        assert_eq!(found.redundant, os_strings(&["-sice:ICE03", "-SICE:ice61"]));
+
+        // Without `-sval` the narrow flags are what skips those checks.
+        let mut args = os_strings(&["-sice:ICE03", "-sacl", "main.wixobj"]);
+        assert_eq!(normalize_suppressions(&mut args), Suppressions::default());
+        assert_eq!(args, os_strings(&["-sice:ICE03", "-sacl", "main.wixobj"]));
+    }
+
+    #[test]
+    fn display_command_quotes_only_when_needed() {
+        let line = display_command(
+            Path::new("C:\\WiX Toolset\\light-real.exe"),
+            &os_strings(&["-sval", "-dFoo=a b", "", "-dQ=\"x\""]),
+        );
+        assert_eq!(
+            line,
+            r#""C:\WiX Toolset\light-real.exe" -sval "-dFoo=a b" "" "-dQ=\"x\"""#
+        );
+    }
+
+    #[test]
+    fn suppress_flags_expands_comma_lists() {
+        assert_eq!(
+            suppress_flags(Tool::LIGHT.default_suppress()),
+            strings(&["-sval", "-sacl"])
+        );
+        assert_eq!(
+            suppress_flags(" sval, ,ice:ICE03,-sacl,"),
+            strings(&["-sval", "-ice:ICE03", "-sacl"])
+        );
+        assert!(suppress_flags("").is_empty());
+    }
+
+    #[test]
+    fn tool_is_selected_from_invocation_name() {
+        let cases = [
+            ("C:/WiX/bin/light.exe", Tool::LIGHT),
+            ("C:/WiX/bin/CANDLE.EXE", Tool::named("candle")),
+            ("/opt/wix/candle", Tool::named("candle")),
+            ("/opt/wix/light_wrapper", Tool::LIGHT),
+            ("C:/WiX/bin/heat.exe", Tool::named("heat")),
+            ("C:/tools/SignTool.exe", Tool::named("signtool")),
+        ];
+        for (exe, expected) in cases {
+            assert_eq!(Tool::from_exe(Path::new(exe)), expected, "{exe}");
+        }
+        assert_eq!(Tool::LIGHT.real_exe_name(), "light-real.exe");
+        assert_eq!(Tool::named("candle").real_exe_name(), "candle-real.exe");
+        let heat = Tool::named("heat");
+        assert_eq!(heat.real_exe_name(), "heat-real.exe");
+        assert!(heat.is_wix());
+        assert_eq!(
+            heat.var("WIX_LIGHT_WRAPPER_SUPPRESS"),
+            "WIX_HEAT_WRAPPER_SUPPRESS"
+        );
+        assert_eq!(heat.wrapper_file_name("toml"), "heat-wrapper.toml");
+    }
+
+    #[test]
+    fn other_tools_follow_the_naming_convention() {
+        let tool = Tool::from_exe(Path::new("C:/build/bar.exe"));
+        assert_eq!(tool, Tool::named("bar"));
+        assert!(!tool.is_wix());
+        assert_eq!(tool.exe_name(), "bar.exe");
+        assert_eq!(tool.real_exe_name(), "bar-real.exe");
+        assert_eq!(tool.wrapper_file_name("toml"), "bar-wrapper.toml");
+        assert_eq!(
+            tool.var("WIX_LIGHT_WRAPPER_SUPPRESS"),
+            "WIX_LIGHT_WRAPPER_SUPPRESS_BAR"
+        );
+        assert_eq!(
+            Tool::named("sign-tool").var("LIGHT_WRAPPER_VERBOSE"),
+            "LIGHT_WRAPPER_VERBOSE_SIGN_TOOL"
+        );
+        assert_eq!(tool.default_suppress(), "");
+        let err = resolve_real_exe(
+            tool,
+            Path::new("/nonexistent/bar.exe"),
+            None,
+            &Search::default(),
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().starts_with(
+                "could not find the real 'bar' tool; looked at:\n  '/nonexistent/bar-real.exe'"
+            ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn candle_resolves_its_own_real_exe_and_vars() {
+        let dir = std::env::temp_dir().join(format!("light-wrapper-candle-{}", std::process::id()));
+        let err = resolve_real_exe(
+            Tool::named("candle"),
+            &dir.join("candle.exe"),
+            None,
+            &Search::default(),
+        )
+        .unwrap_err();
+        assert!(
+            matches!(&err, WrapperError::RealExeNotFound(tool, r) if *tool == Tool::named("candle") && r[0].0 == dir.join("candle-real.exe"))
+        );
+        assert!(err.to_string().contains("real WiX compiler"));
+        assert_eq!(
+            Tool::named("candle").var("WIX_LIGHT_WRAPPER_SUPPRESS"),
+            "WIX_CANDLE_WRAPPER_SUPPRESS"
+        );
+        assert_eq!(Tool::LIGHT.var("LIGHT_REAL_EXE"), "LIGHT_REAL_EXE");
+        assert_eq!(Tool::named("candle").default_suppress(), "");
+    }
+
+    #[test]
+    fn refuses_to_resolve_to_itself() {
+        let dir = std::env::temp_dir().join(format!("light-wrapper-self-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let wrapper = dir.join("light.exe");
+        std::fs::write(&wrapper, b"").unwrap();
+        let err = resolve_real_exe(
+            Tool::LIGHT,
+            &wrapper,
+            Some(&dir.join(".").join("light.exe")),
+            &Search::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, WrapperError::InvokesItself(_)));
+        assert!(err.to_string().starts_with("refusing to invoke itself"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_to_resolve_to_another_wrapper_copy() {
+        let dir = crate::testutil::TempDir::new("resolve-wrapper-copy");
+        let wrapper = dir.join("light.exe");
+        std::fs::write(&wrapper, b"this build").unwrap();
+        let copy = dir.join("light-real.exe");
+        std::fs::write(&copy, format!("MZ older build {MARKER}")).unwrap();
+        for override_exe in [None, Some(copy.as_path())] {
+            let err = resolve_real_exe(Tool::LIGHT, &wrapper, override_exe, &Search::default())
+                .unwrap_err();
+            assert!(
+                matches!(&err, WrapperError::RealExeIsWrapper(Tool::LIGHT, p) if *p == copy),
+                "{err}"
+            );
+            assert!(err.to_string().contains("another copy of this wrapper"));
+        }
+        std::fs::write(&copy, b"MZ genuine light").unwrap();
+        assert_eq!(
+            resolve_real_exe(Tool::LIGHT, &wrapper, None, &Search::default()).unwrap(),
+            copy
+        );
+    }
+
+    #[test]
+    fn detects_ice_validation_failures() {
+        assert!(is_validation_failure(
+            "light.exe : error LGHT0217 : Error executing ICE action 'ICE01'."
+        ));
+        assert!(is_validation_failure(
+            "main.wxs(12) : error LGHT0204 : ICE03: Invalid format string"
+        ));
+        assert!(!is_validation_failure(
+            "warning LGHT1076 : ICE61: This product should remove only older versions"
+        ));
+        assert!(!is_validation_failure(
+            "error LGHT0103 : The system cannot find the file 'device.ico'"
+        ));
+        assert!(!is_validation_failure(""));
+    }
+
+    #[test]
+    fn detects_sharing_violations() {
+        assert!(is_sharing_violation(
+            "light.exe : error LGHT0001 : The process cannot access the file 'app.msi' because it is being used by another process."
+        ));
+        assert!(is_sharing_violation(
+            "error LGHT0001 : The process cannot access the file because another process has locked a portion of the file."
+        ));
+        assert!(is_sharing_violation(
+            "error LGHT0001 : (Exception from HRESULT: 0x80070020)"
+        ));
+        assert!(!is_sharing_violation(
+            "error LGHT0217 : Error executing ICE action 'ICE01'"
+        ));
+        assert!(!is_sharing_violation(""));
+    }
+
+    #[test]
+    fn display_tokens_prints_one_escaped_token_per_line() {
+        let out = display_tokens(
+            Path::new("C:/WiX/light-real.exe"),
+            &os_strings(&["-sacl", "", "a \"b\""]),
+        );
+        assert_eq!(
+            out,
+            "  \"C:/WiX/light-real.exe\"\n  \"-sacl\"\n  \"\"\n  \"a \\\"b\\\"\"\n"
+        );
+    }
+
+    #[test]
+    fn sice_flags_target_individual_checks() {
+        assert_eq!(
+            sice_flags(&[" ice61", "ICE69", ""]),
+            strings(&["-sice:ICE61", "-sice:ICE69"])
+        );
+        let args = build_args(
+            &os_strings(&["-SICE:ice61", "main.wixobj"]),
+            &[],
+            &["-sice:ICE61", "-sice:ICE69"],
+            &[],
+            &[],
+        );
+        assert_eq!(
+            args,
+            os_strings(&["-SICE:ice61", "-sice:ICE69", "main.wixobj"])
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn unpaired_surrogates_are_forwarded_untouched() {
+        use std::os::windows::ffi::OsStringExt;
+        // `C:\Temp\<lone high surrogate>.wixobj`, which no `String` can hold.
+        let mut wide: Vec<u16> = "C:\\Temp\\".encode_utf16().collect();
+        wide.push(0xD800);
+        wide.extend(".wixobj".encode_utf16());
+        let odd = OsString::from_wide(&wide);
+        assert!(odd.to_str().is_none());
+
+        let incoming = vec![OsString::from("-SVAL"), odd.clone()];
+        let args = build_args(&incoming, &[], &["-sval", "-sacl"], &[], &[]);
+        assert_eq!(
+            args,
+            [
+                OsString::from("-SVAL"),
+                OsString::from("-sacl"),
+                odd.clone()
+            ]
+        );
+        assert!(!has_flag("-sval", std::slice::from_ref(&odd)));
+        assert!(duplicate_args(&[odd.clone(), odd.clone()]).is_empty());
+
+        let command = build_command(Tool::LIGHT, Path::new("light-real.exe"), &args, None, 0);
+        assert_eq!(command.get_args().last(), Some(odd.as_os_str()));
+    }
+
+    #[test]
+    fn build_command_can_go_through_wine() {
+        let command = build_command(
+            Tool::LIGHT,
+            Path::new("wix/light-real.exe"),
+            &os_strings(&["-sval", "main.wixobj"]),
+            Some(Path::new("wine64")),
+            0,
+        );
+        assert_eq!(command.get_program(), "wine64");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            ["wix/light-real.exe", "-sval", "main.wixobj"]
+        );
+    }
+
+    #[test]
+    fn build_command_forwards_args_and_marks_the_child() {
+        let command = build_command(
+            Tool::named("candle"),
+            Path::new("wix/candle-real.exe"),
+            &os_strings(&["-arch", "x64", "main.wxs"]),
+            None,
+            1,
+        );
+        assert_eq!(command.get_program(), "wix/candle-real.exe");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            ["-arch", "x64", "main.wxs"]
+        );
+        let envs: Vec<_> = command.get_envs().collect();
+        assert_eq!(
+            envs,
+            [
+                (
+                    std::ffi::OsStr::new("CANDLE_WRAPPER_DEPTH"),
+                    Some(std::ffi::OsStr::new("2"))
+                ),
+                (
+                    std::ffi::OsStr::new("WIX_CANDLE_WRAPPER_ACTIVE"),
+                    Some(std::ffi::OsStr::new("1"))
+                )
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exit_codes_and_signals_are_propagated() {
+        let status = |script: &str| {
+            std::process::Command::new("/bin/sh")
+                .arg("-c")
+                .arg(script)
+                .status()
+                .unwrap()
+        };
+        assert_eq!(exit_code(status("exit 0")), 0);
+        assert_eq!(exit_code(status("exit 217")), 217);
+        assert_eq!(exit_code(status("kill -TERM $$")), 128 + 15);
+        assert_eq!(exit_code(status("kill -KILL $$")), 128 + 9);
+    }
+
+    #[test]
+    fn version_line_names_the_tool_and_carries_the_marker() {
+        let line = version_line(Tool::LIGHT);
+        assert!(
+            line.starts_with(&format!("guimfinancial-light-wrapper {VERSION} ")),
+            "{line}"
+        );
+        assert!(line.contains(MARKER), "{line}");
+        assert!(!line.contains("unknown"), "{line}");
+        assert!(
+            VERSION.split('.').all(|part| part.parse::<u32>().is_ok()),
+            "{VERSION}"
+        );
+        assert!(version_line(Tool::named("candle")).starts_with("guimfinancial-candle-wrapper "));
+    }
+
+    #[test]
+    fn missing_real_exe_is_reported_with_its_path() {
+        let dir =
+            std::env::temp_dir().join(format!("light-wrapper-missing-{}", std::process::id()));
+        let err = resolve_real_exe(
+            Tool::LIGHT,
+            &dir.join("light.exe"),
+            None,
+            &Search::default(),
+        )
+        .unwrap_err();
+        assert!(
+            matches!(&err, WrapperError::RealExeNotFound(Tool::LIGHT, r) if r.len() == if cfg!(windows) { 1 } else { 2 } && r[0].0 == dir.join("light-real.exe"))
+        );
+        assert!(err.to_string().contains("light-real.exe"));
+    }
+
+    #[test]
+    fn resolves_sibling_real_exe() {
+        let dir =
+            std::env::temp_dir().join(format!("light-wrapper-sibling-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("light-real.exe"), b"").unwrap();
+        let resolved = resolve_real_exe(
+            Tool::LIGHT,
+            &dir.join("light.exe"),
+            None,
+            &Search::default(),
+        )
+        .unwrap();
+        assert_eq!(resolved, dir.join("light-real.exe"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn accepts_a_real_tool_without_extension() {
+        let dir = crate::testutil::TempDir::new("wrapper-no-extension");
+        std::fs::write(dir.join("light-real"), b"").unwrap();
+        let wrapper = dir.join("light");
+        let resolve = || resolve_real_exe(Tool::LIGHT, &wrapper, None, &Search::default());
+        assert_eq!(resolve().unwrap(), dir.join("light-real"));
+        std::fs::write(dir.join("light-real.exe"), b"").unwrap();
+        assert_eq!(resolve().unwrap(), dir.join("light-real.exe"));
+    }
+
+    #[test]
+    fn searches_wix_bin_then_path_after_the_sibling() {
+        let root = crate::testutil::TempDir::new("wrapper-search");
+        let wrapper_dir = root.join("wrapper");
+        let wix_bin = root.join("wix").join("bin");
+        let path_dir = root.join("path");
+        let cache_dir = root.join("cache");
+        for dir in [&wrapper_dir, &wix_bin, &path_dir, &cache_dir] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        let wrapper = wrapper_dir.join("light.exe");
+        std::fs::write(&wrapper, b"wrapper").unwrap();
+        std::fs::write(path_dir.join("light.exe"), b"").unwrap();
+        let mut search = Search {
+            preferred: Some(root.join("missing").join("light.exe")),
+            wix_bin: Some(wix_bin.clone()),
+            path: vec![wrapper_dir.clone(), path_dir.clone(), cache_dir.clone()],
+        };
+        let resolve = |search: &Search| resolve_real_exe(Tool::LIGHT, &wrapper, None, search);
+
+        // The wrapper's own directory is on PATH: it is skipped, as is the empty WiX dir, and
+        // a preferred path that doesn't exist falls through to the search.
+        assert_eq!(resolve(&search).unwrap(), path_dir.join("light.exe"));
+
+        std::fs::write(wix_bin.join("light.exe"), b"").unwrap();
+        assert_eq!(resolve(&search).unwrap(), wix_bin.join("light.exe"));
+
+        std::fs::write(cache_dir.join("light-real.exe"), b"").unwrap();
+        assert_eq!(resolve(&search).unwrap(), cache_dir.join("light-real.exe"));
+
+        std::fs::write(wrapper_dir.join("light-real.exe"), b"").unwrap();
+        assert_eq!(
+            resolve(&search).unwrap(),
+            wrapper_dir.join("light-real.exe")
+        );
+
+        search.preferred = Some(path_dir.join("light.exe"));
+        assert_eq!(resolve(&search).unwrap(), path_dir.join("light.exe"));
+    }
+
+    #[test]
+    fn resolved_real_exe_is_cached_until_something_changes() {
+        let dir = crate::testutil::TempDir::new("wrapper-resolve-cache");
+        let cache = dir.join("light-wrapper.resolved");
+        let wrapper = dir.join("light.exe");
+        fs::write(&wrapper, "wrapper").unwrap();
+        let real = dir.join("light-real.exe");
+        fs::write(&real, "real").unwrap();
+        let searches = std::cell::Cell::new(0);
+        let search = || {
+            searches.set(searches.get() + 1);
+            Ok(real.clone())
+        };
+        let resolve = |key: &str, search| resolve_cached(&cache, key, &wrapper, search);
+        let found = |version: Option<&str>| Resolved {
+            real_exe: real.clone(),
+            version: version.map(str::to_string),
+        };
+
+        assert_eq!(resolve("key", &search).unwrap(), found(None));
+        assert_eq!(resolve("key", &search).unwrap(), found(None));
+        assert_eq!(searches.get(), 1, "the second run reads the cache");
+        record_version(&cache, "key", &real, "3.11.2.4516");
+        assert_eq!(resolve("key", &search).unwrap(), found(Some("3.11.2.4516")));
+        assert_eq!(searches.get(), 1);
+
+        // Other settings, or a replaced real tool, search again.
+        resolve("other key", &search).unwrap();
+        assert_eq!(searches.get(), 2);
+        fs::write(&real, "a newer real tool").unwrap();
+        resolve("other key", &search).unwrap();
+        assert_eq!(searches.get(), 3);
+        fs::remove_file(&real).unwrap();
+        resolve("other key", &search).unwrap();
+        assert_eq!(searches.get(), 4);
+
+        // A failed search is passed on and not remembered.
+        fs::write(&real, "real").unwrap();
+        let failing = || Err(WrapperError::RealExeNotFound(Tool::LIGHT, Vec::new()));
+        assert!(resolve_cached(&cache, "failing", &wrapper, failing).is_err());
+        resolve("failing", &search).unwrap();
+        assert_eq!(searches.get(), 5);
+
+        // A remembered tool that has since become a wrapper copy is searched for again.
+        fs::write(&real, MARKER).unwrap();
+        resolve("failing", &search).unwrap();
+        resolve("failing", &search).unwrap();
+        assert_eq!(searches.get(), 7);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cache_records_others_could_write_are_ignored() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = crate::testutil::TempDir::new("wrapper-resolve-cache-mode");
+        let cache = dir.join("light-wrapper.resolved");
+        let wrapper = dir.join("light.exe");
+        fs::write(&wrapper, "wrapper").unwrap();
+        let real = dir.join("light-real.exe");
+        fs::write(&real, "real").unwrap();
+        let searches = std::cell::Cell::new(0);
+        let search = || {
+            searches.set(searches.get() + 1);
+            Ok(real.clone())
+        };
+
+        resolve_cached(&cache, "key", &wrapper, search).unwrap();
+        let mode = fs::metadata(&cache).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        fs::set_permissions(&cache, fs::Permissions::from_mode(0o666)).unwrap();
+        resolve_cached(&cache, "key", &wrapper, search).unwrap();
+        assert_eq!(searches.get(), 2);
+
+        // A link planted under the record's name is replaced, not followed.
+        let elsewhere = dir.join("elsewhere");
+        fs::write(&elsewhere, "untouched").unwrap();
+        fs::remove_file(&cache).unwrap();
+        std::os::unix::fs::symlink(&elsewhere, &cache).unwrap();
+        resolve_cached(&cache, "key", &wrapper, search).unwrap();
+        assert_eq!(searches.get(), 3);
+        assert_eq!(fs::read_to_string(&elsewhere).unwrap(), "untouched");
+        assert!(!fs::symlink_metadata(&cache)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+    }
+
+    #[test]
+    fn resolution_key_follows_the_search_settings() {
+        let dir = crate::testutil::TempDir::new("wrapper-resolution-key");
+        let wrapper = dir.join("light.exe");
+        fs::write(&wrapper, "wrapper").unwrap();
+        let search = Search {
+            path: vec![PathBuf::from("/usr/bin")],
+            ..Search::default()
+        };
+        let key = |current_exe: &Path, override_exe, search: &Search| {
+            resolution_key(Tool::LIGHT, current_exe, override_exe, search)
+        };
+        let first = key(&wrapper, None, &search).unwrap();
+        assert_eq!(key(&wrapper, None, &search), Some(first.clone()));
+        let other = Search {
+            preferred: Some(PathBuf::from("C:/WiX/light.exe")),
+            path: search.path.clone(),
+            ..Search::default()
+        };
+        assert_ne!(key(&wrapper, None, &other), Some(first.clone()));
+        assert_ne!(
+            resolution_key(Tool::named("candle"), &wrapper, None, &Search::default()),
+            Some(first.clone())
+        );
+        assert_ne!(
+            key(&wrapper, Some(Path::new("C:/WiX/light.exe")), &search),
+            Some(first.clone())
+        );
+        assert_eq!(key(&dir.join("missing.exe"), None, &other), None);
+
+        // A sibling appearing beside the wrapper changes the key.
+        fs::write(dir.join("light-real.exe"), "real").unwrap();
+        assert_ne!(key(&wrapper, None, &search), Some(first));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn real_tool_without_execute_permission_is_reported() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = crate::testutil::TempDir::new("wrapper-not-executable");
+        let real = dir.join("light-real.exe");
+        fs::write(&real, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&real, fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(
+            executable_problem(&real),
+            Some(format!(
+                "'{}' is not executable (mode 644); fix it with chmod +x",
+                real.display()
+            ))
+        );
+        fs::set_permissions(&real, fs::Permissions::from_mode(0o744)).unwrap();
+        assert_eq!(executable_problem(&real), None);
+        assert_eq!(executable_problem(&dir.join("missing")), None);
+    }
+
+    #[test]
+    fn not_found_lists_every_rejected_candidate() {
+        let root = crate::testutil::TempDir::new("wrapper-not-found");
+        let wrapper = root.join("light.exe");
+        std::fs::write(&wrapper, b"").unwrap();
+        let err = resolve_real_exe(
+            Tool::LIGHT,
+            &wrapper,
+            None,
+            &Search {
+                preferred: None,
+                wix_bin: Some(root.join("wix-bin")),
+                path: vec![root.join(".")],
+            },
+        )
+        .unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.starts_with("could not find the real WiX linker"),
+            "{message}"
+        );
+        assert!(
+            message.contains(&format!(
+                "'{}' (does not exist)",
+                root.join("light-real.exe").display()
+            )),
+            "{message}"
+        );
+        assert!(
+            message.contains(&format!(
+                "'{}' (does not exist)",
+                root.join("wix-bin").join("light.exe").display()
+            )),
+            "{message}"
+        );
+        assert!(
+            message.contains(&format!(
+                "'{}' (is the wrapper executable)",
+                root.join(".").join("light.exe").display()
+            )),
+            "{message}"
+        );
+    }
+}